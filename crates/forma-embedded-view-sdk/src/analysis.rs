@@ -1,6 +1,9 @@
+use crate::client::Client;
+use crate::host::HostValue;
+use crate::options::call_options;
 use crate::types::*;
-use crate::Result;
-use wasm_bindgen_futures::JsFuture;
+use crate::{clock, ErrorDetails, Result, SdkError};
+use std::time::Duration;
 
 pub(crate) mod js {
     use wasm_bindgen::prelude::*;
@@ -27,66 +30,595 @@ pub(crate) mod js {
         #[wasm_bindgen(method, js_name = "getGroundGrid")]
         pub fn get_ground_grid(this: &AnalysisApi, request: &JsValue) -> ::js_sys::Promise;
     }
+
+    impl AnalysisApi {
+        pub(crate) fn dispatch(&self, method: &str, args: &[JsValue]) -> Option<::js_sys::Promise> {
+            Some(match method {
+                "list" => self.list(&args[0]),
+                "triggerNoise" => self.trigger_noise(&args[0]),
+                "triggerSun" => self.trigger_sun(&args[0]),
+                "getSunAnalysis" => self.get_sun_analysis(&args[0]),
+                "getNoiseAnalysis" => self.get_noise_analysis(&args[0]),
+                "getGroundGrid" => self.get_ground_grid(&args[0]),
+                _ => return None,
+            })
+        }
+    }
 }
 
 /// Interact with Forma's native analysis functionality (sun, noise, wind).
 pub struct AnalysisApi {
-    inner: js::AnalysisApi,
+    client: Client,
 }
 
+call_options!(AnalysisApi);
+
 impl AnalysisApi {
-    pub(crate) fn from_raw(raw: js::AnalysisApi) -> Self {
-        Self { inner: raw }
+    pub(crate) fn new(client: Client) -> Self {
+        Self { client }
     }
 
     /// Fetch analysis records connected to the currently open proposal.
-    pub async fn list(&self, request: &AnalysisListRequest) -> Result<serde_json::Value> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.list(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+    pub async fn list(&self, request: &AnalysisListRequest) -> Result<Vec<AnalysisRecord>> {
+        let result = self
+            .client
+            .call("list", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("list", result)
+    }
+
+    /// Fetch analysis records connected to the currently open proposal as
+    /// untyped JSON.
+    pub async fn list_raw(&self, request: &AnalysisListRequest) -> Result<serde_json::Value> {
+        let result = self
+            .client
+            .call("list", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("list", result)
     }
 
     /// Trigger a noise analysis based on traffic data connected to roads and railways.
-    pub async fn trigger_noise(&self, request: &TriggerNoiseRequest) -> Result<serde_json::Value> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.trigger_noise(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+    pub async fn trigger_noise(&self, request: &TriggerNoiseRequest) -> Result<AnalysisRecord> {
+        let result = self
+            .client
+            .call("triggerNoise", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("triggerNoise", result)
     }
 
     /// Trigger a sun analysis for a specific day of the year.
-    pub async fn trigger_sun(&self, request: &TriggerSunRequest) -> Result<serde_json::Value> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.trigger_sun(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+    pub async fn trigger_sun(&self, request: &TriggerSunRequest) -> Result<AnalysisRecord> {
+        let result = self
+            .client
+            .call("triggerSun", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("triggerSun", result)
     }
 
     /// Fetch a specific sun analysis.
-    pub async fn get_sun_analysis(
-        &self,
-        request: &GetAnalysisRequest,
-    ) -> Result<serde_json::Value> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.get_sun_analysis(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+    pub async fn get_sun_analysis(&self, request: &GetAnalysisRequest) -> Result<AnalysisRecord> {
+        let result = self
+            .client
+            .call("getSunAnalysis", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("getSunAnalysis", result)
     }
 
     /// Fetch a specific noise analysis.
-    pub async fn get_noise_analysis(
+    pub async fn get_noise_analysis(&self, request: &GetAnalysisRequest) -> Result<AnalysisRecord> {
+        let result = self
+            .client
+            .call("getNoiseAnalysis", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("getNoiseAnalysis", result)
+    }
+
+    /// Fetch the current state of `record`.
+    ///
+    /// Sun and noise analyses are fetched by id; other types are looked up in
+    /// [`list`](Self::list).
+    pub async fn refresh(&self, record: &AnalysisRecord) -> Result<AnalysisRecord> {
+        let request = GetAnalysisRequest {
+            id: record.id.clone(),
+        };
+        match record.analysis_type {
+            AnalysisType::Sun => self.get_sun_analysis(&request).await,
+            AnalysisType::Noise => self.get_noise_analysis(&request).await,
+            AnalysisType::Other(_) => self
+                .list(&AnalysisListRequest::default())
+                .await?
+                .into_iter()
+                .find(|candidate| candidate.id == record.id)
+                .ok_or_else(|| {
+                    let err = SdkError::NotFound(ErrorDetails::new(format!(
+                        "analysis not found: {}",
+                        record.id
+                    )));
+                    self.client.in_method("list", err)
+                }),
+        }
+    }
+
+    /// Poll `record` every `poll_interval` until it is
+    /// [done](AnalysisStatus::is_done), calling `on_progress` with every
+    /// fetched state, and return the final one. A failed analysis is returned
+    /// as `Ok`; check its [`status`](AnalysisRecord::status).
+    ///
+    /// Fails with [`SdkError::Timeout`] if the analysis is not done within
+    /// `max_wait`, and with [`SdkError::Decode`] if a fetched record has no
+    /// status. Waiting honours the handle's cancellation token, and each poll
+    /// its timeout and retry policy:
+    ///
+    /// ```no_run
+    /// use forma_embedded_view_sdk::forma;
+    /// use forma_embedded_view_sdk::types::TriggerSunRequest;
+    /// use std::time::Duration;
+    ///
+    /// # async fn run() -> forma_embedded_view_sdk::Result<()> {
+    /// let analysis = forma().analysis();
+    /// let record = analysis
+    ///     .trigger_sun(&TriggerSunRequest { date: "2026-06-21T12:00:00Z".into() })
+    ///     .await?;
+    /// let done = analysis
+    ///     .await_completion(
+    ///         &record,
+    ///         Duration::from_secs(2),
+    ///         Duration::from_secs(600),
+    ///         |record| println!("{:?} {:?}", record.status, record.progress),
+    ///     )
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn await_completion(
         &self,
-        request: &GetAnalysisRequest,
-    ) -> Result<serde_json::Value> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.get_noise_analysis(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        record: &AnalysisRecord,
+        poll_interval: Duration,
+        max_wait: Duration,
+        mut on_progress: impl FnMut(&AnalysisRecord),
+    ) -> Result<AnalysisRecord> {
+        let deadline = clock::current().now_ms() + max_wait.as_secs_f64() * 1000.0;
+        let mut current = record.clone();
+        loop {
+            match &current.status {
+                Some(status) if status.is_done() => return Ok(current),
+                Some(_) => {}
+                None => {
+                    return Err(self.client.unexpected(
+                        "awaitCompletion",
+                        "an analysis record with a status",
+                        HostValue::json(&current)?,
+                    ))
+                }
+            }
+            let remaining = deadline - clock::current().now_ms();
+            if remaining <= 0.0 {
+                return Err(SdkError::Timeout(ErrorDetails::new(format!(
+                    "analysis {} not done within {} ms",
+                    current.id,
+                    max_wait.as_millis()
+                ))));
+            }
+            let delay = poll_interval.min(Duration::from_secs_f64(remaining / 1000.0));
+            self.client.sleep(delay).await?;
+            current = self.refresh(&current).await?;
+            on_progress(&current);
+        }
     }
 
     /// Fetch ground grid result for a sun or noise analysis.
-    pub async fn get_ground_grid(
+    pub async fn get_ground_grid(&self, request: &GetGroundGridRequest) -> Result<GroundGrid> {
+        let result = self
+            .client
+            .call("getGroundGrid", vec![HostValue::json(request)?])
+            .await?;
+        GroundGrid::decode(&self.client, "getGroundGrid", result)
+    }
+
+    /// Fetch ground grid result for a sun or noise analysis as untyped JSON.
+    pub async fn get_ground_grid_raw(
         &self,
         request: &GetGroundGridRequest,
     ) -> Result<serde_json::Value> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.get_ground_grid(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self
+            .client
+            .call("getGroundGrid", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("getGroundGrid", result)
+    }
+}
+
+/// Layout of a ground-level raster: `width` x `height` square cells.
+///
+/// Cell `(col, row)` covers `x0 + col * resolution .. x0 + (col + 1) * resolution`
+/// horizontally and likewise from `y0` vertically, in project coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridGeometry {
+    pub width: usize,
+    pub height: usize,
+    /// Project coordinates of the corner of cell `(0, 0)`.
+    pub x0: f64,
+    pub y0: f64,
+    /// Cell size in metres.
+    pub resolution: f64,
+}
+
+impl GridGeometry {
+    /// Number of cells, `width * height`.
+    pub fn cell_count(&self) -> usize {
+        self.width * self.height
+    }
+
+    /// Cell containing the project coordinates `(x, y)`.
+    pub fn cell_at(&self, x: f64, y: f64) -> Option<(usize, usize)> {
+        let col = ((x - self.x0) / self.resolution).floor();
+        let row = ((y - self.y0) / self.resolution).floor();
+        let in_range = |index: f64, len: usize| index >= 0.0 && index < len as f64;
+        (in_range(col, self.width) && in_range(row, self.height))
+            .then_some((col as usize, row as usize))
+    }
+
+    /// Project coordinates of the centre of cell `(col, row)`.
+    pub fn cell_center(&self, col: usize, row: usize) -> (f64, f64) {
+        (
+            self.x0 + (col as f64 + 0.5) * self.resolution,
+            self.y0 + (row as f64 + 0.5) * self.resolution,
+        )
+    }
+
+    /// Placement of a ground texture covering the grid.
+    pub fn placement(&self) -> GroundTexturePlacement {
+        let size = [
+            self.width as f64 * self.resolution,
+            self.height as f64 * self.resolution,
+        ];
+        GroundTexturePlacement {
+            center: [self.x0 + size[0] / 2.0, self.y0 + size[1] / 2.0],
+            size,
+            rotation: 0.0,
+        }
+    }
+
+    /// Render one pixel per cell, colored by `pixel` from the row-major cell
+    /// index. Cells without a color are transparent. Fails for an empty grid.
+    pub(crate) fn render(&self, pixel: impl Fn(usize) -> Option<[u8; 4]>) -> Result<RgbaImage> {
+        let mut image = RgbaImage::filled(self.width as u32, self.height as u32, [0; 4])?;
+        for index in 0..self.cell_count() {
+            if let Some(rgba) = pixel(index) {
+                let (col, row) = (index % self.width, index / self.width);
+                // Image rows run from the top, grid rows from `y0` upwards.
+                image.set_pixel(col as u32, (self.height - 1 - row) as u32, rgba);
+            }
+        }
+        Ok(image)
+    }
+}
+
+/// Ground-level raster of a sun or noise analysis (sun hours, dB). Cells
+/// outside the analysed area are masked out and have no value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroundGrid {
+    /// Row-major values, one per cell of `geometry`.
+    pub values: Vec<f32>,
+    /// Whether each cell holds a value, one per cell of `geometry`.
+    pub mask: Vec<bool>,
+    pub geometry: GridGeometry,
+}
+
+/// Summary statistics over the unmasked cells of a [`GroundGrid`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridStats {
+    pub count: usize,
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+}
+
+impl GroundGrid {
+    /// Decode the `{ grid, mask, width, height, x0, y0, resolution }` object
+    /// returned by `method`. Values of another shape fail with
+    /// [`SdkError::Decode`].
+    pub(crate) fn decode(
+        client: &Client,
+        method: &'static str,
+        mut value: HostValue,
+    ) -> Result<Self> {
+        let values = match value.take("grid") {
+            HostValue::Floats(values) => values,
+            other => client.expect(method, "a grid of floats", other)?,
+        };
+        let mask = value.take("mask");
+        let width = dimension(client, method, value.take("width"), "a grid width")?;
+        let height = dimension(client, method, value.take("height"), "a grid height")?;
+        let Some(len) = width.checked_mul(height) else {
+            return Err(client.unexpected(
+                method,
+                "a grid of addressable size",
+                HostValue::Json(format!("{width}x{height}").into()),
+            ));
+        };
+        if values.len() != len {
+            return Err(client.unexpected(
+                method,
+                &format!("{len} grid values for {width}x{height} cells"),
+                HostValue::Json(values.len().into()),
+            ));
+        }
+        let mask = if mask.is_nullish() {
+            vec![true; len]
+        } else {
+            let mask = match mask {
+                HostValue::Bytes(mask) | HostValue::Buffer(mask) => mask,
+                other => client.expect::<Vec<u8>>(method, "a grid mask of bytes", other)?,
+            };
+            if mask.len() != len {
+                return Err(client.unexpected(
+                    method,
+                    &format!("{len} mask entries for {width}x{height} cells"),
+                    HostValue::Json(mask.len().into()),
+                ));
+            }
+            mask.into_iter().map(|cell| cell != 0).collect()
+        };
+        let mask = mask
+            .into_iter()
+            .zip(&values)
+            .map(|(valid, value)| valid && !value.is_nan())
+            .collect();
+        Ok(Self {
+            values,
+            mask,
+            geometry: GridGeometry {
+                width,
+                height,
+                x0: client.expect(method, "a grid origin", value.take("x0"))?,
+                y0: client.expect(method, "a grid origin", value.take("y0"))?,
+                resolution: client.expect(method, "a grid resolution", value.take("resolution"))?,
+            },
+        })
+    }
+
+    /// Value of cell `(col, row)`, or `None` if it is masked or out of range.
+    pub fn get(&self, col: usize, row: usize) -> Option<f32> {
+        let GridGeometry { width, height, .. } = self.geometry;
+        if col >= width || row >= height {
+            return None;
+        }
+        let index = row * width + col;
+        self.mask[index].then(|| self.values[index])
+    }
+
+    /// Value of the cell containing `(x, y)`.
+    pub fn value_at(&self, x: f64, y: f64) -> Option<f32> {
+        let (col, row) = self.geometry.cell_at(x, y)?;
+        self.get(col, row)
+    }
+
+    /// Bilinear interpolation between the centres of the four cells around
+    /// `(x, y)`. Masked neighbours are left out and the remaining weights
+    /// renormalized; `None` if all of them are masked.
+    pub fn sample(&self, x: f64, y: f64) -> Option<f32> {
+        let geometry = &self.geometry;
+        let fx = (x - geometry.x0) / geometry.resolution - 0.5;
+        let fy = (y - geometry.y0) / geometry.resolution - 0.5;
+        let (col, row) = (fx.floor(), fy.floor());
+        let (tx, ty) = (fx - col, fy - row);
+        let mut sum = 0.0;
+        let mut weights = 0.0;
+        for (dc, dr, weight) in [
+            (0.0, 0.0, (1.0 - tx) * (1.0 - ty)),
+            (1.0, 0.0, tx * (1.0 - ty)),
+            (0.0, 1.0, (1.0 - tx) * ty),
+            (1.0, 1.0, tx * ty),
+        ] {
+            let (c, r) = (col + dc, row + dr);
+            if weight <= 0.0 || c < 0.0 || r < 0.0 {
+                continue;
+            }
+            if let Some(value) = self.get(c as usize, r as usize) {
+                sum += value as f64 * weight;
+                weights += weight;
+            }
+        }
+        (weights > 0.0).then(|| (sum / weights) as f32)
+    }
+
+    /// Unmasked cells as `(col, row, value)`, row by row.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize, f32)> + '_ {
+        self.values
+            .iter()
+            .zip(&self.mask)
+            .enumerate()
+            .filter(|(_, (_, valid))| **valid)
+            .map(|(index, (value, _))| {
+                let width = self.geometry.width;
+                (index % width, index / width, *value)
+            })
+    }
+
+    /// Count, minimum, maximum and mean of the unmasked cells, or `None` if
+    /// every cell is masked.
+    pub fn stats(&self) -> Option<GridStats> {
+        let mut stats: Option<GridStats> = None;
+        let mut sum = 0.0f64;
+        for (_, _, value) in self.iter() {
+            sum += value as f64;
+            let stats = stats.get_or_insert(GridStats {
+                count: 0,
+                min: value,
+                max: value,
+                mean: 0.0,
+            });
+            stats.count += 1;
+            stats.min = stats.min.min(value);
+            stats.max = stats.max.max(value);
+        }
+        stats.map(|stats| GridStats {
+            mean: (sum / stats.count as f64) as f32,
+            ..stats
+        })
+    }
+
+    /// Value below which `p` percent of the unmasked cells fall (nearest rank).
+    pub fn percentile(&self, p: f64) -> Option<f32> {
+        let mut values: Vec<f32> = self.iter().map(|(_, _, value)| value).collect();
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_by(f32::total_cmp);
+        let rank = (p.clamp(0.0, 100.0) / 100.0 * values.len() as f64).ceil() as usize;
+        Some(values[rank.saturating_sub(1)])
+    }
+
+    /// Render the grid as an image for a ground texture, one pixel per cell,
+    /// coloring each unmasked value with `color`. Masked cells are
+    /// transparent. Pair with [`placement`](Self::placement). Fails for an
+    /// empty grid.
+    pub fn to_image(&self, color: impl Fn(f32) -> [u8; 4]) -> Result<RgbaImage> {
+        self.geometry
+            .render(|index| self.mask[index].then(|| color(self.values[index])))
+    }
+
+    /// Placement of a ground texture covering the grid.
+    pub fn placement(&self) -> GroundTexturePlacement {
+        self.geometry.placement()
+    }
+
+    /// Number of unmasked cells in each bin delimited by the ascending
+    /// `edges`: bin `i` counts values in `edges[i]..edges[i + 1]`, the last
+    /// bin includes its upper edge. Values outside the edges are not counted.
+    pub fn histogram(&self, edges: &[f32]) -> Vec<usize> {
+        let mut counts = vec![0; edges.len().saturating_sub(1)];
+        if counts.is_empty() {
+            return counts;
+        }
+        for (_, _, value) in self.iter() {
+            let bin = edges.partition_point(|edge| *edge <= value);
+            if bin > 0 && bin < edges.len() {
+                counts[bin - 1] += 1;
+            } else if bin == edges.len() && edges.last() == Some(&value) {
+                counts[bin - 2] += 1;
+            }
+        }
+        counts
+    }
+}
+
+/// Decode a grid dimension. Dimensions may arrive as JS numbers (floats),
+/// so any non-negative whole number is accepted.
+fn dimension(
+    client: &Client,
+    method: &'static str,
+    value: HostValue,
+    expected: &str,
+) -> Result<usize> {
+    let received = value.into_json().unwrap_or_default();
+    match received.as_f64() {
+        Some(n) if n >= 0.0 && n.fract() == 0.0 && n <= u32::MAX as f64 => Ok(n as usize),
+        _ => Err(client.unexpected(method, expected, HostValue::Json(received))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeForma;
+    use serde_json::json;
+    use std::rc::Rc;
+
+    /// 2x2 grid at the origin with 10 m cells; the top-right cell is masked.
+    fn grid() -> GroundGrid {
+        GroundGrid {
+            values: vec![1.0, 3.0, 5.0, f32::NAN],
+            mask: vec![true, true, true, false],
+            geometry: GridGeometry {
+                width: 2,
+                height: 2,
+                x0: 0.0,
+                y0: 0.0,
+                resolution: 10.0,
+            },
+        }
+    }
+
+    fn decode(value: serde_json::Value) -> Result<GroundGrid> {
+        let client = Client::new(Rc::new(FakeForma::new())).scoped("analysis");
+        GroundGrid::decode(&client, "getGroundGrid", value.into())
+    }
+
+    #[test]
+    fn sample_interpolates_between_unmasked_centres() {
+        let grid = grid();
+        assert_eq!(grid.sample(5.0, 5.0), Some(1.0));
+        assert_eq!(grid.sample(10.0, 5.0), Some(2.0));
+        // Halfway between all four centres; the masked one is left out.
+        assert_eq!(grid.sample(10.0, 10.0), Some(3.0));
+        assert_eq!(grid.sample(15.0, 15.0), None);
+    }
+
+    #[test]
+    fn statistics_skip_masked_cells() {
+        let grid = grid();
+        assert_eq!(
+            grid.stats(),
+            Some(GridStats {
+                count: 3,
+                min: 1.0,
+                max: 5.0,
+                mean: 3.0,
+            })
+        );
+        assert_eq!(grid.percentile(0.0), Some(1.0));
+        assert_eq!(grid.percentile(50.0), Some(3.0));
+        assert_eq!(grid.percentile(100.0), Some(5.0));
+        assert_eq!(grid.histogram(&[0.0, 2.0, 5.0]), [1, 2]);
+        assert_eq!(grid.histogram(&[2.0, 4.0]), [1]);
+        assert!(grid.histogram(&[1.0]).is_empty());
+    }
+
+    #[test]
+    fn decodes_host_grids() {
+        let grid = decode(json!({
+            "grid": [1.0, 2.0, 3.0, 4.0, 5.0, 6.0],
+            "mask": [1, 1, 0, 1, 1, 1],
+            "width": 3.0,
+            "height": 2,
+            "x0": 10.0,
+            "y0": 20.0,
+            "resolution": 5.0,
+        }))
+        .unwrap();
+        assert_eq!(grid.get(1, 0), Some(2.0));
+        assert_eq!(grid.get(2, 0), None);
+        assert_eq!(grid.value_at(21.0, 26.0), Some(6.0));
+    }
+
+    #[test]
+    fn rejects_grids_of_another_shape() {
+        let dimensions = |width: &serde_json::Value, height: &serde_json::Value| {
+            decode(json!({
+                "grid": [],
+                "width": width,
+                "height": height,
+                "x0": 0.0,
+                "y0": 0.0,
+                "resolution": 1.0,
+            }))
+        };
+        for (width, height) in [
+            (json!(-1.0), json!(0)),
+            (json!(1.5), json!(0)),
+            (json!(1e300), json!(1e300)),
+            (json!(u32::MAX), json!(u32::MAX)),
+        ] {
+            let Err(SdkError::Decode(err)) = dimensions(&width, &height) else {
+                panic!("decoded {width}x{height}");
+            };
+            assert_eq!(err.method.as_deref(), Some("analysis.getGroundGrid"));
+        }
+        let Err(SdkError::Decode(err)) = decode(json!({ "grid": "none" })) else {
+            panic!("decoded a string grid");
+        };
+        assert_eq!(err.expected, "a grid of floats");
     }
 }
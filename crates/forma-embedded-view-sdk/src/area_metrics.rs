@@ -1,6 +1,9 @@
+use crate::client::Client;
+use crate::host::HostValue;
+use crate::options::call_options;
 use crate::types::*;
 use crate::Result;
-use wasm_bindgen_futures::JsFuture;
+use std::collections::BTreeMap;
 
 pub(crate) mod js {
     use wasm_bindgen::prelude::*;
@@ -12,26 +15,266 @@ pub(crate) mod js {
         #[wasm_bindgen(method)]
         pub fn calculate(this: &AreaMetricsApi, request: &JsValue) -> ::js_sys::Promise;
     }
+
+    impl AreaMetricsApi {
+        pub(crate) fn dispatch(&self, method: &str, args: &[JsValue]) -> Option<::js_sys::Promise> {
+            Some(match method {
+                "calculate" => self.calculate(&args[0]),
+                _ => return None,
+            })
+        }
+    }
 }
 
 /// Calculate area metrics / key figures for elements.
 pub struct AreaMetricsApi {
-    inner: js::AreaMetricsApi,
+    client: Client,
 }
 
+call_options!(AreaMetricsApi);
+
 impl AreaMetricsApi {
-    pub(crate) fn from_raw(raw: js::AreaMetricsApi) -> Self {
-        Self { inner: raw }
+    pub(crate) fn new(client: Client) -> Self {
+        Self { client }
     }
 
     /// Calculate area metrics for the given paths. If no paths are given,
     /// metrics are calculated for all elements.
-    pub async fn calculate(
+    pub async fn calculate(&self, request: &AreaMetricsCalculateRequest) -> Result<AreaMetrics> {
+        let result = self.calculate_raw(request).await?;
+        self.client.response("calculate", result.into())
+    }
+
+    /// Calculate area metrics as untyped JSON.
+    pub async fn calculate_raw(
         &self,
         request: &AreaMetricsCalculateRequest,
     ) -> Result<serde_json::Value> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.calculate(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self
+            .client
+            .call("calculate", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("calculate", result)
+    }
+}
+
+/// Square feet per square metre.
+const SQUARE_FEET_PER_SQUARE_METRE: f64 = 10.763_910_416_709_722;
+
+impl AreaMetrics {
+    /// Gross floor area, if it could be calculated. In square metres unless
+    /// converted with [`in_unit_system`](Self::in_unit_system).
+    pub fn gross_floor_area(&self) -> Option<f64> {
+        self.built_in_metrics
+            .gross_floor_area
+            .as_ref()?
+            .value
+            .as_f64()
+    }
+
+    /// Site coverage, if it could be calculated.
+    pub fn site_coverage(&self) -> Option<f64> {
+        self.built_in_metrics.site_coverage.as_ref()?.value.as_f64()
+    }
+
+    /// Gross floor area per function id. Functions that could not be
+    /// calculated are left out.
+    pub fn by_function(&self) -> BTreeMap<String, f64> {
+        let mut areas = BTreeMap::new();
+        let breakdown = self
+            .built_in_metrics
+            .gross_floor_area
+            .iter()
+            .flat_map(|metric| &metric.function_breakdown);
+        for function in breakdown {
+            if let Some(value) = function.value.as_f64() {
+                *areas.entry(function.function_id.clone()).or_insert(0.0) += value;
+            }
+        }
+        areas
+    }
+
+    /// Gross floor area per building, keyed by the building's path (the
+    /// first two segments of each element path, e.g. `"root/a1b2"`).
+    pub fn by_building(&self) -> BTreeMap<String, f64> {
+        self.sum_paths(|metrics| {
+            let mut segments = metrics.path.splitn(3, '/');
+            match (segments.next(), segments.next()) {
+                (Some(root), Some(building)) => Some(format!("{root}/{building}")),
+                _ => None,
+            }
+        })
+    }
+
+    /// Gross floor area per floor index.
+    pub fn by_floor(&self) -> BTreeMap<i32, f64> {
+        self.sum_paths(|metrics| metrics.floor)
+    }
+
+    /// Convert area values (and their units) to `system`. Ratios are left
+    /// unchanged.
+    pub fn in_unit_system(mut self, system: &UnitSystem) -> Self {
+        // As reported, path values share the unit of the gross floor area.
+        let path_unit = match &self.path_unit {
+            Some(unit) => Some(unit.as_str()),
+            None => self
+                .built_in_metrics
+                .gross_floor_area
+                .as_ref()
+                .map_or(Some("m²"), |metric| metric.unit.as_deref()),
+        };
+        if let Some((factor, unit)) = conversion(path_unit, system) {
+            for metrics in &mut self.path_metrics {
+                metrics.gross_floor_area.scale(factor);
+                if let Some(net) = &mut metrics.net_floor_area {
+                    net.scale(factor);
+                }
+            }
+            self.path_unit = Some(unit.into());
+        }
+        let built_in = &mut self.built_in_metrics;
+        for metric in [
+            &mut built_in.gross_floor_area,
+            &mut built_in.net_floor_area,
+            &mut built_in.site_area,
+            &mut built_in.site_coverage,
+            &mut built_in.floor_area_ratio,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            metric.convert(system);
+        }
+        self
+    }
+
+    fn sum_paths<K: Ord>(&self, key: impl Fn(&PathAreaMetrics) -> Option<K>) -> BTreeMap<K, f64> {
+        let mut areas = BTreeMap::new();
+        for metrics in &self.path_metrics {
+            if let (Some(key), Some(value)) = (key(metrics), metrics.gross_floor_area.as_f64()) {
+                *areas.entry(key).or_insert(0.0) += value;
+            }
+        }
+        areas
+    }
+}
+
+impl Metric {
+    /// Convert an area metric to `system`. Metrics without an area unit are
+    /// left unchanged.
+    pub fn convert(&mut self, system: &UnitSystem) {
+        let Some((factor, unit)) = conversion(self.unit.as_deref(), system) else {
+            return;
+        };
+        self.value.scale(factor);
+        for function in &mut self.function_breakdown {
+            function.value.scale(factor);
+        }
+        self.unit = Some(unit.into());
+    }
+}
+
+/// Factor and target unit to convert areas in `unit` to `system`, or `None`
+/// if `unit` is not an area unit or already matches.
+fn conversion(unit: Option<&str>, system: &UnitSystem) -> Option<(f64, &'static str)> {
+    match (unit?, system) {
+        ("m²" | "m2", UnitSystem::Imperial) => Some((SQUARE_FEET_PER_SQUARE_METRE, "ft²")),
+        ("ft²" | "ft2", UnitSystem::Metric) => Some((1.0 / SQUARE_FEET_PER_SQUARE_METRE, "m²")),
+        _ => None,
+    }
+}
+
+impl MetricValue {
+    /// The value, or `None` if it could not be calculated.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            MetricValue::Value(value) => Some(*value),
+            MetricValue::Unavailable(_) => None,
+        }
+    }
+
+    fn scale(&mut self, factor: f64) {
+        if let MetricValue::Value(value) = self {
+            *value *= factor;
+        }
+    }
+}
+
+impl UnitSystem {
+    /// Unit label for areas in this system.
+    pub fn area_unit(&self) -> &'static str {
+        match self {
+            UnitSystem::Metric => "m²",
+            UnitSystem::Imperial => "ft²",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn metrics(built_in: serde_json::Value) -> AreaMetrics {
+        serde_json::from_value(json!({
+            "builtInMetrics": built_in,
+            "pathMetrics": [
+                { "path": "root/a/f0", "floor": 0, "grossFloorArea": 100.0 },
+                { "path": "root/a/f1", "floor": 1, "grossFloorArea": 50.0 },
+                { "path": "root/b", "grossFloorArea": "UNABLE_TO_CALCULATE" },
+            ],
+        }))
+        .unwrap()
+    }
+
+    fn assert_close(actual: Option<f64>, expected: f64) {
+        let actual = actual.unwrap();
+        assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn aggregates_path_values() {
+        let metrics = metrics(json!({}));
+        assert_eq!(
+            metrics.by_building(),
+            BTreeMap::from([("root/a".to_string(), 150.0)])
+        );
+        assert_eq!(metrics.by_floor(), BTreeMap::from([(0, 100.0), (1, 50.0)]));
+    }
+
+    #[test]
+    fn converting_twice_converts_once() {
+        let metrics = metrics(json!({
+            "grossFloorArea": { "value": 150.0, "unit": "m²" },
+            "floorAreaRatio": { "value": 1.5 },
+        }));
+        let imperial = metrics
+            .in_unit_system(&UnitSystem::Imperial)
+            .in_unit_system(&UnitSystem::Imperial);
+        let feet = 150.0 * SQUARE_FEET_PER_SQUARE_METRE;
+        assert_close(imperial.gross_floor_area(), feet);
+        assert_close(imperial.by_building().get("root/a").copied(), feet);
+        let ratio = imperial.built_in_metrics.floor_area_ratio.as_ref().unwrap();
+        assert_close(ratio.value.as_f64(), 1.5);
+
+        let metric = imperial.in_unit_system(&UnitSystem::Metric);
+        assert_close(metric.gross_floor_area(), 150.0);
+        assert_close(metric.by_floor().get(&0).copied(), 100.0);
+    }
+
+    #[test]
+    fn path_values_without_gross_floor_area_round_trip() {
+        let imperial = metrics(json!({}))
+            .in_unit_system(&UnitSystem::Imperial)
+            .in_unit_system(&UnitSystem::Imperial);
+        assert_eq!(imperial.path_unit.as_deref(), Some("ft²"));
+        assert_close(
+            imperial.by_floor().get(&1).copied(),
+            50.0 * SQUARE_FEET_PER_SQUARE_METRE,
+        );
+
+        let metric = imperial.in_unit_system(&UnitSystem::Metric);
+        assert_eq!(metric.path_unit.as_deref(), Some("m²"));
+        assert_close(metric.by_floor().get(&1).copied(), 50.0);
     }
 }
@@ -1,6 +1,8 @@
+use crate::client::Client;
+use crate::host::HostValue;
+use crate::options::call_options;
 use crate::types::*;
 use crate::Result;
-use wasm_bindgen_futures::JsFuture;
 
 pub(crate) mod js {
     use wasm_bindgen::prelude::*;
@@ -24,50 +26,68 @@ pub(crate) mod js {
         #[wasm_bindgen(method, js_name = "refreshCurrentToken")]
         pub fn refresh_current_token(this: &AuthApi) -> ::js_sys::Promise;
     }
+
+    impl AuthApi {
+        pub(crate) fn dispatch(
+            &self,
+            method: &str,
+            _args: &[JsValue],
+        ) -> Option<::js_sys::Promise> {
+            Some(match method {
+                "acquireTokenSilent" => self.acquire_token_silent(),
+                "acquireTokenPopup" => self.acquire_token_popup(),
+                "acquireTokenOverlay" => self.acquire_token_overlay(),
+                "refreshCurrentToken" => self.refresh_current_token(),
+                _ => return None,
+            })
+        }
+    }
 }
 
 /// Manage access tokens for APS (Autodesk Platform Services) auth flows.
 pub struct AuthApi {
-    inner: js::AuthApi,
+    client: Client,
 }
 
+call_options!(AuthApi);
+
 impl AuthApi {
-    pub(crate) fn from_raw(raw: js::AuthApi) -> Self {
-        Self { inner: raw }
+    pub(crate) fn new(client: Client) -> Self {
+        Self { client }
     }
 
     /// Configure extension with client ID, callback URL, and scopes.
     pub fn configure(&self, config: &AuthConfig) -> Result<()> {
-        let js_config = serde_wasm_bindgen::to_value(config)?;
-        self.inner.configure(&js_config);
+        self.client
+            .call_sync("configure", vec![HostValue::json(config)?])?;
         Ok(())
     }
 
     /// Get the current access token if valid, refreshing if needed.
     /// Returns `None` if no token is available.
     pub async fn acquire_token_silent(&self) -> Result<Option<AccessTokenResponse>> {
-        let result = JsFuture::from(self.inner.acquire_token_silent()).await?;
-        if result.is_undefined() || result.is_null() {
+        let result = self.client.call("acquireTokenSilent", vec![]).await?;
+        if result.is_nullish() {
             return Ok(None);
         }
-        Ok(Some(serde_wasm_bindgen::from_value(result)?))
+        Ok(Some(self.client.response("acquireTokenSilent", result)?))
     }
 
     /// Acquire an access token via a popup authorization flow.
     pub async fn acquire_token_popup(&self) -> Result<AccessTokenResponse> {
-        let result = JsFuture::from(self.inner.acquire_token_popup()).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self.client.call("acquireTokenPopup", vec![]).await?;
+        self.client.response("acquireTokenPopup", result)
     }
 
     /// Acquire an access token by showing a login overlay, then popup.
     pub async fn acquire_token_overlay(&self) -> Result<AccessTokenResponse> {
-        let result = JsFuture::from(self.inner.acquire_token_overlay()).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self.client.call("acquireTokenOverlay", vec![]).await?;
+        self.client.response("acquireTokenOverlay", result)
     }
 
     /// Refresh the current token. Returns an error if no token is stored.
     pub async fn refresh_current_token(&self) -> Result<AccessTokenResponse> {
-        let result = JsFuture::from(self.inner.refresh_current_token()).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self.client.call("refreshCurrentToken", vec![]).await?;
+        self.client.response("refreshCurrentToken", result)
     }
 }
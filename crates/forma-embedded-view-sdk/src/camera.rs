@@ -1,8 +1,13 @@
-use crate::subscription::Subscription;
+use crate::client::Client;
+use crate::host::HostValue;
+use crate::options::call_options;
+#[cfg(target_arch = "wasm32")]
+use crate::stream::EventStreamExt;
+use crate::subscription::{EventStream, Subscription};
 use crate::types::*;
 use crate::Result;
-use wasm_bindgen::prelude::*;
-use wasm_bindgen_futures::JsFuture;
+#[cfg(target_arch = "wasm32")]
+use std::time::Duration;
 
 pub(crate) mod js {
     use wasm_bindgen::prelude::*;
@@ -29,58 +34,106 @@ pub(crate) mod js {
             callback: &Closure<dyn FnMut(JsValue)>,
         ) -> ::js_sys::Promise;
     }
+
+    impl CameraApi {
+        pub(crate) fn dispatch(&self, method: &str, args: &[JsValue]) -> Option<::js_sys::Promise> {
+            Some(match method {
+                "move" => self.move_(&args[0]),
+                "switchPerspective" => self.switch_perspective(),
+                "capture" => self.capture(&args[0]),
+                "getCurrent" => self.get_current(),
+                _ => return None,
+            })
+        }
+
+        pub(crate) fn listen(
+            &self,
+            event: &str,
+            handler: &Closure<dyn FnMut(JsValue)>,
+        ) -> Option<::js_sys::Promise> {
+            match event {
+                "subscribe" => Some(self.subscribe(handler)),
+                _ => None,
+            }
+        }
+    }
 }
 
 /// Interact with the camera in the 3D scene.
 pub struct CameraApi {
-    inner: js::CameraApi,
+    client: Client,
 }
 
+call_options!(CameraApi);
+
 impl CameraApi {
-    pub(crate) fn from_raw(raw: js::CameraApi) -> Self {
-        Self { inner: raw }
+    pub(crate) fn new(client: Client) -> Self {
+        Self { client }
     }
 
     /// Move camera view to a new position.
     pub async fn move_to(&self, request: &CameraMoveRequest) -> Result<()> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        JsFuture::from(self.inner.move_(&js_req)).await?;
+        self.client
+            .call("move", vec![HostValue::json(request)?])
+            .await?;
         Ok(())
     }
 
     /// Toggle between perspective and orthographic camera.
     pub async fn switch_perspective(&self) -> Result<()> {
-        JsFuture::from(self.inner.switch_perspective()).await?;
+        self.client.call("switchPerspective", vec![]).await?;
         Ok(())
     }
 
     /// Capture a screenshot of the current camera view.
     /// Returns a `CaptureResult` wrapping the canvas, with methods like `to_data_url()`.
     pub async fn capture(&self, request: &CameraCaptureRequest) -> Result<CaptureResult> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.capture(&js_req)).await?;
-        Ok(CaptureResult { inner: result })
+        let result = self
+            .client
+            .call("capture", vec![HostValue::json(request)?])
+            .await?;
+        match result {
+            HostValue::Handle(inner) => Ok(CaptureResult { inner }),
+            other => Err(self.client.unexpected("capture", "a canvas", other)),
+        }
     }
 
     /// Fetch the current camera state.
     pub async fn get_current(&self) -> Result<CameraState> {
-        let result = JsFuture::from(self.inner.get_current()).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self.client.call("getCurrent", vec![]).await?;
+        self.client.response("getCurrent", result)
     }
 
     /// Subscribe to camera changes.
     pub async fn subscribe(
         &self,
-        mut callback: impl FnMut(CameraState) + 'static,
+        callback: impl FnMut(CameraState) + 'static,
     ) -> Result<Subscription> {
-        let closure = Closure::wrap(Box::new(move |val: JsValue| {
-            if let Ok(state) = serde_wasm_bindgen::from_value(val) {
-                callback(state);
-            }
-        }) as Box<dyn FnMut(JsValue)>);
-        let result = JsFuture::from(self.inner.subscribe(&closure)).await?;
-        let unsubscribe_fn: ::js_sys::Function =
-            ::js_sys::Reflect::get(&result, &"unsubscribe".into())?.into();
-        Ok(Subscription::new(closure, unsubscribe_fn))
+        self.client
+            .subscribe_to("subscribe", vec![], callback)
+            .await
+    }
+
+    /// Camera state changes as a stream. Unsubscribes when dropped.
+    pub async fn states(&self) -> Result<EventStream<CameraState>> {
+        let (sender, stream) = EventStream::channel();
+        let subscription = self.subscribe(move |state| sender.send(state)).await?;
+        Ok(stream.attach(subscription))
+    }
+
+    /// Subscribe to camera changes, delivering at most one state per `period`
+    /// (the latest). Runs on a task spawned with [`spawn_local`](crate::spawn_local),
+    /// so it is only available on wasm; elsewhere, drive
+    /// [`stream::for_each`](crate::stream::for_each) over
+    /// `states().await?.throttle(period)` yourself.
+    #[cfg(target_arch = "wasm32")]
+    pub async fn subscribe_throttled(
+        &self,
+        period: Duration,
+        callback: impl FnMut(CameraState) + 'static,
+    ) -> Result<Subscription> {
+        let (states, subscription) = self.states().await?.detach();
+        crate::stream::drive(states.throttle(period), callback);
+        Ok(subscription)
     }
 }
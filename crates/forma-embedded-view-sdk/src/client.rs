@@ -0,0 +1,546 @@
+use crate::error::{DecodeError, ErrorDetails};
+use crate::host::{FormaHost, HostCall, HostFuture, HostReplySubscribe, HostSubscribe, HostValue};
+use crate::options::{self, CallOptions, Decoding};
+use crate::subscription::{EventErrors, Subscription};
+use crate::trace::{self, CallSpan};
+use crate::{clock, Result, SdkError};
+use serde::de::DeserializeOwned;
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::Poll;
+use std::time::Duration;
+
+/// Shared handle used by every API wrapper to reach the host.
+#[derive(Clone)]
+pub(crate) struct Client {
+    host: Rc<dyn FormaHost>,
+    api: &'static str,
+    options: CallOptions,
+}
+
+impl Client {
+    pub(crate) fn new(host: Rc<dyn FormaHost>) -> Self {
+        Self {
+            host,
+            api: "sdk",
+            options: options::default_options(),
+        }
+    }
+
+    /// Same host, addressing a different (sub-)API.
+    pub(crate) fn scoped(&self, api: &'static str) -> Self {
+        Self {
+            host: self.host.clone(),
+            api,
+            options: self.options.clone(),
+        }
+    }
+
+    pub(crate) fn with_options(self, options: CallOptions) -> Self {
+        Self {
+            options: self.options.merge(options),
+            ..self
+        }
+    }
+
+    pub(crate) fn host(&self) -> &Rc<dyn FormaHost> {
+        &self.host
+    }
+
+    /// Decode the response of `method` into `T`, described as `expected` in
+    /// the error. With [`Decoding::Lenient`], a value of another shape decodes
+    /// to `T::default()` instead.
+    pub(crate) fn decode<T: DeserializeOwned + Default>(
+        &self,
+        method: &'static str,
+        expected: &str,
+        value: HostValue,
+    ) -> Result<T> {
+        let received = value.into_json().unwrap_or_default();
+        match serde_json::from_value(received.clone()) {
+            Ok(decoded) => Ok(decoded),
+            Err(_) if self.options.decoding == Some(Decoding::Lenient) => Ok(T::default()),
+            Err(_) => Err(self.decode_error(method, expected, received)),
+        }
+    }
+
+    /// Decode the response of `method` into `T`, failing with
+    /// [`SdkError::Decode`] if it has another shape.
+    pub(crate) fn response<T: DeserializeOwned>(
+        &self,
+        method: &'static str,
+        value: HostValue,
+    ) -> Result<T> {
+        self.expect(method, &type_label::<T>(), value)
+    }
+
+    /// Decode (part of) the response of `method` into `T`, failing with
+    /// [`SdkError::Decode`] naming `expected` if it has another shape.
+    pub(crate) fn expect<T: DeserializeOwned>(
+        &self,
+        method: &'static str,
+        expected: &str,
+        value: HostValue,
+    ) -> Result<T> {
+        let Ok(received) = value.into_json() else {
+            return Err(self.unexpected(method, expected, HostValue::Undefined));
+        };
+        serde_json::from_value(received.clone())
+            .map_err(|_| self.decode_error(method, expected, received))
+    }
+
+    /// Take the binary contents of the response of `method`.
+    pub(crate) fn bytes(&self, method: &'static str, value: HostValue) -> Result<Vec<u8>> {
+        match value {
+            HostValue::Bytes(bytes) | HostValue::Buffer(bytes) => Ok(bytes),
+            other => self.response(method, other),
+        }
+    }
+
+    /// Take the float contents of the response of `method`.
+    pub(crate) fn floats(&self, method: &'static str, value: HostValue) -> Result<Vec<f32>> {
+        match value {
+            HostValue::Floats(floats) => Ok(floats),
+            other => self.response(method, other),
+        }
+    }
+
+    /// [`SdkError::Decode`] for a response of `method` that is not `expected`.
+    pub(crate) fn unexpected(&self, method: &str, expected: &str, received: HostValue) -> SdkError {
+        self.decode_error(method, expected, received.into_json().unwrap_or_default())
+    }
+
+    /// Attribute `err` to `method` of this client's API, unless it already
+    /// names a method.
+    pub(crate) fn in_method(&self, method: &str, err: SdkError) -> SdkError {
+        err.in_method(self.api, method)
+    }
+
+    fn decode_error(&self, method: &str, expected: &str, received: serde_json::Value) -> SdkError {
+        SdkError::Decode(DecodeError {
+            method: Some(format!("{}.{method}", self.api)),
+            expected: expected.into(),
+            received,
+        })
+    }
+
+    pub(crate) async fn call(
+        &self,
+        method: &'static str,
+        args: Vec<HostValue>,
+    ) -> Result<HostValue> {
+        let span = CallSpan::start("call", self.api, method, &args);
+        let result = span.instrument(self.attempts(method, args)).await;
+        span.finish(result)
+    }
+
+    /// Make the call, retrying according to the retry policy.
+    async fn attempts(&self, method: &'static str, args: Vec<HostValue>) -> Result<HostValue> {
+        let mut attempt = 1;
+        loop {
+            let call = self.host_call(method, args.clone());
+            let result = self.race(self.host.call(call), self.options.timeout).await;
+            let retry = self.options.retry.as_ref();
+            match result {
+                Err(err) if retry.is_some_and(|policy| policy.should_retry(attempt, &err)) => {
+                    let delay = retry.map_or(Duration::ZERO, |policy| {
+                        policy.delay(attempt, options::random_unit())
+                    });
+                    trace::retrying(attempt, delay, &err);
+                    self.sleep(delay)
+                        .await
+                        .map_err(|err| err.in_method(self.api, method))?;
+                    attempt += 1;
+                }
+                result => return result.map_err(|err| err.in_method(self.api, method)),
+            }
+        }
+    }
+
+    pub(crate) fn call_sync(
+        &self,
+        method: &'static str,
+        args: Vec<HostValue>,
+    ) -> Result<HostValue> {
+        let span = CallSpan::start("callSync", self.api, method, &args);
+        let result = self
+            .host
+            .call_sync(self.host_call(method, args))
+            .map_err(|err| err.in_method(self.api, method));
+        span.finish(result)
+    }
+
+    pub(crate) async fn subscribe(
+        &self,
+        event: &'static str,
+        args: Vec<HostValue>,
+        handler: impl FnMut(HostValue) + 'static,
+    ) -> Result<Subscription> {
+        let span = CallSpan::start("subscribe", self.api, event, &args);
+        let subscribe = self
+            .host
+            .subscribe(self.host_subscribe(event, args, handler));
+        let result = span
+            .instrument(subscribe)
+            .await
+            .map_err(|err| err.in_method(self.api, event));
+        span.finish(result)
+    }
+
+    /// Subscribe with events deserialized into `T`. Events that fail to
+    /// deserialize are dropped and reported on the returned subscription.
+    pub(crate) async fn subscribe_to<T: DeserializeOwned>(
+        &self,
+        event: &'static str,
+        args: Vec<HostValue>,
+        callback: impl FnMut(T) + 'static,
+    ) -> Result<Subscription> {
+        let (handler, errors) = self.decoding(event, callback);
+        let subscription = self.subscribe(event, args, handler).await?;
+        Ok(subscription.with_errors(errors))
+    }
+
+    pub(crate) fn subscribe_sync(
+        &self,
+        event: &'static str,
+        args: Vec<HostValue>,
+        handler: impl FnMut(HostValue) + 'static,
+    ) -> Result<Subscription> {
+        let span = CallSpan::start("subscribe", self.api, event, &args);
+        let result = self
+            .host
+            .subscribe_sync(self.host_subscribe(event, args, handler))
+            .map_err(|err| err.in_method(self.api, event));
+        span.finish(result)
+    }
+
+    /// Register a handler whose answer the host waits for.
+    pub(crate) fn subscribe_with_reply(
+        &self,
+        event: &'static str,
+        args: Vec<HostValue>,
+        mut handler: impl FnMut(HostValue) -> HostFuture<HostValue> + 'static,
+    ) -> Result<Subscription> {
+        let api = self.api;
+        let span = CallSpan::start("subscribe", api, event, &args);
+        let subscribe = HostReplySubscribe {
+            api,
+            event,
+            args,
+            handler: Box::new(move |value| {
+                trace::delivered(api, event, &value);
+                handler(value)
+            }),
+        };
+        let result = self
+            .host
+            .subscribe_with_reply(subscribe)
+            .map_err(|err| err.in_method(api, event));
+        span.finish(result)
+    }
+
+    /// Synchronous variant of [`subscribe_to`](Self::subscribe_to).
+    pub(crate) fn subscribe_sync_to<T: DeserializeOwned>(
+        &self,
+        event: &'static str,
+        args: Vec<HostValue>,
+        callback: impl FnMut(T) + 'static,
+    ) -> Result<Subscription> {
+        let (handler, errors) = self.decoding(event, callback);
+        let subscription = self.subscribe_sync(event, args, handler)?;
+        Ok(subscription.with_errors(errors))
+    }
+
+    /// Wrap `callback` into a handler that deserializes events, reporting
+    /// failures to the returned `EventErrors`.
+    fn decoding<T: DeserializeOwned>(
+        &self,
+        event: &'static str,
+        mut callback: impl FnMut(T) + 'static,
+    ) -> (impl FnMut(HostValue) + 'static, Rc<EventErrors>) {
+        let api = self.api;
+        let errors = Rc::new(EventErrors::default());
+        let reported = errors.clone();
+        let handler = move |val: HostValue| match val.deserialize() {
+            Ok(value) => callback(value),
+            Err(err) => {
+                let err = match err {
+                    SdkError::Serialization(msg) => {
+                        SdkError::Serialization(format!("{api}.{event} event: {msg}"))
+                    }
+                    err => err.in_method(api, event),
+                };
+                trace::dropped(api, event, &err);
+                reported.report(err);
+            }
+        };
+        (handler, errors)
+    }
+
+    /// Wait for `duration` on the installed clock, failing early if the
+    /// cancellation token is cancelled.
+    pub(crate) async fn sleep(&self, duration: Duration) -> Result<()> {
+        let sleep = clock::current().sleep(duration);
+        let sleep = Box::pin(async move {
+            sleep.await;
+            Ok(())
+        });
+        self.race(sleep, None).await
+    }
+
+    /// Run `future`, failing early on timeout or cancellation. The future is
+    /// dropped in that case.
+    async fn race<T>(&self, mut future: HostFuture<T>, timeout: Option<Duration>) -> Result<T> {
+        let cancellation = self.options.cancellation.clone();
+        if cancellation
+            .as_ref()
+            .is_some_and(|token| token.is_cancelled())
+        {
+            return Err(cancelled_error());
+        }
+        let mut sleep = timeout.map(|timeout| clock::current().sleep(timeout));
+        let mut cancelled = cancellation.map(|token| Box::pin(token.cancelled()));
+        poll_fn(|cx| {
+            if let Some(cancelled) = cancelled.as_mut() {
+                if cancelled.as_mut().poll(cx).is_ready() {
+                    return Poll::Ready(Err(cancelled_error()));
+                }
+            }
+            if let Poll::Ready(result) = future.as_mut().poll(cx) {
+                return Poll::Ready(result);
+            }
+            if let (Some(sleep), Some(timeout)) = (sleep.as_mut(), timeout) {
+                if sleep.as_mut().poll(cx).is_ready() {
+                    return Poll::Ready(Err(SdkError::Timeout(ErrorDetails::new(format!(
+                        "no answer within {} ms",
+                        timeout.as_millis()
+                    )))));
+                }
+            }
+            Poll::Pending
+        })
+        .await
+    }
+
+    fn host_call(&self, method: &'static str, args: Vec<HostValue>) -> HostCall {
+        HostCall {
+            api: self.api,
+            method,
+            args,
+        }
+    }
+
+    fn host_subscribe(
+        &self,
+        event: &'static str,
+        args: Vec<HostValue>,
+        mut handler: impl FnMut(HostValue) + 'static,
+    ) -> HostSubscribe {
+        let api = self.api;
+        HostSubscribe {
+            api,
+            event,
+            args,
+            handler: Box::new(move |value| {
+                trace::delivered(api, event, &value);
+                handler(value)
+            }),
+        }
+    }
+}
+
+/// Name of `T` without module paths (`Vec<Proposal>`), to describe the
+/// expected shape of a response.
+fn type_label<T>() -> String {
+    let mut label = String::new();
+    // Start of the path being written; `::` drops what precedes it.
+    let mut path = 0;
+    for c in std::any::type_name::<T>().chars() {
+        if c == ':' {
+            label.truncate(path);
+        } else {
+            label.push(c);
+            if !(c.is_alphanumeric() || c == '_') {
+                path = label.len();
+            }
+        }
+    }
+    label
+}
+
+/// A boxed future borrowing from its caller, for running several at once.
+pub(crate) type LocalFuture<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
+
+/// Wait for the first of `running` to finish, remove it and return its
+/// output. Pends forever if `running` is empty.
+pub(crate) async fn first_ready<T>(running: &mut Vec<LocalFuture<'_, T>>) -> T {
+    poll_fn(|cx| {
+        for i in 0..running.len() {
+            if let Poll::Ready(output) = running[i].as_mut().poll(cx) {
+                drop(running.swap_remove(i));
+                return Poll::Ready(output);
+            }
+        }
+        Poll::Pending
+    })
+    .await
+}
+
+fn cancelled_error() -> SdkError {
+    SdkError::Cancelled(ErrorDetails::new("cancelled by caller"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host::HostValue;
+    use crate::options::{CancellationToken, RetryPolicy};
+    use crate::testing::ManualClock;
+    use std::cell::Cell;
+    use std::task::{Context, Waker};
+
+    /// Host whose calls fail with `HostUnavailable` `failures` times, then
+    /// never answer.
+    struct Flaky {
+        failures: Cell<u32>,
+        attempts: Rc<Cell<u32>>,
+    }
+
+    impl FormaHost for Flaky {
+        fn call(&self, _: HostCall) -> HostFuture<HostValue> {
+            self.attempts.set(self.attempts.get() + 1);
+            if self.failures.get() == 0 {
+                return Box::pin(std::future::pending());
+            }
+            self.failures.set(self.failures.get() - 1);
+            Box::pin(async { Err(SdkError::HostUnavailable(ErrorDetails::new("port closed"))) })
+        }
+
+        fn call_sync(&self, _: HostCall) -> Result<HostValue> {
+            unimplemented!()
+        }
+
+        fn subscribe(&self, _: HostSubscribe) -> HostFuture<Subscription> {
+            unimplemented!()
+        }
+
+        fn subscribe_sync(&self, _: HostSubscribe) -> Result<Subscription> {
+            unimplemented!()
+        }
+    }
+
+    fn client(failures: u32, options: CallOptions) -> (Client, Rc<Cell<u32>>) {
+        let attempts = Rc::new(Cell::new(0));
+        let host = Flaky {
+            failures: Cell::new(failures),
+            attempts: attempts.clone(),
+        };
+        let client = Client::new(Rc::new(host))
+            .scoped("sun")
+            .with_options(options);
+        (client, attempts)
+    }
+
+    fn poll<T>(future: &mut LocalFuture<'_, T>) -> Poll<T> {
+        future
+            .as_mut()
+            .poll(&mut Context::from_waker(Waker::noop()))
+    }
+
+    #[test]
+    fn times_out_on_the_installed_clock() {
+        let clock = ManualClock::new();
+        let _guard = clock.install();
+        let (client, _) = client(0, CallOptions::default().timeout(Duration::from_secs(5)));
+        let mut call: LocalFuture<'_, _> = Box::pin(client.call("getDate", vec![]));
+
+        assert!(poll(&mut call).is_pending());
+        clock.advance(Duration::from_millis(4999));
+        assert!(poll(&mut call).is_pending());
+        clock.advance(Duration::from_millis(1));
+        let Poll::Ready(Err(SdkError::Timeout(details))) = poll(&mut call) else {
+            panic!("call did not time out");
+        };
+        assert_eq!(details.method.as_deref(), Some("sun.getDate"));
+    }
+
+    #[test]
+    fn retries_after_the_backoff() {
+        let clock = ManualClock::new();
+        let _guard = clock.install();
+        let policy = RetryPolicy::new(3)
+            .backoff(Duration::from_millis(100), Duration::from_secs(1))
+            .jitter(0.0);
+        let (client, attempts) = client(3, CallOptions::default().retry(policy));
+        let mut call: LocalFuture<'_, _> = Box::pin(client.call("getDate", vec![]));
+
+        assert!(poll(&mut call).is_pending());
+        assert_eq!((attempts.get(), clock.pending_sleeps()), (1, 1));
+        clock.advance(Duration::from_millis(99));
+        assert!(poll(&mut call).is_pending());
+        assert_eq!(attempts.get(), 1);
+        clock.advance(Duration::from_millis(1));
+        assert!(poll(&mut call).is_pending());
+        assert_eq!(attempts.get(), 2);
+        clock.advance(Duration::from_millis(200));
+        let Poll::Ready(Err(SdkError::HostUnavailable(_))) = poll(&mut call) else {
+            panic!("call did not fail after the last attempt");
+        };
+        assert_eq!(attempts.get(), 3);
+    }
+
+    #[test]
+    fn cancellation_interrupts_the_backoff() {
+        let clock = ManualClock::new();
+        let _guard = clock.install();
+        let token = CancellationToken::new();
+        let options = CallOptions::default()
+            .retry(RetryPolicy::new(3))
+            .cancellation(&token);
+        let (client, attempts) = client(1, options);
+        let mut call: LocalFuture<'_, _> = Box::pin(client.call("getDate", vec![]));
+
+        assert!(poll(&mut call).is_pending());
+        token.cancel();
+        assert!(matches!(
+            poll(&mut call),
+            Poll::Ready(Err(SdkError::Cancelled(_)))
+        ));
+        assert_eq!(attempts.get(), 1);
+    }
+
+    #[test]
+    fn lenient_decoding_falls_back_to_the_default() {
+        let (strict, _) = client(0, CallOptions::default());
+        let (lenient, _) = client(0, CallOptions::default().decoding(Decoding::Lenient));
+        let value = || HostValue::Json(serde_json::json!({ "not": "a number" }));
+
+        let Err(SdkError::Decode(err)) =
+            strict.decode::<f64>("getElevationAt", "a number", value())
+        else {
+            panic!("decoded an object as a number");
+        };
+        assert_eq!(err.method.as_deref(), Some("sun.getElevationAt"));
+        assert_eq!(err.expected, "a number");
+        assert_eq!(
+            lenient
+                .decode::<f64>("getElevationAt", "a number", value())
+                .unwrap(),
+            0.0
+        );
+        assert!(lenient.response::<f64>("getElevationAt", value()).is_err());
+    }
+
+    #[test]
+    fn type_labels_drop_module_paths() {
+        assert_eq!(
+            type_label::<Vec<crate::types::ProposalInfo>>(),
+            "Vec<ProposalInfo>"
+        );
+        assert_eq!(
+            type_label::<Option<(String, f64)>>(),
+            "Option<(String, f64)>"
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Future returned by [`Clock::sleep`].
+pub type Sleep = Pin<Box<dyn Future<Output = ()>>>;
+
+/// Time source used for call timeouts and retry backoff.
+///
+/// [`SystemClock`] uses browser timers on wasm and a shared timer thread
+/// natively.
+/// Tests can install [`ManualClock`](crate::testing::ManualClock) to control
+/// time explicitly.
+pub trait Clock {
+    /// Milliseconds since an arbitrary, fixed origin.
+    fn now_ms(&self) -> f64;
+
+    /// Resolve after `duration` has elapsed.
+    fn sleep(&self, duration: Duration) -> Sleep;
+}
+
+/// Wall-clock time: `setTimeout` / `Date.now()` on wasm, a shared timer
+/// thread natively.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+#[cfg(target_arch = "wasm32")]
+mod js {
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(js_name = setTimeout)]
+        pub fn set_timeout(handler: &::js_sys::Function, timeout: i32) -> JsValue;
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Clock for SystemClock {
+    fn now_ms(&self) -> f64 {
+        ::js_sys::Date::now()
+    }
+
+    fn sleep(&self, duration: Duration) -> Sleep {
+        let millis = duration.as_millis().min(i32::MAX as u128) as i32;
+        let promise = ::js_sys::Promise::new(&mut |resolve, _| {
+            js::set_timeout(&resolve, millis);
+        });
+        Box::pin(async move {
+            let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+        })
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Clock for SystemClock {
+    fn now_ms(&self) -> f64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0.0, |elapsed| elapsed.as_secs_f64() * 1000.0)
+    }
+
+    fn sleep(&self, duration: Duration) -> Sleep {
+        use std::task::Poll;
+
+        let state = timer::SleepState::default();
+        let mut started = false;
+        Box::pin(std::future::poll_fn(move |cx| {
+            let mut guard = state.lock().unwrap();
+            if guard.0 || duration.is_zero() {
+                return Poll::Ready(());
+            }
+            guard.1 = Some(cx.waker().clone());
+            if !started {
+                started = true;
+                // A deadline past what `Instant` can hold never elapses.
+                if let Some(deadline) = std::time::Instant::now().checked_add(duration) {
+                    timer::schedule(deadline, state.clone());
+                }
+            }
+            Poll::Pending
+        }))
+    }
+}
+
+/// Native timers: one thread, started on the first sleep, wakes every
+/// [`SystemClock`] sleep.
+#[cfg(not(target_arch = "wasm32"))]
+mod timer {
+    use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+    use std::sync::{Arc, Mutex, OnceLock};
+    use std::task::Waker;
+    use std::time::Instant;
+
+    /// Whether a sleep has elapsed, and the waker to notify once it has.
+    pub(super) type SleepState = Arc<Mutex<(bool, Option<Waker>)>>;
+
+    static TIMER: OnceLock<Sender<(Instant, SleepState)>> = OnceLock::new();
+
+    /// Mark `state` elapsed at `deadline` and wake its waker.
+    pub(super) fn schedule(deadline: Instant, state: SleepState) {
+        let timer = TIMER.get_or_init(|| {
+            let (sender, receiver) = mpsc::channel();
+            std::thread::spawn(move || run(receiver));
+            sender
+        });
+        // The thread only stops once every sender is gone.
+        let _ = timer.send((deadline, state));
+    }
+
+    fn run(receiver: Receiver<(Instant, SleepState)>) {
+        let mut pending: Vec<(Instant, SleepState)> = Vec::new();
+        loop {
+            let received = match pending.iter().map(|(deadline, _)| *deadline).min() {
+                Some(next) => receiver.recv_timeout(next.saturating_duration_since(Instant::now())),
+                None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match received {
+                Ok(sleep) => pending.push(sleep),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+            let now = Instant::now();
+            pending.retain(|(deadline, state)| {
+                if *deadline > now {
+                    return true;
+                }
+                let mut state = state.lock().unwrap();
+                state.0 = true;
+                if let Some(waker) = state.1.take() {
+                    waker.wake();
+                }
+                false
+            });
+        }
+    }
+}
+
+thread_local! {
+    static INSTALLED: RefCell<Option<Rc<dyn Clock>>> = const { RefCell::new(None) };
+}
+
+/// Use `clock` for every SDK call on the current thread until the returned
+/// guard is dropped.
+pub fn install(clock: impl Clock + 'static) -> InstalledClock {
+    let previous = INSTALLED.with(|installed| installed.borrow_mut().replace(Rc::new(clock)));
+    InstalledClock { previous }
+}
+
+/// The clock installed on this thread, or [`SystemClock`].
+pub fn current() -> Rc<dyn Clock> {
+    INSTALLED
+        .with(|installed| installed.borrow().clone())
+        .unwrap_or_else(|| Rc::new(SystemClock))
+}
+
+/// Guard returned by [`install`]. Restores the previously installed clock on drop.
+#[must_use = "the clock is uninstalled when the guard is dropped"]
+pub struct InstalledClock {
+    previous: Option<Rc<dyn Clock>>,
+}
+
+impl Drop for InstalledClock {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        INSTALLED.with(|installed| *installed.borrow_mut() = previous);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::task::{Context, Poll, Waker};
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn system_sleeps_elapse_in_deadline_order() {
+        let mut sleeps: Vec<(u64, Sleep)> = [60, 0, 20, 40]
+            .into_iter()
+            .map(|ms| (ms, SystemClock.sleep(Duration::from_millis(ms))))
+            .collect();
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut elapsed = Vec::new();
+        while !sleeps.is_empty() {
+            sleeps.retain_mut(|(ms, sleep)| match sleep.as_mut().poll(&mut cx) {
+                Poll::Ready(()) => {
+                    elapsed.push(*ms);
+                    false
+                }
+                Poll::Pending => true,
+            });
+        }
+        assert_eq!(elapsed, [0, 20, 40, 60]);
+    }
+
+    #[test]
+    fn installed_clock_is_restored_on_drop() {
+        let manual = crate::testing::ManualClock::new();
+        manual.advance(Duration::from_secs(5));
+        {
+            let _guard = manual.install();
+            assert_eq!(current().now_ms(), 5000.0);
+        }
+        assert!(current().now_ms() > 5000.0);
+    }
+}
@@ -1,6 +1,8 @@
+use crate::client::Client;
+use crate::host::HostValue;
+use crate::options::call_options;
 use crate::types::*;
 use crate::Result;
-use wasm_bindgen_futures::JsFuture;
 
 pub(crate) mod js {
     use wasm_bindgen::prelude::*;
@@ -15,28 +17,41 @@ pub(crate) mod js {
         #[wasm_bindgen(method)]
         pub fn remove(this: &ColorbarApi) -> ::js_sys::Promise;
     }
+
+    impl ColorbarApi {
+        pub(crate) fn dispatch(&self, method: &str, args: &[JsValue]) -> Option<::js_sys::Promise> {
+            Some(match method {
+                "add" => self.add(&args[0]),
+                "remove" => self.remove(),
+                _ => return None,
+            })
+        }
+    }
 }
 
 /// Add and remove a colorbar to the scene view for analysis heatmaps.
 pub struct ColorbarApi {
-    inner: js::ColorbarApi,
+    client: Client,
 }
 
+call_options!(ColorbarApi);
+
 impl ColorbarApi {
-    pub(crate) fn from_raw(raw: js::ColorbarApi) -> Self {
-        Self { inner: raw }
+    pub(crate) fn new(client: Client) -> Self {
+        Self { client }
     }
 
     /// Add a colorbar to the scene view. Only one colorbar at a time.
     pub async fn add(&self, request: &ColorbarAddRequest) -> Result<()> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        JsFuture::from(self.inner.add(&js_req)).await?;
+        self.client
+            .call("add", vec![HostValue::json(request)?])
+            .await?;
         Ok(())
     }
 
     /// Remove the colorbar added by this embedded view.
     pub async fn remove(&self) -> Result<()> {
-        JsFuture::from(self.inner.remove()).await?;
+        self.client.call("remove", vec![]).await?;
         Ok(())
     }
 }
@@ -1,8 +1,8 @@
-use crate::subscription::VoidSubscription;
+use crate::client::Client;
+use crate::options::call_options;
+use crate::subscription::{EventStream, VoidSubscription};
 use crate::types::*;
 use crate::Result;
-use wasm_bindgen::prelude::*;
-use wasm_bindgen_futures::JsFuture;
 
 pub(crate) mod js {
     use wasm_bindgen::prelude::*;
@@ -26,65 +26,95 @@ pub(crate) mod js {
         #[wasm_bindgen(method, js_name = "onEditStart")]
         pub fn on_edit_start(
             this: &DesignToolApi,
-            callback: &Closure<dyn FnMut()>,
+            callback: &Closure<dyn FnMut(JsValue)>,
         ) -> ::js_sys::Promise;
 
         #[wasm_bindgen(method, js_name = "onEditEnd")]
         pub fn on_edit_end(
             this: &DesignToolApi,
-            callback: &Closure<dyn FnMut()>,
+            callback: &Closure<dyn FnMut(JsValue)>,
         ) -> ::js_sys::Promise;
     }
+
+    impl DesignToolApi {
+        pub(crate) fn dispatch(
+            &self,
+            method: &str,
+            _args: &[JsValue],
+        ) -> Option<::js_sys::Promise> {
+            Some(match method {
+                "getPoint" => self.get_point(),
+                "getPolygon" => self.get_polygon(),
+                "getExtrudedPolygon" => self.get_extruded_polygon(),
+                "getLine" => self.get_line(),
+                _ => return None,
+            })
+        }
+
+        pub(crate) fn listen(
+            &self,
+            event: &str,
+            handler: &Closure<dyn FnMut(JsValue)>,
+        ) -> Option<::js_sys::Promise> {
+            Some(match event {
+                "onEditStart" => self.on_edit_start(handler),
+                "onEditEnd" => self.on_edit_end(handler),
+                _ => return None,
+            })
+        }
+    }
 }
 
 /// Enable design tools supplied by the host app (point, polygon, line picking).
 pub struct DesignToolApi {
-    inner: js::DesignToolApi,
+    client: Client,
 }
 
+call_options!(DesignToolApi);
+
 impl DesignToolApi {
-    pub(crate) fn from_raw(raw: js::DesignToolApi) -> Self {
-        Self { inner: raw }
+    pub(crate) fn new(client: Client) -> Self {
+        Self { client }
     }
 
     /// Activate tool for creating a point by clicking in the 3D scene.
     /// Returns `None` if the user cancelled.
     pub async fn get_point(&self) -> Result<Option<Vec3>> {
-        let result = JsFuture::from(self.inner.get_point()).await?;
-        if result.is_undefined() || result.is_null() {
+        let result = self.client.call("getPoint", vec![]).await?;
+        if result.is_nullish() {
             return Ok(None);
         }
-        Ok(Some(serde_wasm_bindgen::from_value(result)?))
+        Ok(Some(self.client.response("getPoint", result)?))
     }
 
     /// Activate tool for creating a polygon.
     /// Returns `None` if the user cancelled.
     pub async fn get_polygon(&self) -> Result<Option<Vec<Vec3>>> {
-        let result = JsFuture::from(self.inner.get_polygon()).await?;
-        if result.is_undefined() || result.is_null() {
+        let result = self.client.call("getPolygon", vec![]).await?;
+        if result.is_nullish() {
             return Ok(None);
         }
-        Ok(Some(serde_wasm_bindgen::from_value(result)?))
+        Ok(Some(self.client.response("getPolygon", result)?))
     }
 
     /// Activate tool for creating an extruded polygon.
     /// Returns `None` if the user cancelled.
     pub async fn get_extruded_polygon(&self) -> Result<Option<ExtrudedPolygon>> {
-        let result = JsFuture::from(self.inner.get_extruded_polygon()).await?;
-        if result.is_undefined() || result.is_null() {
+        let result = self.client.call("getExtrudedPolygon", vec![]).await?;
+        if result.is_nullish() {
             return Ok(None);
         }
-        Ok(Some(serde_wasm_bindgen::from_value(result)?))
+        Ok(Some(self.client.response("getExtrudedPolygon", result)?))
     }
 
     /// Activate tool for creating a line.
     /// Returns `None` if the user cancelled.
     pub async fn get_line(&self) -> Result<Option<Line>> {
-        let result = JsFuture::from(self.inner.get_line()).await?;
-        if result.is_undefined() || result.is_null() {
+        let result = self.client.call("getLine", vec![]).await?;
+        if result.is_nullish() {
             return Ok(None);
         }
-        Ok(Some(serde_wasm_bindgen::from_value(result)?))
+        Ok(Some(self.client.response("getLine", result)?))
     }
 
     /// Subscribe to the 'start' event for edits with the drawing tools.
@@ -92,13 +122,9 @@ impl DesignToolApi {
         &self,
         mut callback: impl FnMut() + 'static,
     ) -> Result<VoidSubscription> {
-        let closure = Closure::wrap(Box::new(move || {
-            callback();
-        }) as Box<dyn FnMut()>);
-        let result = JsFuture::from(self.inner.on_edit_start(&closure)).await?;
-        let unsubscribe_fn: ::js_sys::Function =
-            ::js_sys::Reflect::get(&result, &"unsubscribe".into())?.into();
-        Ok(VoidSubscription::new(closure, unsubscribe_fn))
+        self.client
+            .subscribe("onEditStart", vec![], move |_| callback())
+            .await
     }
 
     /// Subscribe to the 'end' event for edits with the drawing tools.
@@ -106,12 +132,24 @@ impl DesignToolApi {
         &self,
         mut callback: impl FnMut() + 'static,
     ) -> Result<VoidSubscription> {
-        let closure = Closure::wrap(Box::new(move || {
-            callback();
-        }) as Box<dyn FnMut()>);
-        let result = JsFuture::from(self.inner.on_edit_end(&closure)).await?;
-        let unsubscribe_fn: ::js_sys::Function =
-            ::js_sys::Reflect::get(&result, &"unsubscribe".into())?.into();
-        Ok(VoidSubscription::new(closure, unsubscribe_fn))
+        self.client
+            .subscribe("onEditEnd", vec![], move |_| callback())
+            .await
+    }
+
+    /// 'start' events for edits with the drawing tools, as a stream.
+    /// Unsubscribes when dropped.
+    pub async fn edit_starts(&self) -> Result<EventStream<()>> {
+        let (sender, stream) = EventStream::channel();
+        let subscription = self.on_edit_start(move || sender.send(())).await?;
+        Ok(stream.attach(subscription))
+    }
+
+    /// 'end' events for edits with the drawing tools, as a stream.
+    /// Unsubscribes when dropped.
+    pub async fn edit_ends(&self) -> Result<EventStream<()>> {
+        let (sender, stream) = EventStream::channel();
+        let subscription = self.on_edit_end(move || sender.send(())).await?;
+        Ok(stream.attach(subscription))
     }
 }
@@ -0,0 +1,78 @@
+//! Unsaved-work tracking for embedded views.
+//!
+//! Components mark the work they have not yet persisted on a shared
+//! [`DirtyState`], and
+//! [`flush_on_close`](crate::EmbeddedViewSdk::flush_on_close) gives them a
+//! chance to save it before the user closes the panel:
+//!
+//! ```no_run
+//! use forma_embedded_view_sdk::dirty::DirtyState;
+//! use forma_embedded_view_sdk::forma;
+//!
+//! # fn example() -> forma_embedded_view_sdk::Result<()> {
+//! let dirty = DirtyState::new();
+//! dirty.mark("sketch");
+//!
+//! let state = dirty.clone();
+//! let _subscription = forma().flush_on_close(dirty.clone(), move |keys| {
+//!     let state = state.clone();
+//!     async move {
+//!         // Persist `keys`, then report whether the view may close.
+//!         keys.iter().for_each(|key| state.clear(key));
+//!         !state.is_dirty()
+//!     }
+//! })?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::rc::Rc;
+
+/// Set of keys naming unsaved work. Clones share the same state.
+#[derive(Clone, Default)]
+pub struct DirtyState {
+    keys: Rc<RefCell<BTreeSet<String>>>,
+}
+
+impl DirtyState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record unsaved work under `key`. Marking a dirty key again is a no-op.
+    pub fn mark(&self, key: impl Into<String>) {
+        self.keys.borrow_mut().insert(key.into());
+    }
+
+    /// Record that the work under `key` has been saved.
+    pub fn clear(&self, key: &str) {
+        self.keys.borrow_mut().remove(key);
+    }
+
+    /// Forget all unsaved work.
+    pub fn clear_all(&self) {
+        self.keys.borrow_mut().clear();
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        !self.keys.borrow().is_empty()
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.keys.borrow().contains(key)
+    }
+
+    /// The keys with unsaved work, in sorted order.
+    pub fn keys(&self) -> Vec<String> {
+        self.keys.borrow().iter().cloned().collect()
+    }
+}
+
+impl fmt::Debug for DirtyState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.keys.borrow().iter()).finish()
+    }
+}
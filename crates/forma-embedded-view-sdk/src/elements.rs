@@ -1,7 +1,9 @@
+use crate::client::Client;
+use crate::host::HostValue;
+use crate::options::call_options;
 use crate::types::*;
-use crate::Result;
-use wasm_bindgen::JsValue;
-use wasm_bindgen_futures::JsFuture;
+use crate::{polygon, ErrorDetails, Result, SdkError};
+use std::collections::BTreeMap;
 
 pub(crate) mod js {
     use wasm_bindgen::prelude::*;
@@ -32,6 +34,18 @@ pub(crate) mod js {
         pub fn edit_properties(this: &ElementsApi, request: &JsValue) -> ::js_sys::Promise;
     }
 
+    impl ElementsApi {
+        pub(crate) fn dispatch(&self, method: &str, args: &[JsValue]) -> Option<::js_sys::Promise> {
+            Some(match method {
+                "get" => self.get(&args[0]),
+                "getByPath" => self.get_by_path(&args[0]),
+                "getWorldTransform" => self.get_world_transform(&args[0]),
+                "editProperties" => self.edit_properties(&args[0]),
+                _ => return None,
+            })
+        }
+    }
+
     #[wasm_bindgen]
     extern "C" {
         pub type FloorStackApi;
@@ -46,6 +60,16 @@ pub(crate) mod js {
         ) -> ::js_sys::Promise;
     }
 
+    impl FloorStackApi {
+        pub(crate) fn dispatch(&self, method: &str, args: &[JsValue]) -> Option<::js_sys::Promise> {
+            Some(match method {
+                "createFromFloors" => self.create_from_floors(&args[0]),
+                "createFromFloorsBatch" => self.create_from_floors_batch(&args[0]),
+                _ => return None,
+            })
+        }
+    }
+
     #[wasm_bindgen]
     extern "C" {
         pub type RepresentationsApi;
@@ -66,6 +90,18 @@ pub(crate) mod js {
         pub fn graph_building(this: &RepresentationsApi, request: &JsValue) -> ::js_sys::Promise;
     }
 
+    impl RepresentationsApi {
+        pub(crate) fn dispatch(&self, method: &str, args: &[JsValue]) -> Option<::js_sys::Promise> {
+            Some(match method {
+                "volumeMesh" => self.volume_mesh(&args[0]),
+                "footprint" => self.footprint(&args[0]),
+                "grossFloorAreaPolygons" => self.gross_floor_area_polygons(&args[0]),
+                "graphBuilding" => self.graph_building(&args[0]),
+                _ => return None,
+            })
+        }
+    }
+
     #[wasm_bindgen]
     extern "C" {
         pub type BlobsApi;
@@ -73,51 +109,81 @@ pub(crate) mod js {
         #[wasm_bindgen(method)]
         pub fn get(this: &BlobsApi, request: &JsValue) -> ::js_sys::Promise;
     }
+
+    impl BlobsApi {
+        pub(crate) fn dispatch(&self, method: &str, args: &[JsValue]) -> Option<::js_sys::Promise> {
+            Some(match method {
+                "get" => self.get(&args[0]),
+                _ => return None,
+            })
+        }
+    }
 }
 
 /// Read elements and create buildings in Forma's element systems.
 pub struct ElementsApi {
-    inner: js::ElementsApi,
+    client: Client,
 }
 
+call_options!(ElementsApi, FloorStackApi, RepresentationsApi, BlobsApi);
+
 impl ElementsApi {
-    pub(crate) fn from_raw(raw: js::ElementsApi) -> Self {
-        Self { inner: raw }
+    pub(crate) fn new(client: Client) -> Self {
+        Self { client }
     }
 
     /// Access the floor stack sub-API.
     pub fn floor_stack(&self) -> FloorStackApi {
         FloorStackApi {
-            inner: self.inner.floor_stack(),
+            client: self.client.scoped("elements.floorStack"),
         }
     }
 
     /// Access the representations sub-API.
     pub fn representations(&self) -> RepresentationsApi {
         RepresentationsApi {
-            inner: self.inner.representations(),
+            client: self.client.scoped("elements.representations"),
         }
     }
 
     /// Access the blobs sub-API.
     pub fn blobs(&self) -> BlobsApi {
         BlobsApi {
-            inner: self.inner.blobs(),
+            client: self.client.scoped("elements.blobs"),
         }
     }
 
-    /// Get an element by URN.
-    pub async fn get(&self, request: &GetElementRequest) -> Result<serde_json::Value> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.get(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+    /// Get an element by URN, with the elements of its hierarchy.
+    pub async fn get(&self, request: &GetElementRequest) -> Result<ElementResponse> {
+        let result = self.get_raw(request).await?;
+        self.client.response("get", result.into())
+    }
+
+    /// Get an element by URN as untyped JSON.
+    pub async fn get_raw(&self, request: &GetElementRequest) -> Result<serde_json::Value> {
+        let result = self
+            .client
+            .call("get", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("get", result)
     }
 
     /// Get an element hierarchy located at a path relative to the root.
-    pub async fn get_by_path(&self, request: &GetElementByPathRequest) -> Result<serde_json::Value> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.get_by_path(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+    pub async fn get_by_path(&self, request: &GetElementByPathRequest) -> Result<ElementResponse> {
+        let result = self.get_by_path_raw(request).await?;
+        self.client.response("getByPath", result.into())
+    }
+
+    /// Get an element hierarchy located at a path as untyped JSON.
+    pub async fn get_by_path_raw(
+        &self,
+        request: &GetElementByPathRequest,
+    ) -> Result<serde_json::Value> {
+        let result = self
+            .client
+            .call("getByPath", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("getByPath", result)
     }
 
     /// Get the world transform of an element relative to the root element.
@@ -125,58 +191,298 @@ impl ElementsApi {
         &self,
         request: &GetWorldTransformRequest,
     ) -> Result<Transform> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.get_world_transform(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self
+            .client
+            .call("getWorldTransform", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("getWorldTransform", result)
     }
 
     /// Add, edit, and remove custom properties on elements via JSON Merge Patch.
     pub async fn edit_properties(&self, request: &EditPropertiesRequest) -> Result<()> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        JsFuture::from(self.inner.edit_properties(&js_req)).await?;
+        self.client
+            .call("editProperties", vec![HostValue::json(request)?])
+            .await?;
         Ok(())
     }
 }
 
 /// Create Floor Stack buildings.
 pub struct FloorStackApi {
-    inner: js::FloorStackApi,
+    client: Client,
 }
 
 impl FloorStackApi {
-    /// Create a 2.5D building from a stack of floors.
-    pub async fn create_from_floors(
+    /// Create a 2.5D building from a stack of floors. The stack is validated
+    /// first (see [`FloorStack::validate`]).
+    ///
+    /// ```no_run
+    /// # async fn run() -> forma_embedded_view_sdk::Result<()> {
+    /// use forma_embedded_view_sdk::forma;
+    /// use forma_embedded_view_sdk::types::{Floor, FloorStack, ProposalAddElementRequest};
+    ///
+    /// let outline = vec![[0.0, 0.0], [20.0, 0.0], [20.0, 12.0], [0.0, 12.0]];
+    /// let stack = FloorStack::new()
+    ///     .with_floor(Floor::new(outline.clone(), 4.5).with_function_id("commercial"))
+    ///     .with_floors(Floor::new(outline, 3.0).with_function_id("residential"), 5);
+    /// let building = forma().elements().floor_stack().create_from_floors(&stack).await?;
+    /// forma()
+    ///     .proposal()
+    ///     .add_element(&ProposalAddElementRequest {
+    ///         urn: building.urn,
+    ///         position: None,
+    ///         transform: None,
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn create_from_floors(&self, stack: &FloorStack) -> Result<UrnResult> {
+        stack.validate()?;
+        let request = CreateFromFloorsRequest::from(stack);
+        let result = self.create_from_floors_raw(&request).await?;
+        self.client.response("createFromFloors", result.into())
+    }
+
+    /// Send a floor stack request as is, returning untyped JSON.
+    pub async fn create_from_floors_raw(
         &self,
         request: &CreateFromFloorsRequest,
     ) -> Result<serde_json::Value> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.create_from_floors(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self
+            .client
+            .call("createFromFloors", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("createFromFloors", result)
     }
 
-    /// Create multiple 2.5D buildings from stacks of floors.
-    pub async fn create_from_floors_batch(
+    /// Create multiple 2.5D buildings from stacks of floors, returning their
+    /// URNs in the order of `stacks`. Nothing is sent if any stack is invalid.
+    pub async fn create_from_floors_batch(&self, stacks: &[FloorStack]) -> Result<Vec<UrnResult>> {
+        for (index, stack) in stacks.iter().enumerate() {
+            stack
+                .validate()
+                .map_err(|err| err.at(&format!("stacks[{index}]")))?;
+        }
+        let requests: Vec<_> = stacks.iter().map(CreateFromFloorsRequest::from).collect();
+        let result = self.create_from_floors_batch_raw(&requests).await?;
+        self.client.response("createFromFloorsBatch", result.into())
+    }
+
+    /// Send floor stack requests as is, returning untyped JSON.
+    pub async fn create_from_floors_batch_raw(
         &self,
-        request: &CreateFromFloorsRequest,
+        requests: &[CreateFromFloorsRequest],
     ) -> Result<serde_json::Value> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.create_from_floors_batch(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self
+            .client
+            .call("createFromFloorsBatch", vec![HostValue::json(&requests)?])
+            .await?;
+        self.client.response("createFromFloorsBatch", result)
+    }
+}
+
+impl FloorStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `floor` on top of the stack.
+    pub fn with_floor(mut self, floor: Floor) -> Self {
+        self.floors.push(floor);
+        self
+    }
+
+    /// Add `count` copies of `floor` on top of the stack.
+    pub fn with_floors(mut self, floor: Floor, count: usize) -> Self {
+        self.floors.extend(std::iter::repeat_n(floor, count));
+        self
+    }
+
+    /// Total height of the stack, in metres.
+    pub fn height(&self) -> f64 {
+        self.floors.iter().map(|floor| floor.height).sum()
+    }
+
+    /// Check that the stack has at least one floor and every floor is valid
+    /// (see [`Floor::validate`]).
+    pub fn validate(&self) -> Result<()> {
+        if self.floors.is_empty() {
+            return Err(SdkError::InvalidRequest(ErrorDetails::new(
+                "floor stack has no floors",
+            )));
+        }
+        for (index, floor) in self.floors.iter().enumerate() {
+            floor
+                .validate()
+                .map_err(|err| err.at(&format!("floors[{index}]")))?;
+        }
+        Ok(())
+    }
+}
+
+/// How far, in metres, a unit corner may stick out of its floor outline.
+const UNIT_TOLERANCE: f64 = 1e-6;
+
+impl Floor {
+    /// A floor of `height` metres over `polygon`, which is closed and made
+    /// counter-clockwise if needed.
+    pub fn new(polygon: Vec<Vec2>, height: f64) -> Self {
+        Self {
+            polygon: polygon::normalize(polygon),
+            height,
+            function_id: None,
+            units: Vec::new(),
+        }
+    }
+
+    pub fn with_function_id(mut self, function_id: impl Into<String>) -> Self {
+        self.function_id = Some(function_id.into());
+        self
+    }
+
+    pub fn with_unit(mut self, unit: FloorUnit) -> Self {
+        self.units.push(unit);
+        self
+    }
+
+    /// Check that the height is positive and finite, and that the outline
+    /// and every unit are closed, counter-clockwise rings that do not cross
+    /// themselves (see [`polygon::validate`]), with the units inside the
+    /// outline.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |message: String| SdkError::InvalidRequest(ErrorDetails::new(message));
+        if !(self.height > 0.0 && self.height.is_finite()) {
+            return Err(invalid(format!(
+                "height must be positive, got {}",
+                self.height
+            )));
+        }
+        polygon::validate(&self.polygon).map_err(|err| invalid(format!("polygon: {err}")))?;
+        for (index, unit) in self.units.iter().enumerate() {
+            polygon::validate(&unit.polygon)
+                .map_err(|err| invalid(format!("units[{index}]: polygon: {err}")))?;
+            if let Some(point) = unit
+                .polygon
+                .iter()
+                .position(|&point| !polygon::covers(&self.polygon, point, UNIT_TOLERANCE))
+            {
+                return Err(invalid(format!(
+                    "units[{index}]: point {point} lies outside the floor polygon"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FloorUnit {
+    /// A unit over `polygon`, which is closed and made counter-clockwise if
+    /// needed.
+    pub fn new(polygon: Vec<Vec2>) -> Self {
+        Self {
+            polygon: polygon::normalize(polygon),
+            program: None,
+            function_id: None,
+        }
+    }
+
+    pub fn with_program(mut self, program: UnitProgram) -> Self {
+        self.program = Some(program);
+        self
+    }
+
+    pub fn with_function_id(mut self, function_id: impl Into<String>) -> Self {
+        self.function_id = Some(function_id.into());
+        self
+    }
+}
+
+impl From<&FloorStack> for CreateFromFloorsRequest {
+    /// Floors without units are sent as outlines. Each floor with units gets
+    /// a plan of its own, with corners shared between units merged into one
+    /// vertex.
+    fn from(stack: &FloorStack) -> Self {
+        let mut floors = Vec::with_capacity(stack.floors.len());
+        let mut plans = Vec::new();
+        for floor in &stack.floors {
+            if floor.units.is_empty() {
+                floors.push(FloorStackFloor::Polygon {
+                    polygon: floor.polygon.clone(),
+                    height: floor.height,
+                    function_id: floor.function_id.clone(),
+                });
+                continue;
+            }
+            let plan = floor_plan(format!("plan{}", plans.len()), floor);
+            floors.push(FloorStackFloor::Plan {
+                plan_id: plan.id.clone(),
+                height: floor.height,
+            });
+            plans.push(plan);
+        }
+        Self {
+            floors,
+            plans,
+            extra: serde_json::Map::new(),
+        }
+    }
+}
+
+fn floor_plan(id: String, floor: &Floor) -> FloorPlan {
+    let mut vertices: Vec<FloorPlanVertex> = Vec::new();
+    let mut vertex_ids = BTreeMap::new();
+    let mut vertex_id = |[x, y]: Vec2| -> String {
+        vertex_ids
+            .entry((x.to_bits(), y.to_bits()))
+            .or_insert_with(|| {
+                let id = format!("v{}", vertices.len());
+                vertices.push(FloorPlanVertex {
+                    id: id.clone(),
+                    x,
+                    y,
+                });
+                id
+            })
+            .clone()
+    };
+    let units = floor
+        .units
+        .iter()
+        .map(|unit| {
+            // Plan rings are implicitly closed.
+            let open = &unit.polygon[..unit.polygon.len().saturating_sub(1)];
+            FloorPlanUnit {
+                polygon: open.iter().map(|&point| vertex_id(point)).collect(),
+                holes: Vec::new(),
+                program: unit.program,
+                function_id: unit
+                    .function_id
+                    .clone()
+                    .or_else(|| floor.function_id.clone()),
+            }
+        })
+        .collect();
+    FloorPlan {
+        id,
+        vertices,
+        units,
     }
 }
 
 /// Access derived representations of elements (volume mesh, footprint, etc.).
 pub struct RepresentationsApi {
-    inner: js::RepresentationsApi,
+    client: Client,
 }
 
 impl RepresentationsApi {
     /// Get the volume mesh for an element (GLB binary data).
     pub async fn volume_mesh(&self, request: &VolumeMeshRequest) -> Result<Vec<u8>> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.volume_mesh(&js_req)).await?;
-        let array = ::js_sys::Uint8Array::new(&result);
-        Ok(array.to_vec())
+        let result = self
+            .client
+            .call("volumeMesh", vec![HostValue::json(request)?])
+            .await?;
+        self.client.bytes("volumeMesh", result)
     }
 
     /// Get the footprint of an element (GeoJSON FeatureCollection).
@@ -184,9 +490,11 @@ impl RepresentationsApi {
         &self,
         request: &RepresentationFootprintRequest,
     ) -> Result<serde_json::Value> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.footprint(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self
+            .client
+            .call("footprint", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("footprint", result)
     }
 
     /// Get the gross floor area polygons for an element.
@@ -194,9 +502,11 @@ impl RepresentationsApi {
         &self,
         request: &RepresentationFootprintRequest,
     ) -> Result<serde_json::Value> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.gross_floor_area_polygons(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self
+            .client
+            .call("grossFloorAreaPolygons", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("grossFloorAreaPolygons", result)
     }
 
     /// Get the graph building representation for an element.
@@ -204,24 +514,26 @@ impl RepresentationsApi {
         &self,
         request: &GraphBuildingRequest,
     ) -> Result<serde_json::Value> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.graph_building(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self
+            .client
+            .call("graphBuilding", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("graphBuilding", result)
     }
 }
 
 /// Read blobs related to an element.
 pub struct BlobsApi {
-    inner: js::BlobsApi,
+    client: Client,
 }
 
 impl BlobsApi {
     /// Retrieve a blob by its ID. Returns the raw binary data.
     pub async fn get(&self, request: &BlobGetRequest) -> Result<Vec<u8>> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.get(&js_req)).await?;
-        let data = ::js_sys::Reflect::get(&result, &JsValue::from_str("data"))?;
-        let array = ::js_sys::Uint8Array::new(&data);
-        Ok(array.to_vec())
+        let mut result = self
+            .client
+            .call("get", vec![HostValue::json(request)?])
+            .await?;
+        self.client.bytes("get", result.take("data"))
     }
 }
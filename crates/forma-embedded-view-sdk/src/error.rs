@@ -1,26 +1,276 @@
+use serde::{Deserialize, Serialize};
 use std::fmt;
 use wasm_bindgen::JsValue;
 
-#[derive(Debug)]
+/// What the host reported about a failed call.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorDetails {
+    /// JS error name (`"TypeError"`, `"AbortError"`), if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stack: Option<String>,
+    /// HTTP status attached to the error, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// SDK method that failed, as `"api.method"` (`"proposal.addElement"`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+}
+
+impl ErrorDetails {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            ..Self::default()
+        }
+    }
+}
+
+impl fmt::Display for ErrorDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(method) = &self.method {
+            write!(f, " in {method}")?;
+        }
+        f.write_str(": ")?;
+        if let Some(name) = &self.name {
+            write!(f, "{name}: ")?;
+        }
+        f.write_str(&self.message)?;
+        if let Some(status) = self.status {
+            write!(f, " (status {status})")?;
+        }
+        Ok(())
+    }
+}
+
+/// A host response whose shape the SDK did not expect.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodeError {
+    /// SDK method that returned the value, as `"api.method"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    /// What the SDK expected (`"a number"`, `"a boolean"`).
+    pub expected: String,
+    /// The value the host returned. Opaque handles are reported as `null`.
+    pub received: serde_json::Value,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("unexpected response")?;
+        if let Some(method) = &self.method {
+            write!(f, " from {method}")?;
+        }
+        write!(
+            f,
+            ": expected {}, received {}",
+            self.expected, self.received
+        )
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Errors returned by SDK calls, classified by failure kind.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "details", rename_all = "camelCase")]
 pub enum SdkError {
-    Js(JsValue),
+    /// The user or project lacks the required permission (e.g. read-only project).
+    PermissionDenied(ErrorDetails),
+    /// The requested element, proposal, object or id does not exist.
+    NotFound(ErrorDetails),
+    /// The call was aborted before it completed.
+    Cancelled(ErrorDetails),
+    /// The host did not answer in time.
+    Timeout(ErrorDetails),
+    /// The host is unreachable (panel detached, host reloading).
+    HostUnavailable(ErrorDetails),
+    /// The host does not implement the requested `api.method`.
+    Unsupported(ErrorDetails),
+    /// Any other failure reported by the host.
+    Host(ErrorDetails),
+    /// The request failed validation and was not sent to the host.
+    InvalidRequest(ErrorDetails),
+    /// The host answered with a value of an unexpected shape.
+    Decode(DecodeError),
     Serialization(String),
 }
 
-impl fmt::Display for SdkError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl SdkError {
+    /// Details of the failure, or `None` for decode and serialization errors.
+    pub fn details(&self) -> Option<&ErrorDetails> {
+        match self {
+            SdkError::PermissionDenied(details)
+            | SdkError::NotFound(details)
+            | SdkError::Cancelled(details)
+            | SdkError::Timeout(details)
+            | SdkError::HostUnavailable(details)
+            | SdkError::Unsupported(details)
+            | SdkError::Host(details)
+            | SdkError::InvalidRequest(details) => Some(details),
+            SdkError::Decode(_) | SdkError::Serialization(_) => None,
+        }
+    }
+
+    /// The SDK method that failed, as `"api.method"`.
+    pub fn method(&self) -> Option<&str> {
         match self {
-            SdkError::Js(val) => write!(f, "JS error: {:?}", val),
-            SdkError::Serialization(msg) => write!(f, "Serialization error: {msg}"),
+            SdkError::Decode(err) => err.method.as_deref(),
+            _ => self.details()?.method.as_deref(),
+        }
+    }
+
+    /// Whether the failure is likely to go away on retry: timeouts, an
+    /// unavailable host, rate limiting (429) and server errors (5xx).
+    pub fn is_transient(&self) -> bool {
+        match self {
+            SdkError::Timeout(_) | SdkError::HostUnavailable(_) => true,
+            SdkError::Host(details) => {
+                matches!(details.status, Some(429) | Some(500..=599))
+            }
+            _ => false,
+        }
+    }
+
+    /// Classify a host failure from its JS error name, HTTP status and message.
+    pub fn classify(details: ErrorDetails) -> Self {
+        let message = details.message.to_lowercase();
+        let has = |needles: &[&str]| needles.iter().any(|needle| message.contains(needle));
+        let variant: fn(ErrorDetails) -> SdkError = match (details.name.as_deref(), details.status)
+        {
+            (Some("AbortError"), _) => SdkError::Cancelled,
+            (Some("TimeoutError"), _) => SdkError::Timeout,
+            (Some("NotAllowedError" | "SecurityError"), _) | (_, Some(401 | 403)) => {
+                SdkError::PermissionDenied
+            }
+            (Some("NotFoundError"), _) | (_, Some(404 | 410)) => SdkError::NotFound,
+            (Some("NotSupportedError"), _) => SdkError::Unsupported,
+            (_, Some(408 | 504)) => SdkError::Timeout,
+            (_, Some(502 | 503)) => SdkError::HostUnavailable,
+            _ if has(&[
+                "permission",
+                "not allowed",
+                "forbidden",
+                "unauthorized",
+                "read-only",
+            ]) =>
+            {
+                SdkError::PermissionDenied
+            }
+            _ if has(&["not found", "does not exist"]) => SdkError::NotFound,
+            _ if has(&["aborted", "cancelled", "canceled"]) => SdkError::Cancelled,
+            _ if has(&["timed out", "timeout"]) => SdkError::Timeout,
+            _ if has(&["not connected", "disconnected", "port closed"]) => {
+                SdkError::HostUnavailable
+            }
+            _ if has(&["not supported", "not implemented", "is not a function"]) => {
+                SdkError::Unsupported
+            }
+            _ => SdkError::Host,
+        };
+        variant(details)
+    }
+
+    /// Prefix the message of a validation error with the location `at`
+    /// (`"floors[2]"`). Other errors are returned unchanged.
+    pub(crate) fn at(self, at: &str) -> Self {
+        match self {
+            SdkError::InvalidRequest(mut details) => {
+                details.message = format!("{at}: {}", details.message);
+                SdkError::InvalidRequest(details)
+            }
+            err => err,
+        }
+    }
+
+    /// Record the failing method if the host did not.
+    pub(crate) fn in_method(mut self, api: &str, method: &str) -> Self {
+        let failed = match &mut self {
+            SdkError::Decode(err) => Some(&mut err.method),
+            _ => self.details_mut().map(|details| &mut details.method),
+        };
+        if let Some(failed) = failed {
+            failed.get_or_insert_with(|| format!("{api}.{method}"));
+        }
+        self
+    }
+
+    fn details_mut(&mut self) -> Option<&mut ErrorDetails> {
+        match self {
+            SdkError::PermissionDenied(details)
+            | SdkError::NotFound(details)
+            | SdkError::Cancelled(details)
+            | SdkError::Timeout(details)
+            | SdkError::HostUnavailable(details)
+            | SdkError::Unsupported(details)
+            | SdkError::Host(details)
+            | SdkError::InvalidRequest(details) => Some(details),
+            SdkError::Decode(_) | SdkError::Serialization(_) => None,
         }
     }
 }
 
+impl fmt::Display for SdkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (label, details) = match self {
+            SdkError::PermissionDenied(details) => ("Permission denied", details),
+            SdkError::NotFound(details) => ("Not found", details),
+            SdkError::Cancelled(details) => ("Cancelled", details),
+            SdkError::Timeout(details) => ("Timed out", details),
+            SdkError::HostUnavailable(details) => ("Host unavailable", details),
+            SdkError::Unsupported(details) => ("Unsupported by host", details),
+            SdkError::Host(details) => ("Host error", details),
+            SdkError::InvalidRequest(details) => ("Invalid request", details),
+            SdkError::Decode(err) => return write!(f, "Decode error: {err}"),
+            SdkError::Serialization(msg) => return write!(f, "Serialization error: {msg}"),
+        };
+        write!(f, "{label}{details}")
+    }
+}
+
 impl std::error::Error for SdkError {}
 
+/// Parse a rejected promise value (an `Error`, a plain object, or a string).
 impl From<JsValue> for SdkError {
     fn from(val: JsValue) -> Self {
-        SdkError::Js(val)
+        let get = |key: &str| ::js_sys::Reflect::get(&val, &JsValue::from_str(key)).ok();
+        let get_string = |key: &str| get(key).and_then(|v| v.as_string());
+        let status = || {
+            let response = get("response").filter(|r| r.is_object());
+            [
+                get("status"),
+                get("statusCode"),
+                response
+                    .and_then(|r| ::js_sys::Reflect::get(&r, &JsValue::from_str("status")).ok()),
+            ]
+            .into_iter()
+            .flatten()
+            .find_map(|v| v.as_f64())
+            .map(|status| status as u16)
+        };
+        let details = if let Some(message) = val.as_string() {
+            ErrorDetails::new(message)
+        } else if val.is_object() {
+            ErrorDetails {
+                name: get_string("name"),
+                message: get_string("message").unwrap_or_else(|| {
+                    ::js_sys::JSON::stringify(&val)
+                        .ok()
+                        .and_then(|s| s.as_string())
+                        .unwrap_or_else(|| format!("{val:?}"))
+                }),
+                stack: get_string("stack"),
+                status: status(),
+                method: None,
+            }
+        } else {
+            ErrorDetails::new(format!("{val:?}"))
+        };
+        SdkError::classify(details)
     }
 }
 
@@ -29,3 +279,9 @@ impl From<serde_wasm_bindgen::Error> for SdkError {
         SdkError::Serialization(format!("{err}"))
     }
 }
+
+impl From<serde_json::Error> for SdkError {
+    fn from(err: serde_json::Error) -> Self {
+        SdkError::Serialization(format!("{err}"))
+    }
+}
@@ -1,8 +1,11 @@
+use crate::client::Client;
+use crate::host::HostValue;
+use crate::options::call_options;
 use crate::types::*;
 use crate::Result;
-use wasm_bindgen_futures::JsFuture;
 
 pub(crate) mod js {
+    use crate::js_host::optional;
     use wasm_bindgen::prelude::*;
 
     #[wasm_bindgen]
@@ -16,6 +19,15 @@ pub(crate) mod js {
         pub fn invoke_endpoint(this: &ExtensionsApi, request: &JsValue) -> ::js_sys::Promise;
     }
 
+    impl ExtensionsApi {
+        pub(crate) fn dispatch(&self, method: &str, args: &[JsValue]) -> Option<::js_sys::Promise> {
+            Some(match method {
+                "invokeEndpoint" => self.invoke_endpoint(&args[0]),
+                _ => return None,
+            })
+        }
+    }
+
     #[wasm_bindgen]
     extern "C" {
         pub type ExtensionsStorageApi;
@@ -44,22 +56,37 @@ pub(crate) mod js {
             request: &JsValue,
         ) -> ::js_sys::Promise;
     }
+
+    impl ExtensionsStorageApi {
+        pub(crate) fn dispatch(&self, method: &str, args: &[JsValue]) -> Option<::js_sys::Promise> {
+            Some(match method {
+                "setObject" => self.set_object(&args[0]),
+                "getTextObject" => self.get_text_object(&args[0]),
+                "getBinaryObject" => self.get_binary_object(&args[0]),
+                "listObjects" => self.list_objects(optional(&args[0])),
+                "deleteObject" => self.delete_object(&args[0]),
+                _ => return None,
+            })
+        }
+    }
 }
 
 /// Access extension-specific services such as endpoints and cloud storage.
 pub struct ExtensionsApi {
-    inner: js::ExtensionsApi,
+    client: Client,
 }
 
+call_options!(ExtensionsApi, ExtensionsStorageApi);
+
 impl ExtensionsApi {
-    pub(crate) fn from_raw(raw: js::ExtensionsApi) -> Self {
-        Self { inner: raw }
+    pub(crate) fn new(client: Client) -> Self {
+        Self { client }
     }
 
     /// Access the extension storage sub-API.
     pub fn storage(&self) -> ExtensionsStorageApi {
         ExtensionsStorageApi {
-            inner: self.inner.storage(),
+            client: self.client.scoped("extensions.storage"),
         }
     }
 
@@ -68,22 +95,25 @@ impl ExtensionsApi {
         &self,
         request: &InvokeEndpointRequest,
     ) -> Result<serde_json::Value> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.invoke_endpoint(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self
+            .client
+            .call("invokeEndpoint", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("invokeEndpoint", result)
     }
 }
 
 /// Extension storage backed by AWS S3 for saving data per authcontext.
 pub struct ExtensionsStorageApi {
-    inner: js::ExtensionsStorageApi,
+    client: Client,
 }
 
 impl ExtensionsStorageApi {
     /// Add or replace a storage object.
     pub async fn set_object(&self, request: &StorageSetObjectRequest) -> Result<()> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        JsFuture::from(self.inner.set_object(&js_req)).await?;
+        self.client
+            .call("setObject", vec![HostValue::json(request)?])
+            .await?;
         Ok(())
     }
 
@@ -92,9 +122,11 @@ impl ExtensionsStorageApi {
         &self,
         request: &StorageGetTextObjectRequest,
     ) -> Result<serde_json::Value> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.get_text_object(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self
+            .client
+            .call("getTextObject", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("getTextObject", result)
     }
 
     /// Fetch binary data for the specified key.
@@ -102,10 +134,11 @@ impl ExtensionsStorageApi {
         &self,
         request: &StorageGetBinaryObjectRequest,
     ) -> Result<Vec<u8>> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.get_binary_object(&js_req)).await?;
-        let array = ::js_sys::Uint8Array::new(&result);
-        Ok(array.to_vec())
+        let result = self
+            .client
+            .call("getBinaryObject", vec![HostValue::json(request)?])
+            .await?;
+        self.client.bytes("getBinaryObject", result)
     }
 
     /// List all storage objects for the extension.
@@ -114,17 +147,18 @@ impl ExtensionsStorageApi {
         request: Option<&StorageListObjectsRequest>,
     ) -> Result<serde_json::Value> {
         let js_req = request
-            .map(serde_wasm_bindgen::to_value)
-            .transpose()?;
-        let result =
-            JsFuture::from(self.inner.list_objects(js_req.as_ref())).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+            .map(HostValue::json)
+            .transpose()?
+            .unwrap_or(HostValue::Undefined);
+        let result = self.client.call("listObjects", vec![js_req]).await?;
+        self.client.response("listObjects", result)
     }
 
     /// Delete object corresponding to the specified key.
     pub async fn delete_object(&self, request: &StorageDeleteObjectRequest) -> Result<()> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        JsFuture::from(self.inner.delete_object(&js_req)).await?;
+        self.client
+            .call("deleteObject", vec![HostValue::json(request)?])
+            .await?;
         Ok(())
     }
 }
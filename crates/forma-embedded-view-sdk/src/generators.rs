@@ -1,8 +1,12 @@
+use crate::client::Client;
+use crate::host::HostValue;
+use crate::options::call_options;
 use crate::types::*;
-use crate::Result;
-use wasm_bindgen_futures::JsFuture;
+use crate::{ErrorDetails, Result, SdkError};
+use std::collections::BTreeSet;
 
 pub(crate) mod js {
+    use crate::js_host::optional;
     use wasm_bindgen::prelude::*;
 
     #[wasm_bindgen]
@@ -15,34 +19,174 @@ pub(crate) mod js {
         #[wasm_bindgen(method)]
         pub fn list(this: &GeneratorsApi, request: Option<&JsValue>) -> ::js_sys::Promise;
     }
+
+    impl GeneratorsApi {
+        pub(crate) fn dispatch(&self, method: &str, args: &[JsValue]) -> Option<::js_sys::Promise> {
+            Some(match method {
+                "put" => self.put(&args[0]),
+                "list" => self.list(optional(&args[0])),
+                _ => return None,
+            })
+        }
+    }
 }
 
 /// Manage generators registered in Forma.
 pub struct GeneratorsApi {
-    inner: js::GeneratorsApi,
+    client: Client,
 }
 
+call_options!(GeneratorsApi);
+
 impl GeneratorsApi {
-    pub(crate) fn from_raw(raw: js::GeneratorsApi) -> Self {
-        Self { inner: raw }
+    pub(crate) fn new(client: Client) -> Self {
+        Self { client }
     }
 
-    /// Create or replace a generator.
-    pub async fn put(&self, request: &GeneratorPutRequest) -> Result<serde_json::Value> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.put(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+    /// Create or replace a generator. Fails with
+    /// [`SdkError::InvalidRequest`] without calling the host if the
+    /// definition does not [`validate`](GeneratorDefinition::validate).
+    pub async fn put(&self, request: &GeneratorPutRequest) -> Result<GeneratorDefinition> {
+        request
+            .generator
+            .validate()
+            .map_err(|err| self.client.in_method("put", err))?;
+        let result = self
+            .client
+            .call("put", vec![HostValue::json(request)?])
+            .await?;
+        if result.is_nullish() {
+            return Ok(request.generator.clone());
+        }
+        self.client.response("put", result)
     }
 
-    /// List generators within the specified authcontext.
-    pub async fn list(
+    /// List a page of generators within the specified authcontext.
+    pub async fn list(&self, request: Option<&GeneratorListRequest>) -> Result<GeneratorList> {
+        let result = self.client.call("list", vec![list_arg(request)?]).await?;
+        self.client.response("list", result)
+    }
+
+    /// List a page of generators as untyped JSON.
+    pub async fn list_raw(
         &self,
         request: Option<&GeneratorListRequest>,
     ) -> Result<serde_json::Value> {
-        let js_req = request
-            .map(serde_wasm_bindgen::to_value)
-            .transpose()?;
-        let result = JsFuture::from(self.inner.list(js_req.as_ref())).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self.client.call("list", vec![list_arg(request)?]).await?;
+        self.client.response("list", result)
+    }
+
+    /// List every generator within `authcontext` (the current project if
+    /// `None`), following pagination. Stops at a token already followed, so
+    /// a host cycling through pages cannot loop forever.
+    pub async fn list_all(&self, authcontext: Option<&str>) -> Result<Vec<GeneratorDefinition>> {
+        let mut request = GeneratorListRequest {
+            authcontext: authcontext.map(str::to_string),
+            ..GeneratorListRequest::default()
+        };
+        let mut generators = Vec::new();
+        let mut seen = BTreeSet::new();
+        loop {
+            let page = self.list(Some(&request)).await?;
+            generators.extend(page.generators);
+            match page.next_token {
+                Some(token) if seen.insert(token.clone()) => request.next_token = Some(token),
+                _ => return Ok(generators),
+            }
+        }
+    }
+}
+
+fn list_arg(request: Option<&GeneratorListRequest>) -> Result<HostValue> {
+    Ok(request
+        .map(HostValue::json)
+        .transpose()?
+        .unwrap_or(HostValue::Undefined))
+}
+
+impl GeneratorDefinition {
+    pub fn new(
+        id: impl Into<String>,
+        name: impl Into<String>,
+        authcontext: impl Into<String>,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            name: name.into(),
+            authcontext: authcontext.into(),
+            input_schema: serde_json::Value::Null,
+            output_element_type: None,
+            endpoint: None,
+            version: None,
+            description: None,
+            extra: serde_json::Map::new(),
+        }
+    }
+
+    /// Set the JSON Schema of the generator's input.
+    pub fn with_input_schema(mut self, schema: serde_json::Value) -> Self {
+        self.input_schema = schema;
+        self
+    }
+
+    pub fn with_output_element_type(mut self, element_type: impl Into<String>) -> Self {
+        self.output_element_type = Some(element_type.into());
+        self
+    }
+
+    /// Run the generator through `endpoint_id` of extension `extension_id`.
+    pub fn with_endpoint(
+        mut self,
+        extension_id: impl Into<String>,
+        endpoint_id: impl Into<String>,
+    ) -> Self {
+        self.endpoint = Some(GeneratorEndpoint {
+            extension_id: extension_id.into(),
+            endpoint_id: endpoint_id.into(),
+        });
+        self
+    }
+
+    pub fn with_version(mut self, version: impl Into<String>) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Check that the id, name and authcontext are set, the input schema
+    /// (if any) is a JSON Schema object, and the endpoint (if any) names
+    /// both an extension and an endpoint.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |message: String| {
+            Err(SdkError::InvalidRequest(ErrorDetails::new(format!(
+                "generator {:?}: {message}",
+                self.id
+            ))))
+        };
+        for (field, value) in [
+            ("id", &self.id),
+            ("name", &self.name),
+            ("authcontext", &self.authcontext),
+        ] {
+            if value.trim().is_empty() {
+                return invalid(format!("{field} is empty"));
+            }
+        }
+        if !matches!(
+            self.input_schema,
+            serde_json::Value::Null | serde_json::Value::Bool(_) | serde_json::Value::Object(_)
+        ) {
+            return invalid("inputSchema is not a JSON Schema object".to_string());
+        }
+        if let Some(endpoint) = &self.endpoint {
+            if endpoint.extension_id.is_empty() || endpoint.endpoint_id.is_empty() {
+                return invalid("endpoint needs an extensionId and an endpointId".to_string());
+            }
+        }
+        Ok(())
     }
 }
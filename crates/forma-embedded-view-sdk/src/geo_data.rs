@@ -1,6 +1,8 @@
+use crate::client::Client;
+use crate::host::HostValue;
+use crate::options::call_options;
 use crate::types::*;
 use crate::Result;
-use wasm_bindgen_futures::JsFuture;
 
 pub(crate) mod js {
     use wasm_bindgen::prelude::*;
@@ -12,23 +14,36 @@ pub(crate) mod js {
         #[wasm_bindgen(method)]
         pub fn upload(this: &GeoDataApi, request: &JsValue) -> ::js_sys::Promise;
     }
+
+    impl GeoDataApi {
+        pub(crate) fn dispatch(&self, method: &str, args: &[JsValue]) -> Option<::js_sys::Promise> {
+            Some(match method {
+                "upload" => self.upload(&args[0]),
+                _ => return None,
+            })
+        }
+    }
 }
 
 /// Upload buildings, roads, and property boundaries as GeoJSON.
 pub struct GeoDataApi {
-    inner: js::GeoDataApi,
+    client: Client,
 }
 
+call_options!(GeoDataApi);
+
 impl GeoDataApi {
-    pub(crate) fn from_raw(raw: js::GeoDataApi) -> Self {
-        Self { inner: raw }
+    pub(crate) fn new(client: Client) -> Self {
+        Self { client }
     }
 
     /// Upload GeoJSON data to Forma and add it to the library.
     /// Supports 2.5D buildings, roads, and property boundaries.
     pub async fn upload(&self, request: &GeoDataUploadRequest) -> Result<LibraryItem> {
-        let js_request = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.upload(&js_request)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self
+            .client
+            .call("upload", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("upload", result)
     }
 }
@@ -1,8 +1,11 @@
+use crate::client::Client;
+use crate::host::HostValue;
+use crate::options::call_options;
 use crate::types::*;
 use crate::Result;
-use wasm_bindgen_futures::JsFuture;
 
 pub(crate) mod js {
+    use crate::js_host::optional;
     use wasm_bindgen::prelude::*;
 
     #[wasm_bindgen]
@@ -30,16 +33,33 @@ pub(crate) mod js {
             request: &JsValue,
         ) -> ::js_sys::Promise;
     }
+
+    impl GeometryApi {
+        pub(crate) fn dispatch(&self, method: &str, args: &[JsValue]) -> Option<::js_sys::Promise> {
+            Some(match method {
+                "getPathsByCategory" => self.get_paths_by_category(&args[0]),
+                "getPathsForVirtualElements" => {
+                    self.get_paths_for_virtual_elements(optional(&args[0]))
+                }
+                "getFootprint" => self.get_footprint(&args[0]),
+                "getTriangles" => self.get_triangles(optional(&args[0])),
+                "getPathsInsidePolygons" => self.get_paths_inside_polygons(&args[0]),
+                _ => return None,
+            })
+        }
+    }
 }
 
 /// Read geometry data from Forma (paths by category, footprints, triangles).
 pub struct GeometryApi {
-    inner: js::GeometryApi,
+    client: Client,
 }
 
+call_options!(GeometryApi);
+
 impl GeometryApi {
-    pub(crate) fn from_raw(raw: js::GeometryApi) -> Self {
-        Self { inner: raw }
+    pub(crate) fn new(client: Client) -> Self {
+        Self { client }
     }
 
     /// Fetch paths of all elements tagged with a specific category.
@@ -47,16 +67,20 @@ impl GeometryApi {
         &self,
         request: &GetPathsByCategoryRequest,
     ) -> Result<Vec<String>> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.get_paths_by_category(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self
+            .client
+            .call("getPathsByCategory", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("getPathsByCategory", result)
     }
 
     /// Fetch paths of all elements with the 'virtual' property set to true.
     pub async fn get_paths_for_virtual_elements(&self) -> Result<Vec<String>> {
-        let result =
-            JsFuture::from(self.inner.get_paths_for_virtual_elements(None)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self
+            .client
+            .call("getPathsForVirtualElements", vec![])
+            .await?;
+        self.client.response("getPathsForVirtualElements", result)
     }
 
     /// Fetch the footprint representation of an element.
@@ -64,9 +88,11 @@ impl GeometryApi {
         &self,
         request: &GetFootprintRequest,
     ) -> Result<serde_json::Value> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.get_footprint(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self
+            .client
+            .call("getFootprint", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("getFootprint", result)
     }
 
     /// Fetch concatenated triangle mesh for an element and its children.
@@ -76,12 +102,11 @@ impl GeometryApi {
         request: Option<&GetTrianglesRequest>,
     ) -> Result<Vec<f32>> {
         let js_req = request
-            .map(serde_wasm_bindgen::to_value)
-            .transpose()?;
-        let result =
-            JsFuture::from(self.inner.get_triangles(js_req.as_ref())).await?;
-        let typed_array: ::js_sys::Float32Array = result.into();
-        Ok(typed_array.to_vec())
+            .map(HostValue::json)
+            .transpose()?
+            .unwrap_or(HostValue::Undefined);
+        let result = self.client.call("getTriangles", vec![js_req]).await?;
+        self.client.floats("getTriangles", result)
     }
 
     /// Get all paths where geometry overlaps the provided polygons.
@@ -89,8 +114,10 @@ impl GeometryApi {
         &self,
         request: &GetPathsInsidePolygonsRequest,
     ) -> Result<Vec<String>> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.get_paths_inside_polygons(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self
+            .client
+            .call("getPathsInsidePolygons", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("getPathsInsidePolygons", result)
     }
 }
@@ -0,0 +1,267 @@
+use crate::error::ErrorDetails;
+use crate::subscription::Subscription;
+use crate::{Result, SdkError};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use wasm_bindgen::JsValue;
+
+pub use crate::js_host::JsHost;
+
+/// Boxed, non-`Send` future returned by [`FormaHost`] methods.
+pub type HostFuture<T> = Pin<Box<dyn Future<Output = Result<T>>>>;
+
+/// Callback invoked by a host for every event delivered on a subscription.
+pub type HostHandler = Box<dyn FnMut(HostValue)>;
+
+/// Callback invoked by a host for every event of a subscription whose answer
+/// the host waits for.
+pub type HostReplyHandler = Box<dyn FnMut(HostValue) -> HostFuture<HostValue>>;
+
+/// A value exchanged between the SDK wrappers and a [`FormaHost`].
+///
+/// Most requests and responses are plain JSON. Binary payloads (meshes, GLBs,
+/// blobs) and dates are kept as dedicated variants so that a host can map
+/// them onto the matching JS types without a lossy JSON round-trip.
+///
+/// Host values serialize with an external tag (`{"json": ...}`,
+/// `{"bytes": [...]}`) so that they round-trip through fixtures; `Handle`
+/// cannot be serialized.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HostValue {
+    /// No value (`undefined` in JS). Used for omitted optional arguments.
+    Undefined,
+    /// Any JSON-compatible value.
+    Json(serde_json::Value),
+    /// Byte array (`Uint8Array` in JS).
+    Bytes(Vec<u8>),
+    /// Raw binary buffer (`ArrayBuffer` in JS).
+    Buffer(Vec<u8>),
+    /// Float array (`Float32Array` in JS).
+    Floats(Vec<f32>),
+    /// ISO 8601 date (`Date` in JS).
+    Date(String),
+    /// RGBA pixels, row by row from the top (`HTMLCanvasElement` in JS).
+    Image {
+        width: u32,
+        height: u32,
+        rgba: Vec<u8>,
+    },
+    /// Object with at least one non-JSON member.
+    Object(BTreeMap<String, HostValue>),
+    /// Opaque host object such as a canvas or a message port.
+    #[serde(skip)]
+    Handle(JsValue),
+}
+
+impl HostValue {
+    /// Serialize a request into a JSON host value.
+    pub fn json<T: Serialize + ?Sized>(value: &T) -> Result<Self> {
+        Ok(HostValue::Json(serde_json::to_value(value)?))
+    }
+
+    /// Deserialize a JSON host value into a response type.
+    /// `Undefined` is treated as JSON `null`.
+    pub fn deserialize<T: DeserializeOwned>(self) -> Result<T> {
+        Ok(serde_json::from_value(self.into_json()?)?)
+    }
+
+    /// Whether this is `Undefined` or JSON `null`.
+    pub fn is_nullish(&self) -> bool {
+        matches!(
+            self,
+            HostValue::Undefined | HostValue::Json(serde_json::Value::Null)
+        )
+    }
+
+    /// Convert to a JSON value. Binary members are converted to arrays of
+    /// numbers; opaque handles cannot be converted.
+    pub fn into_json(self) -> Result<serde_json::Value> {
+        match self {
+            HostValue::Undefined => Ok(serde_json::Value::Null),
+            HostValue::Json(value) => Ok(value),
+            HostValue::Bytes(bytes) | HostValue::Buffer(bytes) => Ok(bytes.into()),
+            HostValue::Floats(floats) => Ok(floats.into()),
+            HostValue::Date(date) => Ok(date.into()),
+            HostValue::Image {
+                width,
+                height,
+                rgba,
+            } => Ok(serde_json::json!({ "width": width, "height": height, "rgba": rgba })),
+            HostValue::Object(members) => members
+                .into_iter()
+                .map(|(key, value)| Ok((key, value.into_json()?)))
+                .collect::<Result<serde_json::Map<_, _>>>()
+                .map(serde_json::Value::Object),
+            HostValue::Handle(_) => Err(SdkError::Serialization(
+                "opaque host handle cannot be converted to JSON".into(),
+            )),
+        }
+    }
+
+    /// Take the binary contents of a `Bytes` or `Buffer` value.
+    pub fn into_bytes(self) -> Result<Vec<u8>> {
+        match self {
+            HostValue::Bytes(bytes) | HostValue::Buffer(bytes) => Ok(bytes),
+            other => other.deserialize(),
+        }
+    }
+
+    /// Take the contents of a `Floats` value.
+    pub fn into_floats(self) -> Result<Vec<f32>> {
+        match self {
+            HostValue::Floats(floats) => Ok(floats),
+            other => other.deserialize(),
+        }
+    }
+
+    /// Remove and return a member of an object value.
+    pub fn take(&mut self, key: &str) -> HostValue {
+        match self {
+            HostValue::Object(members) => members.remove(key).unwrap_or(HostValue::Undefined),
+            HostValue::Json(serde_json::Value::Object(members)) => members
+                .remove(key)
+                .map(HostValue::Json)
+                .unwrap_or(HostValue::Undefined),
+            _ => HostValue::Undefined,
+        }
+    }
+}
+
+impl From<serde_json::Value> for HostValue {
+    fn from(value: serde_json::Value) -> Self {
+        HostValue::Json(value)
+    }
+}
+
+impl From<&str> for HostValue {
+    fn from(value: &str) -> Self {
+        HostValue::Json(value.into())
+    }
+}
+
+/// A single request/response call addressed to a host.
+///
+/// `api` is the dotted path of the JS sub-API (`"proposal"`,
+/// `"terrain.groundTexture"`), or `"sdk"` for methods on the root object.
+/// `method` is the JS method name (`"addElement"`).
+#[derive(Debug, Clone)]
+pub struct HostCall {
+    pub api: &'static str,
+    pub method: &'static str,
+    pub args: Vec<HostValue>,
+}
+
+/// A subscription request addressed to a host.
+///
+/// `event` is the JS method used to subscribe (`"subscribe"`,
+/// `"onLocaleUpdate"`). `args` holds any extra arguments passed after the
+/// handler, such as subscribe options.
+pub struct HostSubscribe {
+    pub api: &'static str,
+    pub event: &'static str,
+    pub args: Vec<HostValue>,
+    pub handler: HostHandler,
+}
+
+/// A subscription request whose handler answers each event
+/// (`"onEmbeddedViewClosing"`).
+pub struct HostReplySubscribe {
+    pub api: &'static str,
+    pub event: &'static str,
+    pub args: Vec<HostValue>,
+    pub handler: HostReplyHandler,
+}
+
+/// Backend that the [`EmbeddedViewSdk`](crate::EmbeddedViewSdk) and every
+/// sub-API dispatch through.
+///
+/// [`JsHost`] forwards to the `forma-embedded-view-sdk` npm module and is
+/// what [`forma()`](crate::forma) and [`EmbeddedViewSdk::new`](crate::EmbeddedViewSdk::new)
+/// use. Other implementations can answer calls without a browser, e.g. for
+/// native unit tests.
+pub trait FormaHost {
+    /// Perform an asynchronous call.
+    fn call(&self, call: HostCall) -> HostFuture<HostValue>;
+
+    /// Perform a synchronous call (getters such as `getProjectId`).
+    fn call_sync(&self, call: HostCall) -> Result<HostValue>;
+
+    /// Register an event handler whose registration resolves asynchronously.
+    fn subscribe(&self, subscribe: HostSubscribe) -> HostFuture<Subscription>;
+
+    /// Register an event handler synchronously (`onMessagePort`).
+    fn subscribe_sync(&self, subscribe: HostSubscribe) -> Result<Subscription>;
+
+    /// Register a handler whose answer the host waits for
+    /// (`onEmbeddedViewClosing`). Unsupported unless implemented.
+    fn subscribe_with_reply(&self, subscribe: HostReplySubscribe) -> Result<Subscription> {
+        Err(unsupported(subscribe.api, subscribe.event))
+    }
+}
+
+impl<H: FormaHost + ?Sized> FormaHost for Rc<H> {
+    fn call(&self, call: HostCall) -> HostFuture<HostValue> {
+        (**self).call(call)
+    }
+
+    fn call_sync(&self, call: HostCall) -> Result<HostValue> {
+        (**self).call_sync(call)
+    }
+
+    fn subscribe(&self, subscribe: HostSubscribe) -> HostFuture<Subscription> {
+        (**self).subscribe(subscribe)
+    }
+
+    fn subscribe_sync(&self, subscribe: HostSubscribe) -> Result<Subscription> {
+        (**self).subscribe_sync(subscribe)
+    }
+
+    fn subscribe_with_reply(&self, subscribe: HostReplySubscribe) -> Result<Subscription> {
+        (**self).subscribe_with_reply(subscribe)
+    }
+}
+
+/// Error returned by hosts for calls they do not implement.
+pub fn unsupported(api: &str, method: &str) -> SdkError {
+    SdkError::Unsupported(ErrorDetails {
+        method: Some(format!("{api}.{method}")),
+        ..ErrorDetails::new("not implemented by this host")
+    })
+}
+
+thread_local! {
+    static INSTALLED: RefCell<Option<Rc<dyn FormaHost>>> = const { RefCell::new(None) };
+}
+
+/// Make [`forma()`](crate::forma) use `host` on the current thread until the
+/// returned guard is dropped.
+///
+/// This lets code that calls `forma()` directly run against a fake or
+/// recorded host in native tests.
+pub fn install(host: impl FormaHost + 'static) -> InstalledHost {
+    let previous = INSTALLED.with(|installed| installed.borrow_mut().replace(Rc::new(host)));
+    InstalledHost { previous }
+}
+
+pub(crate) fn installed() -> Option<Rc<dyn FormaHost>> {
+    INSTALLED.with(|installed| installed.borrow().clone())
+}
+
+/// Guard returned by [`install`]. Restores the previously installed host on drop.
+#[must_use = "the host is uninstalled when the guard is dropped"]
+pub struct InstalledHost {
+    previous: Option<Rc<dyn FormaHost>>,
+}
+
+impl Drop for InstalledHost {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        INSTALLED.with(|installed| *installed.borrow_mut() = previous);
+    }
+}
@@ -1,7 +1,10 @@
+use crate::client::{self, Client, LocalFuture};
+use crate::host::HostValue;
+use crate::options::call_options;
 use crate::types::*;
-use crate::Result;
-use wasm_bindgen::JsValue;
-use wasm_bindgen_futures::JsFuture;
+use crate::{ErrorDetails, Result, SdkError};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 
 pub(crate) mod js {
     use wasm_bindgen::prelude::*;
@@ -34,36 +37,63 @@ pub(crate) mod js {
         #[wasm_bindgen(method, js_name = "createUrn")]
         pub fn create_urn(this: &IntegrateApi, authcontext: &str) -> String;
     }
+
+    impl IntegrateApi {
+        pub(crate) fn dispatch(&self, method: &str, args: &[JsValue]) -> Option<::js_sys::Promise> {
+            Some(match method {
+                "createElementHierarchy" => self.create_element_hierarchy(&args[0]),
+                "createElementV2" => self.create_element_v2(&args[0]),
+                "updateElementV2" => self.update_element_v2(&args[0]),
+                "batchIngestElementsV2" => self.batch_ingest_elements_v2(&args[0]),
+                "uploadFile" => self.upload_file(&args[0]),
+                _ => return None,
+            })
+        }
+    }
 }
 
 /// Create and upload elements to the integrate element system.
 pub struct IntegrateApi {
-    inner: js::IntegrateApi,
+    client: Client,
 }
 
+call_options!(IntegrateApi);
+
 impl IntegrateApi {
-    pub(crate) fn from_raw(raw: js::IntegrateApi) -> Self {
-        Self { inner: raw }
+    pub(crate) fn new(client: Client) -> Self {
+        Self { client }
     }
 
-    /// Create a hierarchy of elements (deprecated in favor of `create_element_v2`).
+    /// Create a hierarchy of elements (deprecated in favor of
+    /// `create_element_v2`). Child ids are checked against the hierarchy
+    /// before sending.
     pub async fn create_element_hierarchy(
         &self,
         request: &CreateElementHierarchyRequest,
     ) -> Result<serde_json::Value> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.create_element_hierarchy(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        validate_hierarchy(&request.data)?;
+        let result = self
+            .client
+            .call("createElementHierarchy", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("createElementHierarchy", result)
     }
 
-    /// Create a new element (v2).
+    /// Create a new element (v2). The element is validated first (see
+    /// [`ElementV2::validate`]).
     pub async fn create_element_v2(
         &self,
         request: &CreateElementV2Request,
     ) -> Result<UrnResult> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.create_element_v2(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        if let Some(urn) = &request.urn {
+            validate_urn(urn).map_err(|message| invalid(format!("urn: {message}")))?;
+        }
+        request.element.validate()?;
+        let result = self
+            .client
+            .call("createElementV2", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("createElementV2", result)
     }
 
     /// Update an existing element. The update is merged onto the existing element.
@@ -71,34 +101,744 @@ impl IntegrateApi {
         &self,
         request: &UpdateElementV2Request,
     ) -> Result<serde_json::Value> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.update_element_v2(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        validate_urn(&request.urn).map_err(|message| invalid(format!("urn: {message}")))?;
+        request.element.validate()?;
+        let result = self
+            .client
+            .call("updateElementV2", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("updateElementV2", result)
     }
 
-    /// Create and/or update multiple elements in a batch.
+    /// Create and/or update multiple elements in a batch. URNs must be
+    /// unique and children referenced within the batch must come before
+    /// their parents.
     pub async fn batch_ingest_elements_v2(
         &self,
         request: &BatchIngestElementsV2Request,
     ) -> Result<serde_json::Value> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.batch_ingest_elements_v2(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        validate_batch(request)?;
+        let result = self
+            .client
+            .call("batchIngestElementsV2", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("batchIngestElementsV2", result)
     }
 
     /// Upload a file to integrate file storage.
     pub async fn upload_file(&self, request: &UploadFileRequest) -> Result<FileUploadResult> {
-        let obj = ::js_sys::Object::new();
-        let buf = ::js_sys::Uint8Array::new_with_length(request.data.len() as u32);
-        buf.copy_from(&request.data);
-        ::js_sys::Reflect::set(&obj, &JsValue::from_str("data"), &buf.buffer())?;
-        ::js_sys::Reflect::set(&obj, &JsValue::from_str("name"), &JsValue::from_str(&request.name))?;
-        let result = JsFuture::from(self.inner.upload_file(&obj)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let js_req = HostValue::Object(BTreeMap::from([
+            ("data".to_string(), HostValue::Buffer(request.data.clone())),
+            ("name".to_string(), HostValue::json(&request.name)?),
+        ]));
+        let result = self.client.call("uploadFile", vec![js_req]).await?;
+        self.client.response("uploadFile", result)
+    }
+
+    /// Create a new URN for an element. Fails with [`SdkError::Decode`] if
+    /// the host does not answer with a string, and with [`SdkError::Host`] if
+    /// the string is not an element URN.
+    pub fn create_urn(&self, authcontext: &str) -> Result<String> {
+        let result = self
+            .client
+            .call_sync("createUrn", vec![authcontext.into()])?;
+        let urn: String = self.client.decode("createUrn", "a string", result)?;
+        validate_urn(&urn).map_err(|message| {
+            let err = SdkError::Host(ErrorDetails::new(format!("createUrn returned {message}")));
+            self.client.in_method("createUrn", err)
+        })?;
+        Ok(urn)
+    }
+
+    /// Assign URNs to `root` and its descendants with [`create_urn`] (keeping
+    /// those already set) and flatten them into a batch, children first and
+    /// `root` last. Every element is validated on the way; errors name the
+    /// element by its path from `root`.
+    ///
+    /// [`create_urn`]: IntegrateApi::create_urn
+    pub fn prepare(
+        &self,
+        authcontext: &str,
+        root: &ElementNode,
+    ) -> Result<BatchIngestElementsV2Request> {
+        if authcontext.is_empty() {
+            return Err(invalid("authcontext is empty".into()));
+        }
+        let mut elements = Vec::new();
+        self.flatten(authcontext, root, "root", &mut elements)?;
+        let request = BatchIngestElementsV2Request {
+            authcontext: authcontext.to_string(),
+            elements,
+        };
+        validate_batch(&request)?;
+        Ok(request)
+    }
+
+    /// Ingest `root` and its descendants in one batch (see [`prepare`]) and
+    /// return the URN of `root`.
+    ///
+    /// ```no_run
+    /// # async fn run() -> forma_embedded_view_sdk::Result<()> {
+    /// use forma_embedded_view_sdk::forma;
+    /// use forma_embedded_view_sdk::types::{ElementNode, RepresentationRef, UploadFileRequest};
+    ///
+    /// let integrate = forma().integrate_elements();
+    /// let glb: Vec<u8> = Vec::new();
+    /// let blob = integrate
+    ///     .upload_file(&UploadFileRequest { data: glb, name: "tower.glb".into() })
+    ///     .await?;
+    /// let site = ElementNode::new().with_name("Import").with_child(
+    ///     "tower",
+    ///     ElementNode::new()
+    ///         .with_category("building")
+    ///         .with_volume_mesh(RepresentationRef::linked(blob.blob_id)),
+    /// );
+    /// let root = integrate.ingest("pro_123", &site).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`prepare`]: IntegrateApi::prepare
+    pub async fn ingest(&self, authcontext: &str, root: &ElementNode) -> Result<UrnResult> {
+        let request = self.prepare(authcontext, root)?;
+        let urn = request
+            .elements
+            .last()
+            .map(|element| element.urn.clone())
+            .expect("prepared batch contains the root");
+        self.batch_ingest_elements_v2(&request).await?;
+        Ok(UrnResult { urn })
+    }
+
+    /// Ingest a large batch in chunks of at most `options.max_chunk_bytes`
+    /// and `options.max_chunk_elements`, running up to `options.concurrency`
+    /// chunks at once. A chunk is only sent once the chunks holding the
+    /// children of its elements have succeeded; elements whose children
+    /// failed are skipped. `on_progress` is called after every chunk.
+    ///
+    /// The batch is validated as a whole first and nothing is sent if it is
+    /// invalid. Failed chunks do not stop the others: the returned report has
+    /// the outcome of every element, and [`IngestReport::remaining`] gives the
+    /// batch to send again to finish the job.
+    ///
+    /// ```no_run
+    /// # async fn run(site: forma_embedded_view_sdk::types::ElementNode) -> forma_embedded_view_sdk::Result<()> {
+    /// use forma_embedded_view_sdk::forma;
+    /// use forma_embedded_view_sdk::integrate::BulkIngestOptions;
+    ///
+    /// let integrate = forma().integrate_elements();
+    /// let mut batch = integrate.prepare("pro_123", &site)?;
+    /// let options = BulkIngestOptions::default();
+    /// for _ in 0..3 {
+    ///     let report = integrate
+    ///         .bulk_ingest(&batch, &options, |progress| {
+    ///             println!("{}/{}", progress.done(), progress.total);
+    ///         })
+    ///         .await?;
+    ///     if report.is_complete() {
+    ///         break;
+    ///     }
+    ///     batch = report.remaining(&batch);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn bulk_ingest(
+        &self,
+        request: &BatchIngestElementsV2Request,
+        options: &BulkIngestOptions,
+        mut on_progress: impl FnMut(&IngestProgress),
+    ) -> Result<IngestReport> {
+        validate_batch(request)?;
+        let chunks = chunk_batch(request, options);
+        let elements = &request.elements;
+        let index: BTreeMap<&str, usize> = elements
+            .iter()
+            .enumerate()
+            .map(|(i, element)| (element.urn.as_str(), i))
+            .collect();
+        // In-batch children of every element.
+        let children: Vec<Vec<usize>> = elements
+            .iter()
+            .map(|element| {
+                element
+                    .element
+                    .children
+                    .iter()
+                    .filter_map(|child| index.get(child.urn.as_str()).copied())
+                    .collect()
+            })
+            .collect();
+        let mut chunk_of = vec![0; elements.len()];
+        for (chunk, members) in chunks.iter().enumerate() {
+            for &member in members {
+                chunk_of[member] = chunk;
+            }
+        }
+        // In-batch parents of every element in another chunk, and for every
+        // chunk the number of such child links still without an outcome.
+        let mut parents = vec![Vec::new(); elements.len()];
+        let mut waiting = vec![0; chunks.len()];
+        for (parent, children) in children.iter().enumerate() {
+            for &child in children {
+                if chunk_of[child] != chunk_of[parent] {
+                    parents[child].push(parent);
+                    waiting[chunk_of[parent]] += 1;
+                }
+            }
+        }
+        let mut ready: VecDeque<usize> = (0..chunks.len())
+            .filter(|&chunk| waiting[chunk] == 0)
+            .collect();
+        let mut outcomes: Vec<Option<IngestOutcome>> = vec![None; elements.len()];
+        let mut progress = IngestProgress {
+            total: elements.len(),
+            chunks: chunks.len(),
+            ..IngestProgress::default()
+        };
+        let mut running: Vec<ChunkFuture<'_>> = Vec::new();
+        let concurrency = options.concurrency.max(1);
+        loop {
+            while running.len() < concurrency {
+                let Some(chunk) = ready.pop_front() else {
+                    break;
+                };
+                let mut members = Vec::with_capacity(chunks[chunk].len());
+                for &member in &chunks[chunk] {
+                    let blocked = children[member]
+                        .iter()
+                        .any(|&child| outcomes[child].as_ref().is_some_and(|o| !o.is_ingested()));
+                    if blocked {
+                        outcomes[member] = Some(IngestOutcome::Skipped);
+                        progress.skipped += 1;
+                    } else {
+                        members.push(member);
+                    }
+                }
+                if members.is_empty() {
+                    progress.chunks_done += 1;
+                    on_progress(&progress);
+                    release(
+                        &chunks[chunk],
+                        &parents,
+                        &chunk_of,
+                        &mut waiting,
+                        &mut ready,
+                    );
+                    continue;
+                }
+                let chunk_request = BatchIngestElementsV2Request {
+                    authcontext: request.authcontext.clone(),
+                    elements: members.iter().map(|&i| elements[i].clone()).collect(),
+                };
+                running.push(Box::pin(async move {
+                    let result = self.batch_ingest_elements_v2(&chunk_request).await;
+                    (chunk, members, result)
+                }));
+            }
+            if running.is_empty() {
+                break;
+            }
+            let (chunk, members, result) = client::first_ready(&mut running).await;
+            let outcome = match result {
+                Ok(_) => {
+                    progress.ingested += members.len();
+                    IngestOutcome::Ingested
+                }
+                Err(err) => {
+                    progress.failed += members.len();
+                    IngestOutcome::Failed(err)
+                }
+            };
+            for member in members {
+                outcomes[member] = Some(outcome.clone());
+            }
+            progress.chunks_done += 1;
+            on_progress(&progress);
+            release(
+                &chunks[chunk],
+                &parents,
+                &chunk_of,
+                &mut waiting,
+                &mut ready,
+            );
+        }
+        let mut report = IngestReport {
+            outcomes: BTreeMap::new(),
+        };
+        for (index, (element, outcome)) in elements.iter().zip(outcomes).enumerate() {
+            let outcome = outcome.ok_or_else(|| {
+                invalid(format!(
+                    "elements[{index}]: never sent, its children were not all scheduled"
+                ))
+            })?;
+            report.outcomes.insert(element.urn.clone(), outcome);
+        }
+        Ok(report)
+    }
+
+    /// Push the elements of `node` onto `elements`, children first, and
+    /// return the URN of `node`.
+    fn flatten(
+        &self,
+        authcontext: &str,
+        node: &ElementNode,
+        path: &str,
+        elements: &mut Vec<IngestElement>,
+    ) -> Result<String> {
+        let urn = match &node.urn {
+            Some(urn) => {
+                validate_urn(urn).map_err(|message| invalid(format!("{path}: urn: {message}")))?;
+                urn.clone()
+            }
+            None => self.create_urn(authcontext)?,
+        };
+        let mut children = Vec::with_capacity(node.children.len());
+        for child in &node.children {
+            children.push(ElementChild {
+                urn: self.flatten(
+                    authcontext,
+                    &child.node,
+                    &format!("{path}/{}", child.key),
+                    elements,
+                )?,
+                key: child.key.clone(),
+                transform: child.transform,
+            });
+        }
+        let element = ElementV2 {
+            properties: node.properties.clone(),
+            children,
+            representations: node.representations.clone(),
+        };
+        element.validate().map_err(|err| err.at(path))?;
+        elements.push(IngestElement { urn, element });
+        Ok(elements.last().expect("pushed").urn.clone())
+    }
+}
+
+/// Limits for [`IntegrateApi::bulk_ingest`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BulkIngestOptions {
+    /// Maximum size of the JSON body of a chunk, in bytes. An element larger
+    /// than this is sent alone.
+    pub max_chunk_bytes: usize,
+    /// Maximum number of elements in a chunk.
+    pub max_chunk_elements: usize,
+    /// Maximum number of chunks in flight.
+    pub concurrency: usize,
+}
+
+impl Default for BulkIngestOptions {
+    /// 4 MiB and 500 elements per chunk, 4 chunks at a time.
+    fn default() -> Self {
+        Self {
+            max_chunk_bytes: 4 * 1024 * 1024,
+            max_chunk_elements: 500,
+            concurrency: 4,
+        }
+    }
+}
+
+/// Progress of an [`IntegrateApi::bulk_ingest`] run, in elements and chunks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IngestProgress {
+    pub total: usize,
+    pub ingested: usize,
+    pub failed: usize,
+    /// Elements not sent because one of their children was not ingested.
+    pub skipped: usize,
+    pub chunks: usize,
+    pub chunks_done: usize,
+}
+
+impl IngestProgress {
+    /// Elements with an outcome so far.
+    pub fn done(&self) -> usize {
+        self.ingested + self.failed + self.skipped
+    }
+}
+
+/// What happened to one element of a bulk ingest.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IngestOutcome {
+    Ingested,
+    /// The chunk holding the element failed with this error.
+    Failed(SdkError),
+    /// Not sent because one of the element's children was not ingested.
+    Skipped,
+}
+
+impl IngestOutcome {
+    pub fn is_ingested(&self) -> bool {
+        matches!(self, IngestOutcome::Ingested)
+    }
+}
+
+/// Outcome of every element of an [`IntegrateApi::bulk_ingest`] run, by URN.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IngestReport {
+    pub outcomes: BTreeMap<String, IngestOutcome>,
+}
+
+impl IngestReport {
+    /// Whether every element was ingested.
+    pub fn is_complete(&self) -> bool {
+        self.outcomes.values().all(IngestOutcome::is_ingested)
+    }
+
+    /// URNs of the ingested elements.
+    pub fn ingested(&self) -> impl Iterator<Item = &str> + '_ {
+        self.outcomes
+            .iter()
+            .filter(|(_, outcome)| outcome.is_ingested())
+            .map(|(urn, _)| urn.as_str())
+    }
+
+    /// Failed elements with the error of their chunk.
+    pub fn failed(&self) -> impl Iterator<Item = (&str, &SdkError)> + '_ {
+        self.outcomes
+            .iter()
+            .filter_map(|(urn, outcome)| match outcome {
+                IngestOutcome::Failed(err) => Some((urn.as_str(), err)),
+                _ => None,
+            })
+    }
+
+    /// The elements of `request` that were not ingested, in their original
+    /// order, to pass to [`IntegrateApi::bulk_ingest`] again. Their children
+    /// that were ingested are referenced by URN and not sent again.
+    pub fn remaining(
+        &self,
+        request: &BatchIngestElementsV2Request,
+    ) -> BatchIngestElementsV2Request {
+        BatchIngestElementsV2Request {
+            authcontext: request.authcontext.clone(),
+            elements: request
+                .elements
+                .iter()
+                .filter(|element| {
+                    !self
+                        .outcomes
+                        .get(&element.urn)
+                        .is_some_and(IngestOutcome::is_ingested)
+                })
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+impl ElementNode {
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    /// Create a new URN for an element.
-    pub fn create_urn(&self, authcontext: &str) -> String {
-        self.inner.create_urn(authcontext)
+    /// Reuse an existing URN, updating that element instead of creating one.
+    pub fn with_urn(mut self, urn: impl Into<String>) -> Self {
+        self.urn = Some(urn.into());
+        self
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.properties.name = Some(name.into());
+        self
+    }
+
+    pub fn with_category(mut self, category: impl Into<String>) -> Self {
+        self.properties.category = Some(category.into());
+        self
+    }
+
+    /// Set a custom property.
+    pub fn with_property(
+        mut self,
+        key: impl Into<String>,
+        value: impl Into<serde_json::Value>,
+    ) -> Self {
+        self.properties.custom.insert(key.into(), value.into());
+        self
+    }
+
+    /// Set the GLB mesh, usually [`RepresentationRef::linked`] to an uploaded
+    /// file.
+    pub fn with_volume_mesh(mut self, representation: RepresentationRef) -> Self {
+        self.representations.volume_mesh = Some(representation);
+        self
+    }
+
+    /// Set the footprint, usually [`RepresentationRef::embedded_json`] with a
+    /// GeoJSON feature collection.
+    pub fn with_footprint(mut self, representation: RepresentationRef) -> Self {
+        self.representations.footprint = Some(representation);
+        self
+    }
+
+    /// Add a child at `key` with no transform.
+    pub fn with_child(self, key: impl Into<String>, node: ElementNode) -> Self {
+        self.push_child(key.into(), None, node)
+    }
+
+    /// Add a child at `key`, placed by `transform` relative to this element.
+    pub fn with_child_at(
+        self,
+        key: impl Into<String>,
+        transform: Transform,
+        node: ElementNode,
+    ) -> Self {
+        self.push_child(key.into(), Some(transform), node)
+    }
+
+    fn push_child(mut self, key: String, transform: Option<Transform>, node: ElementNode) -> Self {
+        self.children.push(ElementNodeChild {
+            key,
+            transform,
+            node,
+        });
+        self
+    }
+}
+
+impl RepresentationRef {
+    /// A representation stored in the blob `blob_id`, as returned by
+    /// `IntegrateApi::upload_file`.
+    pub fn linked(blob_id: impl Into<String>) -> Self {
+        Self {
+            kind: "linked".into(),
+            id: Some(blob_id.into()),
+            data: None,
+            extra: serde_json::Map::new(),
+        }
+    }
+
+    /// A representation carried inline as JSON.
+    pub fn embedded_json(data: serde_json::Value) -> Self {
+        Self {
+            kind: "embedded-json".into(),
+            id: None,
+            data: Some(data),
+            extra: serde_json::Map::new(),
+        }
+    }
+
+    /// Check that a linked representation names a blob and an embedded one
+    /// carries data.
+    fn validate(&self) -> std::result::Result<(), String> {
+        match self.kind.as_str() {
+            "linked" if self.id.as_deref().is_none_or(str::is_empty) => {
+                Err("linked representation has no blob id".into())
+            }
+            "embedded-json" if self.data.is_none() => {
+                Err("embedded representation has no data".into())
+            }
+            "" => Err("representation has no type".into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl ElementV2 {
+    /// Check that child keys are non-empty, contain no `/` and are unique,
+    /// child URNs are Forma element URNs, transforms are finite and
+    /// representations are complete.
+    pub fn validate(&self) -> Result<()> {
+        let mut keys = BTreeSet::new();
+        for (index, child) in self.children.iter().enumerate() {
+            let at = |message: String| invalid(format!("children[{index}]: {message}"));
+            if child.key.is_empty() || child.key.contains('/') {
+                return Err(at(format!("invalid key {:?}", child.key)));
+            }
+            if !keys.insert(child.key.as_str()) {
+                return Err(at(format!("duplicate key {:?}", child.key)));
+            }
+            validate_urn(&child.urn).map_err(at)?;
+            if let Some(transform) = &child.transform {
+                if transform.iter().any(|value| !value.is_finite()) {
+                    return Err(at("transform is not finite".into()));
+                }
+            }
+        }
+        let representations = &self.representations;
+        let typed = [
+            ("volumeMesh", &representations.volume_mesh),
+            ("footprint", &representations.footprint),
+            ("grossFloorArea", &representations.gross_floor_area),
+            ("graphBuilding", &representations.graph_building),
+        ];
+        for (name, representation) in typed {
+            if let Some(representation) = representation {
+                representation
+                    .validate()
+                    .map_err(|message| invalid(format!("representations.{name}: {message}")))?;
+            }
+        }
+        for (name, value) in &representations.other {
+            if let Ok(representation) = RepresentationRef::deserialize(value) {
+                representation
+                    .validate()
+                    .map_err(|message| invalid(format!("representations.{name}: {message}")))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn invalid(message: String) -> SdkError {
+    SdkError::InvalidRequest(ErrorDetails::new(message))
+}
+
+fn validate_urn(urn: &str) -> std::result::Result<(), String> {
+    match urn.strip_prefix("urn:adsk-forma-elements:") {
+        Some(rest) if rest.split(':').count() >= 3 && !rest.split(':').any(str::is_empty) => Ok(()),
+        _ => Err(format!("{urn:?} is not an element URN")),
+    }
+}
+
+/// A chunk being sent by [`IntegrateApi::bulk_ingest`]: its index, the
+/// elements sent and the result.
+type ChunkFuture<'a> = LocalFuture<'a, (usize, Vec<usize>, Result<serde_json::Value>)>;
+
+/// Count the elements of a finished chunk as done for their parents in other
+/// chunks, queueing the chunks left with nothing to wait for.
+fn release(
+    members: &[usize],
+    parents: &[Vec<usize>],
+    chunk_of: &[usize],
+    waiting: &mut [usize],
+    ready: &mut VecDeque<usize>,
+) {
+    for &member in members {
+        for &parent in &parents[member] {
+            let chunk = chunk_of[parent];
+            waiting[chunk] -= 1;
+            if waiting[chunk] == 0 {
+                ready.push_back(chunk);
+            }
+        }
+    }
+}
+
+/// Split a valid batch into chunks of element indices within the limits of
+/// `options`. Elements are ordered by height in the hierarchy (leaves first),
+/// so chunks of the same height do not depend on each other.
+fn chunk_batch(
+    request: &BatchIngestElementsV2Request,
+    options: &BulkIngestOptions,
+) -> Vec<Vec<usize>> {
+    let elements = &request.elements;
+    let index: BTreeMap<&str, usize> = elements
+        .iter()
+        .enumerate()
+        .map(|(i, element)| (element.urn.as_str(), i))
+        .collect();
+    // Children come first in a valid batch, so their heights are known.
+    let mut heights = vec![0; elements.len()];
+    for (i, element) in elements.iter().enumerate() {
+        heights[i] = element
+            .element
+            .children
+            .iter()
+            .filter_map(|child| index.get(child.urn.as_str()))
+            .map(|&child| heights[child] + 1)
+            .max()
+            .unwrap_or(0);
+    }
+    let mut order: Vec<usize> = (0..elements.len()).collect();
+    order.sort_by_key(|&i| heights[i]);
+    // `{"authcontext":"...","elements":[]}`
+    let overhead = request.authcontext.len() + 32;
+    let max_elements = options.max_chunk_elements.max(1);
+    let mut chunks: Vec<Vec<usize>> = Vec::new();
+    let mut bytes = overhead;
+    for i in order {
+        // Elements serialize infallibly: they were decoded from or built as JSON.
+        let size = serde_json::to_vec(&elements[i]).map_or(0, |json| json.len()) + 1;
+        match chunks.last_mut() {
+            Some(chunk)
+                if chunk.len() < max_elements && bytes + size <= options.max_chunk_bytes =>
+            {
+                chunk.push(i);
+                bytes += size;
+            }
+            _ => {
+                chunks.push(vec![i]);
+                bytes = overhead + size;
+            }
+        }
+    }
+    chunks
+}
+
+/// Check that URNs are valid and unique and that children ingested in the
+/// same batch come before their parents.
+fn validate_batch(request: &BatchIngestElementsV2Request) -> Result<()> {
+    if request.authcontext.is_empty() {
+        return Err(invalid("authcontext is empty".into()));
+    }
+    let mut ingested = BTreeSet::new();
+    let batch: BTreeSet<_> = request.elements.iter().map(|e| e.urn.as_str()).collect();
+    for (index, element) in request.elements.iter().enumerate() {
+        let at = |message: String| invalid(format!("elements[{index}]: {message}"));
+        validate_urn(&element.urn).map_err(at)?;
+        if !ingested.insert(element.urn.as_str()) {
+            return Err(at(format!("duplicate urn {:?}", element.urn)));
+        }
+        element
+            .element
+            .validate()
+            .map_err(|err| err.at(&format!("elements[{index}]")))?;
+        if let Some(child) = element.element.children.iter().find(|child| {
+            batch.contains(child.urn.as_str()) && !ingested.contains(child.urn.as_str())
+        }) {
+            return Err(at(format!(
+                "child {:?} is ingested after its parent",
+                child.key
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Check that the root and every child id refer to an element of the
+/// hierarchy, ids match their keys and the hierarchy has no cycles.
+fn validate_hierarchy(hierarchy: &ElementHierarchy) -> Result<()> {
+    let elements = &hierarchy.elements;
+    if !elements.contains_key(&hierarchy.root_element) {
+        return Err(invalid(format!(
+            "root element {:?} is not in the hierarchy",
+            hierarchy.root_element
+        )));
+    }
+    for (id, element) in elements {
+        let at = |message: String| invalid(format!("elements.{id}: {message}"));
+        if &element.id != id {
+            return Err(at(format!("id {:?} does not match its key", element.id)));
+        }
+        if let Some(child) = element
+            .children
+            .iter()
+            .find(|child| !elements.contains_key(&child.id))
+        {
+            return Err(at(format!("child {:?} is not in the hierarchy", child.id)));
+        }
+    }
+    // Depth-first walk from the root; meeting an element already on the
+    // current branch means a cycle.
+    let mut branch = vec![(hierarchy.root_element.as_str(), 0)];
+    while let Some(&(id, next)) = branch.last() {
+        let Some(child) = elements[id].children.get(next) else {
+            branch.pop();
+            continue;
+        };
+        let depth = branch.len() - 1;
+        branch[depth].1 += 1;
+        if branch.iter().any(|(ancestor, _)| *ancestor == child.id) {
+            return Err(invalid(format!(
+                "elements.{}: child {:?} is also its ancestor",
+                id, child.id
+            )));
+        }
+        branch.push((child.id.as_str(), 0));
     }
+    Ok(())
 }
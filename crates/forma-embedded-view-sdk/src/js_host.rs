@@ -0,0 +1,302 @@
+use crate::host::{
+    unsupported, FormaHost, HostCall, HostFuture, HostReplySubscribe, HostSubscribe, HostValue,
+};
+use crate::subscription::Subscription;
+use crate::{js, ErrorDetails, Result, SdkError};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+
+/// [`FormaHost`] backed by the `forma-embedded-view-sdk` npm module.
+pub struct JsHost {
+    sdk: js::EmbeddedViewSdk,
+}
+
+impl JsHost {
+    pub(crate) fn from_raw(raw: js::EmbeddedViewSdk) -> Self {
+        Self { sdk: raw }
+    }
+
+    /// Construct a new JS `EmbeddedViewSdk` with an optional config object.
+    pub fn new(config: Option<&JsValue>) -> Self {
+        Self::from_raw(js::EmbeddedViewSdk::new(config))
+    }
+
+    fn dispatch(&self, api: &str, method: &str, args: &[JsValue]) -> Option<::js_sys::Promise> {
+        let sdk = &self.sdk;
+        match api {
+            "sdk" => sdk.dispatch(method, args),
+            "analysis" => sdk.analysis().dispatch(method, args),
+            "areaMetrics" => sdk.area_metrics().dispatch(method, args),
+            "auth" => sdk.auth().dispatch(method, args),
+            "camera" => sdk.camera().dispatch(method, args),
+            "colorbar" => sdk.colorbar().dispatch(method, args),
+            "designTool" => sdk.design_tool().dispatch(method, args),
+            "elements" => sdk.elements().dispatch(method, args),
+            "elements.floorStack" => sdk.elements().floor_stack().dispatch(method, args),
+            "elements.representations" => sdk.elements().representations().dispatch(method, args),
+            "elements.blobs" => sdk.elements().blobs().dispatch(method, args),
+            "extensions" => sdk.extensions().dispatch(method, args),
+            "extensions.storage" => sdk.extensions().storage().dispatch(method, args),
+            "generators" => sdk.generators().dispatch(method, args),
+            "geoData" => sdk.geo_data().dispatch(method, args),
+            "geometry" => sdk.geometry_api().dispatch(method, args),
+            "integrateElements" => sdk.integrate_elements().dispatch(method, args),
+            "library" => sdk.library().dispatch(method, args),
+            "predictiveAnalysis" => sdk.predictive_analysis().dispatch(method, args),
+            "project" => sdk.project().dispatch(method, args),
+            "proposal" => sdk.proposal().dispatch(method, args),
+            "render" => sdk.render().dispatch(method, args),
+            "render.glb" => sdk.render().glb().dispatch(method, args),
+            "render.geojson" => sdk.render().geojson().dispatch(method, args),
+            "render.elementColors" => sdk.render().element_colors().dispatch(method, args),
+            "selection" => sdk.selection().dispatch(method, args),
+            "sun" => sdk.sun().dispatch(method, args),
+            "terrain" => sdk.terrain().dispatch(method, args),
+            "terrain.groundTexture" => sdk.terrain().ground_texture().dispatch(method, args),
+            _ => None,
+        }
+    }
+
+    fn listen(
+        &self,
+        api: &str,
+        event: &str,
+        handler: &Closure<dyn FnMut(JsValue)>,
+        args: &[JsValue],
+    ) -> Option<::js_sys::Promise> {
+        let sdk = &self.sdk;
+        match api {
+            "sdk" => sdk.listen(event, handler),
+            "camera" => sdk.camera().listen(event, handler),
+            "designTool" => sdk.design_tool().listen(event, handler),
+            "proposal" => sdk.proposal().listen(event, handler, args),
+            "selection" => sdk.selection().listen(event, handler),
+            _ => None,
+        }
+    }
+
+    fn call_sync_raw(&self, call: &HostCall, args: &[JsValue]) -> Option<JsValue> {
+        let sdk = &self.sdk;
+        match (call.api, call.method) {
+            ("sdk", "origin") => Some(sdk.origin().into()),
+            ("sdk", "getProjectId") => Some(sdk.get_project_id().into()),
+            ("sdk", "getExtensionId") => Some(sdk.get_extension_id().into()),
+            ("sdk", "getRegion") => Some(sdk.get_region().into()),
+            ("sdk", "getEmbeddedViewId") => Some(sdk.get_embedded_view_id().into()),
+            ("auth", "configure") => {
+                sdk.auth().configure(&args[0]);
+                Some(JsValue::UNDEFINED)
+            }
+            ("integrateElements", "createUrn") => {
+                let authcontext = args[0].as_string().unwrap_or_default();
+                Some(sdk.integrate_elements().create_urn(&authcontext).into())
+            }
+            _ => None,
+        }
+    }
+}
+
+impl FormaHost for JsHost {
+    fn call(&self, call: HostCall) -> HostFuture<HostValue> {
+        let promise = js_args(&call.args).and_then(|args| {
+            self.dispatch(call.api, call.method, &args)
+                .ok_or_else(|| unsupported(call.api, call.method))
+        });
+        Box::pin(async move { Ok(from_js(JsFuture::from(promise?).await?)) })
+    }
+
+    fn call_sync(&self, call: HostCall) -> Result<HostValue> {
+        let args = js_args(&call.args)?;
+        self.call_sync_raw(&call, &args)
+            .map(from_js)
+            .ok_or_else(|| unsupported(call.api, call.method))
+    }
+
+    fn subscribe(&self, subscribe: HostSubscribe) -> HostFuture<Subscription> {
+        let HostSubscribe {
+            api,
+            event,
+            args,
+            handler,
+        } = subscribe;
+        let closure = js_handler(handler);
+        let promise = js_args(&args).and_then(|args| {
+            self.listen(api, event, &closure, &args)
+                .ok_or_else(|| unsupported(api, event))
+        });
+        Box::pin(async move {
+            let result = JsFuture::from(promise?).await?;
+            let unsubscribe_fn: ::js_sys::Function =
+                ::js_sys::Reflect::get(&result, &"unsubscribe".into())?.into();
+            Ok(js_subscription(closure, unsubscribe_fn))
+        })
+    }
+
+    fn subscribe_sync(&self, subscribe: HostSubscribe) -> Result<Subscription> {
+        match (subscribe.api, subscribe.event) {
+            ("sdk", "onMessagePort") => {
+                let closure = js_handler(subscribe.handler);
+                let unsubscribe_fn = self.sdk.on_message_port(&closure);
+                Ok(js_subscription(closure, unsubscribe_fn))
+            }
+            (api, event) => Err(unsupported(api, event)),
+        }
+    }
+
+    fn subscribe_with_reply(&self, subscribe: HostReplySubscribe) -> Result<Subscription> {
+        match (subscribe.api, subscribe.event) {
+            ("sdk", "onEmbeddedViewClosing") => {
+                let mut handler = subscribe.handler;
+                let closure = Closure::wrap(Box::new(move |val: JsValue| {
+                    let reply = handler(from_js(val));
+                    wasm_bindgen_futures::future_to_promise(async move {
+                        let value = reply.await.map_err(|err| JsValue::from(err.to_string()))?;
+                        to_js(&value).map_err(|err| JsValue::from(err.to_string()))
+                    })
+                })
+                    as Box<dyn FnMut(JsValue) -> ::js_sys::Promise>);
+                let unsubscribe_fn = self.sdk.on_embedded_view_closing(&closure).unsubscribe();
+                Ok(js_subscription(closure, unsubscribe_fn))
+            }
+            (api, event) => Err(unsupported(api, event)),
+        }
+    }
+}
+
+fn js_handler(mut handler: crate::host::HostHandler) -> Closure<dyn FnMut(JsValue)> {
+    Closure::wrap(Box::new(move |val: JsValue| handler(from_js(val))) as Box<dyn FnMut(JsValue)>)
+}
+
+fn js_subscription<T: ?Sized + 'static>(
+    closure: Closure<T>,
+    unsubscribe_fn: ::js_sys::Function,
+) -> Subscription {
+    Subscription::new(move || {
+        let _ = unsubscribe_fn.call0(&JsValue::NULL);
+        drop(closure);
+    })
+}
+
+/// Convert call arguments, padding with `undefined` so that dispatchers can
+/// index the arguments they expect without bounds checks.
+fn js_args(args: &[HostValue]) -> Result<Vec<JsValue>> {
+    let mut js_args = args.iter().map(to_js).collect::<Result<Vec<_>>>()?;
+    while js_args.len() < 2 {
+        js_args.push(JsValue::UNDEFINED);
+    }
+    Ok(js_args)
+}
+
+pub(crate) fn to_js(value: &HostValue) -> Result<JsValue> {
+    Ok(match value {
+        HostValue::Undefined => JsValue::UNDEFINED,
+        HostValue::Json(json) => {
+            json.serialize(&serde_wasm_bindgen::Serializer::json_compatible())?
+        }
+        HostValue::Bytes(bytes) => ::js_sys::Uint8Array::from(bytes.as_slice()).into(),
+        HostValue::Buffer(bytes) => ::js_sys::Uint8Array::from(bytes.as_slice()).buffer().into(),
+        HostValue::Floats(floats) => ::js_sys::Float32Array::from(floats.as_slice()).into(),
+        HostValue::Date(date) => ::js_sys::Date::new(&JsValue::from_str(date)).into(),
+        HostValue::Image {
+            width,
+            height,
+            rgba,
+        } => canvas(*width, *height, rgba)?,
+        HostValue::Object(members) => {
+            let obj = ::js_sys::Object::new();
+            for (key, member) in members {
+                ::js_sys::Reflect::set(&obj, &JsValue::from_str(key), &to_js(member)?)?;
+            }
+            obj.into()
+        }
+        HostValue::Handle(handle) => handle.clone(),
+    })
+}
+
+/// Draw RGBA pixels onto a new canvas.
+fn canvas(width: u32, height: u32, rgba: &[u8]) -> Result<JsValue> {
+    let unavailable = |what: &str| SdkError::HostUnavailable(ErrorDetails::new(what));
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or_else(|| unavailable("no document to create a canvas in"))?;
+    let canvas: web_sys::HtmlCanvasElement = document.create_element("canvas")?.unchecked_into();
+    canvas.set_width(width);
+    canvas.set_height(height);
+    let context: web_sys::CanvasRenderingContext2d = canvas
+        .get_context("2d")?
+        .ok_or_else(|| unavailable("canvas has no 2d context"))?
+        .unchecked_into();
+    let image = web_sys::ImageData::new_with_u8_clamped_array_and_sh(
+        wasm_bindgen::Clamped(rgba),
+        width,
+        height,
+    )?;
+    context.put_image_data(&image, 0.0, 0.0)?;
+    Ok(canvas.into())
+}
+
+pub(crate) fn from_js(value: JsValue) -> HostValue {
+    if value.is_undefined() {
+        return HostValue::Undefined;
+    }
+    if let Some(buffer) = value.dyn_ref::<::js_sys::ArrayBuffer>() {
+        return HostValue::Buffer(::js_sys::Uint8Array::new(buffer).to_vec());
+    }
+    if let Some(bytes) = value.dyn_ref::<::js_sys::Uint8Array>() {
+        return HostValue::Bytes(bytes.to_vec());
+    }
+    if let Some(floats) = value.dyn_ref::<::js_sys::Float32Array>() {
+        return HostValue::Floats(floats.to_vec());
+    }
+    if let Some(date) = value.dyn_ref::<::js_sys::Date>() {
+        return HostValue::Date(date.to_iso_string().into());
+    }
+    if is_plain_object(&value) {
+        let mut members = BTreeMap::new();
+        let mut all_json = true;
+        for entry in ::js_sys::Object::entries(value.unchecked_ref()).iter() {
+            let entry: ::js_sys::Array = entry.unchecked_into();
+            let Some(key) = entry.get(0).as_string() else {
+                continue;
+            };
+            let member = from_js(entry.get(1));
+            all_json &= matches!(member, HostValue::Json(_) | HostValue::Undefined);
+            members.insert(key, member);
+        }
+        if !all_json {
+            return HostValue::Object(members);
+        }
+        return HostValue::Json(serde_json::Value::Object(
+            members
+                .into_iter()
+                .filter_map(|(key, member)| match member {
+                    HostValue::Json(json) => Some((key, json)),
+                    _ => None,
+                })
+                .collect(),
+        ));
+    }
+    if value.is_object() && !::js_sys::Array::is_array(&value) {
+        return HostValue::Handle(value);
+    }
+    match serde_wasm_bindgen::from_value(value.clone()) {
+        Ok(json) => HostValue::Json(json),
+        Err(_) => HostValue::Handle(value),
+    }
+}
+
+fn is_plain_object(value: &JsValue) -> bool {
+    if !value.is_object() || ::js_sys::Array::is_array(value) {
+        return false;
+    }
+    let proto = ::js_sys::Object::get_prototype_of(value);
+    proto.is_null() || ::js_sys::Object::get_prototype_of(&proto).is_null()
+}
+
+/// Map an `undefined` argument to `None` for externs taking optional arguments.
+pub(crate) fn optional(value: &JsValue) -> Option<&JsValue> {
+    (!value.is_undefined()).then_some(value)
+}
@@ -4,36 +4,54 @@ pub mod analysis;
 pub mod area_metrics;
 pub mod auth;
 pub mod camera;
+pub mod clock;
 pub mod colorbar;
 pub mod design_tool;
+pub mod dirty;
 pub mod elements;
 pub mod error;
 pub mod extensions;
 pub mod generators;
 pub mod geo_data;
 pub mod geometry;
+pub mod host;
 pub mod integrate;
 pub mod library;
+pub mod options;
+pub mod polygon;
 pub mod predictive_analysis;
 pub mod project;
 pub mod proposal;
+pub mod recording;
 pub mod render;
 pub mod selection;
+pub mod stream;
 pub mod sun;
 pub mod terrain;
+pub mod testing;
+pub mod trace;
 pub mod types;
 
+mod client;
+mod js_host;
 mod subscription;
-pub use subscription::{Subscription, VoidSubscription};
+pub use subscription::{EventStream, Subscription, VoidSubscription};
 
-pub use error::SdkError;
+pub use error::{DecodeError, ErrorDetails, SdkError};
+pub use host::{FormaHost, JsHost};
+pub use options::{CallOptions, CancellationToken, Decoding, RetryPolicy};
 pub use wasm_bindgen_futures::spawn_local;
 pub type Result<T> = std::result::Result<T, SdkError>;
 
+use client::Client;
+use dirty::DirtyState;
+use host::HostValue;
+use serde::Serialize;
+use std::future::Future;
+use std::rc::Rc;
 use types::*;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
-use wasm_bindgen_futures::JsFuture;
 
 pub(crate) mod js {
     use wasm_bindgen::prelude::*;
@@ -184,6 +202,34 @@ pub(crate) mod js {
         pub fn get_host_origin() -> String;
     }
 
+    impl EmbeddedViewSdk {
+        pub(crate) fn dispatch(&self, method: &str, args: &[JsValue]) -> Option<::js_sys::Promise> {
+            Some(match method {
+                "ping" => self.ping(),
+                "getPresentationUnitSystem" => self.get_presentation_unit_system(),
+                "getCanEdit" => self.get_can_edit(),
+                "getCanViewHub" => self.get_can_view_hub(),
+                "getCanEditHub" => self.get_can_edit_hub(),
+                "openFloatingPanel" => self.open_floating_panel(&args[0]),
+                "closeEmbeddedView" => self.close_embedded_view(&args[0]),
+                "createMessagePort" => self.create_message_port(&args[0]),
+                _ => return None,
+            })
+        }
+
+        pub(crate) fn listen(
+            &self,
+            event: &str,
+            handler: &Closure<dyn FnMut(JsValue)>,
+        ) -> Option<::js_sys::Promise> {
+            Some(match event {
+                "onEmbeddedViewStateChange" => self.on_embedded_view_state_change(handler),
+                "onLocaleUpdate" => self.on_locale_update(handler),
+                _ => return None,
+            })
+        }
+    }
+
     #[wasm_bindgen(module = "forma-embedded-view-sdk/auto")]
     extern "C" {
         #[wasm_bindgen(thread_local_v2, js_name = "Forma")]
@@ -192,42 +238,61 @@ pub(crate) mod js {
 }
 
 /// The main Forma Embedded View SDK entry point.
+///
+/// All calls are dispatched through a [`FormaHost`]. [`EmbeddedViewSdk::new`]
+/// and [`forma()`] use the JS SDK ([`JsHost`]); [`EmbeddedViewSdk::with_host`]
+/// accepts any other backend.
 pub struct EmbeddedViewSdk {
-    inner: js::EmbeddedViewSdk,
+    client: Client,
 }
 
+options::call_options!(EmbeddedViewSdk);
+
 impl EmbeddedViewSdk {
     pub fn new(config: Option<&SdkConfig>) -> Result<Self> {
         let js_config = config
-            .map(serde_wasm_bindgen::to_value)
+            .map(|config| config.serialize(&serde_wasm_bindgen::Serializer::json_compatible()))
             .transpose()?;
-        Ok(Self {
-            inner: js::EmbeddedViewSdk::new(js_config.as_ref()),
-        })
+        Ok(Self::with_host(JsHost::new(js_config.as_ref())))
+    }
+
+    /// Create an SDK handle that dispatches every call through `host`.
+    pub fn with_host(host: impl FormaHost + 'static) -> Self {
+        Self {
+            client: Client::new(Rc::new(host)),
+        }
+    }
+
+    /// The host this SDK handle dispatches through.
+    pub fn host(&self) -> Rc<dyn FormaHost> {
+        self.client.host().clone()
     }
 
-    pub(crate) fn from_raw(raw: js::EmbeddedViewSdk) -> Self {
-        Self { inner: raw }
+    pub fn origin(&self) -> Result<String> {
+        self.get_string("origin")
     }
 
-    pub fn origin(&self) -> String {
-        self.inner.origin()
+    pub fn get_project_id(&self) -> Result<String> {
+        self.get_string("getProjectId")
     }
 
-    pub fn get_project_id(&self) -> String {
-        self.inner.get_project_id()
+    pub fn get_extension_id(&self) -> Result<String> {
+        self.get_string("getExtensionId")
     }
 
-    pub fn get_extension_id(&self) -> String {
-        self.inner.get_extension_id()
+    pub fn get_region(&self) -> Result<String> {
+        self.get_string("getRegion")
     }
 
-    pub fn get_region(&self) -> String {
-        self.inner.get_region()
+    pub fn get_embedded_view_id(&self) -> Result<String> {
+        self.get_string("getEmbeddedViewId")
     }
 
-    pub fn get_embedded_view_id(&self) -> String {
-        self.inner.get_embedded_view_id()
+    /// Call a synchronous string getter. With [`Decoding::Lenient`], a value
+    /// of another shape reads as `""`.
+    fn get_string(&self, method: &'static str) -> Result<String> {
+        let result = self.client.call_sync(method, vec![])?;
+        self.client.decode(method, "a string", result)
     }
 
     pub fn get_host_origin() -> String {
@@ -235,180 +300,240 @@ impl EmbeddedViewSdk {
     }
 
     pub async fn ping(&self) -> Result<()> {
-        JsFuture::from(self.inner.ping()).await?;
+        self.client.call("ping", vec![]).await?;
         Ok(())
     }
 
     pub async fn get_presentation_unit_system(&self) -> Result<UnitSystem> {
-        let result = JsFuture::from(self.inner.get_presentation_unit_system()).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self.client.call("getPresentationUnitSystem", vec![]).await?;
+        self.client.response("getPresentationUnitSystem", result)
     }
 
     pub async fn get_can_edit(&self) -> Result<bool> {
-        let result = JsFuture::from(self.inner.get_can_edit()).await?;
-        Ok(result.as_bool().unwrap_or(false))
+        let result = self.client.call("getCanEdit", vec![]).await?;
+        self.client.decode("getCanEdit", "a boolean", result)
     }
 
     pub async fn get_can_view_hub(&self) -> Result<bool> {
-        let result = JsFuture::from(self.inner.get_can_view_hub()).await?;
-        Ok(result.as_bool().unwrap_or(false))
+        let result = self.client.call("getCanViewHub", vec![]).await?;
+        self.client.decode("getCanViewHub", "a boolean", result)
     }
 
     pub async fn get_can_edit_hub(&self) -> Result<bool> {
-        let result = JsFuture::from(self.inner.get_can_edit_hub()).await?;
-        Ok(result.as_bool().unwrap_or(false))
+        let result = self.client.call("getCanEditHub", vec![]).await?;
+        self.client.decode("getCanEditHub", "a boolean", result)
     }
 
     pub async fn open_floating_panel(&self, options: &FloatingPanelOptions) -> Result<()> {
-        let js_options = serde_wasm_bindgen::to_value(options)?;
-        JsFuture::from(self.inner.open_floating_panel(&js_options)).await?;
+        self.client
+            .call("openFloatingPanel", vec![HostValue::json(options)?])
+            .await?;
         Ok(())
     }
 
     pub async fn close_embedded_view(&self, options: &CloseEmbeddedViewOptions) -> Result<()> {
-        let js_options = serde_wasm_bindgen::to_value(options)?;
-        JsFuture::from(self.inner.close_embedded_view(&js_options)).await?;
+        self.client
+            .call("closeEmbeddedView", vec![HostValue::json(options)?])
+            .await?;
         Ok(())
     }
 
     pub async fn on_embedded_view_state_change(
         &self,
-        mut callback: impl FnMut(EmbeddedViewState) + 'static,
+        callback: impl FnMut(EmbeddedViewState) + 'static,
     ) -> Result<Subscription> {
-        let closure = Closure::wrap(Box::new(move |val: JsValue| {
-            if let Ok(state) = serde_wasm_bindgen::from_value(val) {
-                callback(state);
-            }
-        }) as Box<dyn FnMut(JsValue)>);
-        let result = JsFuture::from(self.inner.on_embedded_view_state_change(&closure)).await?;
-        let unsubscribe_fn: ::js_sys::Function = ::js_sys::Reflect::get(&result, &"unsubscribe".into())?.into();
-        Ok(Subscription::new(closure, unsubscribe_fn))
+        self.client
+            .subscribe_to("onEmbeddedViewStateChange", vec![], callback)
+            .await
     }
 
     pub async fn on_locale_update(
         &self,
-        mut callback: impl FnMut(LocaleUpdate) + 'static,
+        callback: impl FnMut(LocaleUpdate) + 'static,
     ) -> Result<Subscription> {
-        let closure = Closure::wrap(Box::new(move |val: JsValue| {
-            if let Ok(update) = serde_wasm_bindgen::from_value(val) {
-                callback(update);
+        self.client
+            .subscribe_to("onLocaleUpdate", vec![], callback)
+            .await
+    }
+
+    /// Embedded view state changes as a stream. Unsubscribes when dropped.
+    pub async fn embedded_view_states(&self) -> Result<EventStream<EmbeddedViewState>> {
+        let (sender, stream) = EventStream::channel();
+        let subscription = self
+            .on_embedded_view_state_change(move |state| sender.send(state))
+            .await?;
+        Ok(stream.attach(subscription))
+    }
+
+    /// Locale updates as a stream. Unsubscribes when dropped.
+    pub async fn locale_updates(&self) -> Result<EventStream<LocaleUpdate>> {
+        let (sender, stream) = EventStream::channel();
+        let subscription = self.on_locale_update(move |update| sender.send(update)).await?;
+        Ok(stream.attach(subscription))
+    }
+
+    /// Run `handler` when the user closes the embedded view. The view closes
+    /// once the returned future resolves to `true`; `false` keeps it open.
+    ///
+    /// Use this to flush unsaved state before the panel goes away. See
+    /// [`flush_on_close`](Self::flush_on_close) for the common case.
+    pub fn on_embedded_view_closing<F, Fut>(&self, mut handler: F) -> Result<Subscription>
+    where
+        F: FnMut() -> Fut + 'static,
+        Fut: Future<Output = bool> + 'static,
+    {
+        self.client
+            .subscribe_with_reply("onEmbeddedViewClosing", vec![], move |_| {
+                let allow = handler();
+                Box::pin(async move { Ok(HostValue::Json(allow.await.into())) })
+            })
+    }
+
+    /// Close immediately while `dirty` is clean. Otherwise call `flush` with
+    /// the dirty keys and close only if it resolves to `true`.
+    pub fn flush_on_close<F, Fut>(&self, dirty: DirtyState, mut flush: F) -> Result<Subscription>
+    where
+        F: FnMut(Vec<String>) -> Fut + 'static,
+        Fut: Future<Output = bool> + 'static,
+    {
+        self.on_embedded_view_closing(move || {
+            let keys = dirty.keys();
+            let flushed = (!keys.is_empty()).then(|| flush(keys));
+            async move {
+                match flushed {
+                    Some(flushed) => flushed.await,
+                    None => true,
+                }
             }
-        }) as Box<dyn FnMut(JsValue)>);
-        let result = JsFuture::from(self.inner.on_locale_update(&closure)).await?;
-        let unsubscribe_fn: ::js_sys::Function = ::js_sys::Reflect::get(&result, &"unsubscribe".into())?.into();
-        Ok(Subscription::new(closure, unsubscribe_fn))
+        })
     }
 
     pub async fn create_message_port(
         &self,
         options: &CreateMessagePortOptions,
     ) -> Result<MessagePortHandle> {
-        let js_options = serde_wasm_bindgen::to_value(options)?;
-        let result = JsFuture::from(self.inner.create_message_port(&js_options)).await?;
-        Ok(MessagePortHandle { inner: result })
+        let result = self
+            .client
+            .call("createMessagePort", vec![HostValue::json(options)?])
+            .await?;
+        match result {
+            HostValue::Handle(inner) => Ok(MessagePortHandle { inner }),
+            other => Err(self
+                .client
+                .unexpected("createMessagePort", "a MessagePort", other)),
+        }
     }
 
     pub fn on_message_port(
         &self,
-        mut callback: impl FnMut(serde_json::Value) + 'static,
-    ) -> Subscription {
-        let closure = Closure::wrap(Box::new(move |val: JsValue| {
-            if let Ok(v) = serde_wasm_bindgen::from_value(val) {
-                callback(v);
-            }
-        }) as Box<dyn FnMut(JsValue)>);
-        let unsubscribe_fn = self.inner.on_message_port(&closure);
-        Subscription::new(closure, unsubscribe_fn)
+        callback: impl FnMut(serde_json::Value) + 'static,
+    ) -> Result<Subscription> {
+        self.client.subscribe_sync_to("onMessagePort", vec![], callback)
+    }
+
+    /// Message ports as a stream. Unsubscribes when dropped.
+    pub fn message_ports(&self) -> Result<EventStream<serde_json::Value>> {
+        let (sender, stream) = EventStream::channel();
+        let subscription = self.on_message_port(move |port| sender.send(port))?;
+        Ok(stream.attach(subscription))
     }
 
     // ---- Sub-API accessors ----
 
     pub fn analysis(&self) -> analysis::AnalysisApi {
-        analysis::AnalysisApi::from_raw(self.inner.analysis())
+        analysis::AnalysisApi::new(self.client.scoped("analysis"))
     }
 
     pub fn area_metrics(&self) -> area_metrics::AreaMetricsApi {
-        area_metrics::AreaMetricsApi::from_raw(self.inner.area_metrics())
+        area_metrics::AreaMetricsApi::new(self.client.scoped("areaMetrics"))
     }
 
     pub fn auth(&self) -> auth::AuthApi {
-        auth::AuthApi::from_raw(self.inner.auth())
+        auth::AuthApi::new(self.client.scoped("auth"))
     }
 
     pub fn camera(&self) -> camera::CameraApi {
-        camera::CameraApi::from_raw(self.inner.camera())
+        camera::CameraApi::new(self.client.scoped("camera"))
     }
 
     pub fn colorbar(&self) -> colorbar::ColorbarApi {
-        colorbar::ColorbarApi::from_raw(self.inner.colorbar())
+        colorbar::ColorbarApi::new(self.client.scoped("colorbar"))
     }
 
     pub fn design_tool(&self) -> design_tool::DesignToolApi {
-        design_tool::DesignToolApi::from_raw(self.inner.design_tool())
+        design_tool::DesignToolApi::new(self.client.scoped("designTool"))
     }
 
     pub fn elements(&self) -> elements::ElementsApi {
-        elements::ElementsApi::from_raw(self.inner.elements())
+        elements::ElementsApi::new(self.client.scoped("elements"))
     }
 
     pub fn extensions(&self) -> extensions::ExtensionsApi {
-        extensions::ExtensionsApi::from_raw(self.inner.extensions())
+        extensions::ExtensionsApi::new(self.client.scoped("extensions"))
     }
 
     pub fn generators(&self) -> generators::GeneratorsApi {
-        generators::GeneratorsApi::from_raw(self.inner.generators())
+        generators::GeneratorsApi::new(self.client.scoped("generators"))
     }
 
     pub fn geometry(&self) -> geometry::GeometryApi {
-        geometry::GeometryApi::from_raw(self.inner.geometry_api())
+        geometry::GeometryApi::new(self.client.scoped("geometry"))
     }
 
     pub fn integrate_elements(&self) -> integrate::IntegrateApi {
-        integrate::IntegrateApi::from_raw(self.inner.integrate_elements())
+        integrate::IntegrateApi::new(self.client.scoped("integrateElements"))
     }
 
     pub fn library(&self) -> library::LibraryApi {
-        library::LibraryApi::from_raw(self.inner.library())
+        library::LibraryApi::new(self.client.scoped("library"))
     }
 
     pub fn project(&self) -> project::ProjectApi {
-        project::ProjectApi::from_raw(self.inner.project())
+        project::ProjectApi::new(self.client.scoped("project"))
     }
 
     pub fn proposal(&self) -> proposal::ProposalApi {
-        proposal::ProposalApi::from_raw(self.inner.proposal())
+        proposal::ProposalApi::new(self.client.scoped("proposal"))
     }
 
     pub fn render(&self) -> render::RenderApi {
-        render::RenderApi::from_raw(self.inner.render())
+        render::RenderApi::new(self.client.scoped("render"))
     }
 
     pub fn selection(&self) -> selection::SelectionApi {
-        selection::SelectionApi::from_raw(self.inner.selection())
+        selection::SelectionApi::new(self.client.scoped("selection"))
     }
 
     pub fn sun(&self) -> sun::SunApi {
-        sun::SunApi::from_raw(self.inner.sun())
+        sun::SunApi::new(self.client.scoped("sun"))
     }
 
     pub fn terrain(&self) -> terrain::TerrainApi {
-        terrain::TerrainApi::from_raw(self.inner.terrain())
+        terrain::TerrainApi::new(self.client.scoped("terrain"))
     }
 
     pub fn geo_data(&self) -> geo_data::GeoDataApi {
-        geo_data::GeoDataApi::from_raw(self.inner.geo_data())
+        geo_data::GeoDataApi::new(self.client.scoped("geoData"))
     }
 
     pub fn predictive_analysis(&self) -> predictive_analysis::PredictiveAnalysisApi {
-        predictive_analysis::PredictiveAnalysisApi::from_raw(self.inner.predictive_analysis())
+        predictive_analysis::PredictiveAnalysisApi::new(self.client.scoped("predictiveAnalysis"))
     }
 }
 
 /// Pre-configured singleton SDK instance (from `forma-embedded-view-sdk/auto`).
+///
+/// If a host has been installed on this thread with [`host::install`], it is
+/// used instead of the JS SDK.
 pub fn forma() -> EmbeddedViewSdk {
+    if let Some(host) = host::installed() {
+        return EmbeddedViewSdk {
+            client: Client::new(host),
+        };
+    }
     js::FORMA.with(|f| {
         let js_val: &JsValue = f.as_ref();
         let cloned: js::EmbeddedViewSdk = js_val.clone().unchecked_into();
-        EmbeddedViewSdk::from_raw(cloned)
+        EmbeddedViewSdk::with_host(JsHost::from_raw(cloned))
     })
 }
@@ -1,6 +1,8 @@
+use crate::client::Client;
+use crate::host::HostValue;
+use crate::options::call_options;
 use crate::types::*;
 use crate::Result;
-use wasm_bindgen_futures::JsFuture;
 
 pub(crate) mod js {
     use wasm_bindgen::prelude::*;
@@ -18,36 +20,54 @@ pub(crate) mod js {
         #[wasm_bindgen(method, js_name = "deleteItem")]
         pub fn delete_item(this: &LibraryApi, request: &JsValue) -> ::js_sys::Promise;
     }
+
+    impl LibraryApi {
+        pub(crate) fn dispatch(&self, method: &str, args: &[JsValue]) -> Option<::js_sys::Promise> {
+            Some(match method {
+                "createItem" => self.create_item(&args[0]),
+                "updateItem" => self.update_item(&args[0]),
+                "deleteItem" => self.delete_item(&args[0]),
+                _ => return None,
+            })
+        }
+    }
 }
 
 /// Manage items in the user's Library.
 pub struct LibraryApi {
-    inner: js::LibraryApi,
+    client: Client,
 }
 
+call_options!(LibraryApi);
+
 impl LibraryApi {
-    pub(crate) fn from_raw(raw: js::LibraryApi) -> Self {
-        Self { inner: raw }
+    pub(crate) fn new(client: Client) -> Self {
+        Self { client }
     }
 
     /// Add data to Library as a new item.
     pub async fn create_item(&self, request: &LibraryCreateItemRequest) -> Result<LibraryItem> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.create_item(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self
+            .client
+            .call("createItem", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("createItem", result)
     }
 
     /// Update an existing library item.
     pub async fn update_item(&self, request: &LibraryUpdateItemRequest) -> Result<LibraryItem> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.update_item(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self
+            .client
+            .call("updateItem", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("updateItem", result)
     }
 
     /// Delete an existing library item.
     pub async fn delete_item(&self, request: &LibraryDeleteItemRequest) -> Result<()> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        JsFuture::from(self.inner.delete_item(&js_req)).await?;
+        self.client
+            .call("deleteItem", vec![HostValue::json(request)?])
+            .await?;
         Ok(())
     }
 }
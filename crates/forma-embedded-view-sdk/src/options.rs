@@ -0,0 +1,403 @@
+//! Per-call and SDK-wide call options: timeouts, cancellation, retries and
+//! response decoding.
+//!
+//! Every API handle (`forma().analysis()`, `forma().design_tool()`, ...) has
+//! `with_timeout`, `with_cancellation`, `with_retry` and `with_options`,
+//! which apply to the calls made through the returned handle:
+//!
+//! ```no_run
+//! use forma_embedded_view_sdk::{forma, CancellationToken};
+//! use std::time::Duration;
+//!
+//! # async fn run() -> forma_embedded_view_sdk::Result<()> {
+//! let cancel = CancellationToken::new();
+//! let polygon = forma()
+//!     .design_tool()
+//!     .with_timeout(Duration::from_secs(30))
+//!     .with_cancellation(&cancel)
+//!     .get_polygon()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Options set on [`EmbeddedViewSdk`](crate::EmbeddedViewSdk) are inherited
+//! by its sub-APIs. [`set_default_options`] sets SDK-wide defaults for
+//! handles created afterwards, including those returned by
+//! [`forma()`](crate::forma).
+//!
+//! A timed-out or cancelled call fails with [`SdkError::Timeout`] or
+//! [`SdkError::Cancelled`]. The JS promise itself cannot be aborted; its
+//! eventual result is discarded.
+//!
+//! Retries are opt-in. With a [`RetryPolicy`], a failed call is attempted
+//! again after an exponential, jittered backoff as long as the policy's
+//! predicate accepts the error (by default [`SdkError::is_transient`]). The
+//! timeout applies to each attempt; cancellation also interrupts the backoff.
+//!
+//! ```no_run
+//! use forma_embedded_view_sdk::{forma, RetryPolicy};
+//! use forma_embedded_view_sdk::types::StorageSetObjectRequest;
+//! use std::time::Duration;
+//!
+//! # async fn run() -> forma_embedded_view_sdk::Result<()> {
+//! let retry = RetryPolicy::new(4).backoff(Duration::from_millis(250), Duration::from_secs(5));
+//! forma()
+//!     .extensions()
+//!     .storage()
+//!     .with_retry(retry)
+//!     .set_object(&StorageSetObjectRequest {
+//!         key: "settings".into(),
+//!         data: "{}".into(),
+//!     })
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Responses are decoded strictly: every wrapper fails with
+//! [`SdkError::Decode`] when the host answers with a value of another shape,
+//! naming the method and the value received. For wrappers that expect a
+//! number, a boolean or a string, set [`Decoding::Lenient`] to fall back to
+//! `0.0`, `false` or `""` instead:
+//!
+//! ```no_run
+//! use forma_embedded_view_sdk::options::{set_default_options, CallOptions};
+//! use forma_embedded_view_sdk::Decoding;
+//!
+//! set_default_options(CallOptions::default().decoding(Decoding::Lenient));
+//! ```
+//!
+//! [`SdkError::Timeout`]: crate::SdkError::Timeout
+//! [`SdkError::Cancelled`]: crate::SdkError::Cancelled
+//! [`SdkError::is_transient`]: crate::SdkError::is_transient
+//! [`SdkError::Decode`]: crate::SdkError::Decode
+
+use crate::SdkError;
+use std::cell::RefCell;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+/// Options applied to calls made through an API handle.
+#[derive(Debug, Clone, Default)]
+pub struct CallOptions {
+    /// Fail with `SdkError::Timeout` if the host has not answered in time.
+    pub timeout: Option<Duration>,
+    /// Fail with `SdkError::Cancelled` once this token is cancelled.
+    pub cancellation: Option<CancellationToken>,
+    /// Retry failed calls according to this policy.
+    pub retry: Option<RetryPolicy>,
+    /// How responses of an unexpected shape are handled. Strict if unset.
+    pub decoding: Option<Decoding>,
+}
+
+/// How wrappers handle host responses of an unexpected shape.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Decoding {
+    /// Fail with `SdkError::Decode`.
+    #[default]
+    Strict,
+    /// Substitute a default (`0.0`, `false`, `""`).
+    Lenient,
+}
+
+impl CallOptions {
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn cancellation(mut self, token: &CancellationToken) -> Self {
+        self.cancellation = Some(token.clone());
+        self
+    }
+
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    pub fn decoding(mut self, decoding: Decoding) -> Self {
+        self.decoding = Some(decoding);
+        self
+    }
+
+    /// Overlay the options set in `other` on top of these.
+    pub(crate) fn merge(mut self, other: CallOptions) -> Self {
+        if other.timeout.is_some() {
+            self.timeout = other.timeout;
+        }
+        if other.cancellation.is_some() {
+            self.cancellation = other.cancellation;
+        }
+        if other.retry.is_some() {
+            self.retry = other.retry;
+        }
+        if other.decoding.is_some() {
+            self.decoding = other.decoding;
+        }
+        self
+    }
+}
+
+/// When and how often to retry a failed call.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first one.
+    pub max_attempts: u32,
+    /// Delay before the second attempt.
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between attempts.
+    pub max_backoff: Duration,
+    /// Factor applied to the delay after each attempt.
+    pub multiplier: f64,
+    /// Fraction of each delay that is randomized (0.0 to 1.0).
+    pub jitter: f64,
+    retry_if: Rc<dyn Fn(&SdkError) -> bool>,
+}
+
+impl RetryPolicy {
+    /// Up to `max_attempts` attempts for transient errors, starting at 200 ms
+    /// and doubling up to 10 s, with 20% jitter.
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(10),
+            multiplier: 2.0,
+            jitter: 0.2,
+            retry_if: Rc::new(SdkError::is_transient),
+        }
+    }
+
+    /// A policy that never retries, to override an SDK-wide policy per call.
+    pub fn never() -> Self {
+        Self::new(1)
+    }
+
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Retry only errors accepted by `predicate`.
+    pub fn retry_if(mut self, predicate: impl Fn(&SdkError) -> bool + 'static) -> Self {
+        self.retry_if = Rc::new(predicate);
+        self
+    }
+
+    /// Whether to make another attempt after `attempt` (1-based) failed with `err`.
+    pub fn should_retry(&self, attempt: u32, err: &SdkError) -> bool {
+        attempt < self.max_attempts && (self.retry_if)(err)
+    }
+
+    /// Backoff before the attempt following `attempt` (1-based). `random` is
+    /// a value in `[0, 1)` used for jitter.
+    pub fn delay(&self, attempt: u32, random: f64) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let base = (self.initial_backoff.as_secs_f64() * self.multiplier.powi(exponent))
+            .min(self.max_backoff.as_secs_f64());
+        let jittered = base * (1.0 - self.jitter * random);
+        Duration::from_secs_f64(jittered.max(0.0))
+    }
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("max_backoff", &self.max_backoff)
+            .field("multiplier", &self.multiplier)
+            .field("jitter", &self.jitter)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Uniform random value in `[0, 1)` for backoff jitter.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn random_unit() -> f64 {
+    ::js_sys::Math::random()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn random_unit() -> f64 {
+    use std::hash::{BuildHasher, Hasher};
+    let bits = std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+thread_local! {
+    static DEFAULTS: RefCell<CallOptions> = RefCell::new(CallOptions::default());
+}
+
+/// Set SDK-wide default options for handles created afterwards on this thread.
+pub fn set_default_options(options: CallOptions) {
+    DEFAULTS.with(|defaults| *defaults.borrow_mut() = options);
+}
+
+pub(crate) fn default_options() -> CallOptions {
+    DEFAULTS.with(|defaults| defaults.borrow().clone())
+}
+
+#[derive(Default)]
+struct TokenState {
+    cancelled: bool,
+    wakers: Vec<Waker>,
+}
+
+/// Cancels in-flight calls made with it. Clones share the same state.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    state: Rc<RefCell<TokenState>>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel every call made with this token, now and in the future.
+    pub fn cancel(&self) {
+        let wakers = {
+            let mut state = self.state.borrow_mut();
+            state.cancelled = true;
+            std::mem::take(&mut state.wakers)
+        };
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.borrow().cancelled
+    }
+
+    /// Resolves once the token is cancelled.
+    pub fn cancelled(&self) -> Cancelled {
+        Cancelled {
+            token: self.clone(),
+        }
+    }
+}
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+/// Future returned by [`CancellationToken::cancelled`].
+pub struct Cancelled {
+    token: CancellationToken,
+}
+
+impl Future for Cancelled {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.token.state.borrow_mut();
+        if state.cancelled {
+            return Poll::Ready(());
+        }
+        if !state.wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+            state.wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+/// Add `with_options`, `with_timeout`, `with_cancellation` and `with_retry` to API handles
+/// holding a `client: Client` field.
+macro_rules! call_options {
+    ($($api:ty),* $(,)?) => {
+        $(
+            impl $api {
+                /// Apply `options` to calls made through the returned handle.
+                pub fn with_options(mut self, options: $crate::options::CallOptions) -> Self {
+                    self.client = self.client.with_options(options);
+                    self
+                }
+
+                /// Fail calls made through the returned handle with
+                /// `SdkError::Timeout` if the host does not answer in time.
+                pub fn with_timeout(self, timeout: ::std::time::Duration) -> Self {
+                    self.with_options($crate::options::CallOptions::default().timeout(timeout))
+                }
+
+                /// Fail calls made through the returned handle with
+                /// `SdkError::Cancelled` once `token` is cancelled.
+                pub fn with_cancellation(self, token: &$crate::options::CancellationToken) -> Self {
+                    self.with_options($crate::options::CallOptions::default().cancellation(token))
+                }
+
+                /// Retry failed calls made through the returned handle
+                /// according to `policy`.
+                pub fn with_retry(self, policy: $crate::options::RetryPolicy) -> Self {
+                    self.with_options($crate::options::CallOptions::default().retry(policy))
+                }
+            }
+        )*
+    };
+}
+
+pub(crate) use call_options;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorDetails;
+
+    #[test]
+    fn delay_grows_up_to_the_maximum() {
+        let policy = RetryPolicy::new(10)
+            .backoff(Duration::from_millis(100), Duration::from_millis(500))
+            .jitter(0.0);
+        let delays: Vec<_> = (1..=5).map(|attempt| policy.delay(attempt, 0.5)).collect();
+        assert_eq!(delays, [100, 200, 400, 500, 500].map(Duration::from_millis));
+        assert_eq!(policy.delay(u32::MAX, 0.5), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn jitter_shortens_the_delay() {
+        let policy = RetryPolicy::new(3)
+            .backoff(Duration::from_millis(1000), Duration::from_secs(10))
+            .jitter(0.2);
+        assert_eq!(policy.delay(1, 0.0), Duration::from_millis(1000));
+        assert_eq!(policy.delay(1, 0.5), Duration::from_millis(900));
+        assert_eq!(RetryPolicy::new(3).jitter(7.0).jitter, 1.0);
+    }
+
+    #[test]
+    fn retries_transient_errors_until_the_last_attempt() {
+        let policy = RetryPolicy::new(3);
+        let unavailable = SdkError::HostUnavailable(ErrorDetails::new("port closed"));
+        let not_found = SdkError::NotFound(ErrorDetails::new("missing"));
+        assert!(policy.should_retry(1, &unavailable));
+        assert!(policy.should_retry(2, &unavailable));
+        assert!(!policy.should_retry(3, &unavailable));
+        assert!(!policy.should_retry(1, &not_found));
+        assert!(!RetryPolicy::never().should_retry(1, &unavailable));
+        let custom = RetryPolicy::new(3).retry_if(|err| matches!(err, SdkError::NotFound(_)));
+        assert!(custom.should_retry(1, &not_found));
+    }
+}
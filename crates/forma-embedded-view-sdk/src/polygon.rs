@@ -0,0 +1,361 @@
+//! Planar polygon helpers for footprints sent to the host (terrain pads,
+//! floor stacks).
+//!
+//! Rings are lists of `[x, y]` project coordinates. A closed ring repeats its
+//! first point at the end. Forma expects closed, counter-clockwise rings that
+//! do not cross themselves; [`normalize`] produces one from any simple
+//! outline and [`validate`] checks one before it is sent.
+
+use crate::types::Vec2;
+use crate::{ErrorDetails, SdkError};
+use std::fmt;
+
+/// Why a ring is not a valid footprint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolygonError {
+    /// Point at this index has a NaN or infinite coordinate.
+    NonFinite(usize),
+    /// The last point differs from the first.
+    NotClosed,
+    /// Points at this index and the next are equal.
+    DuplicatePoint(usize),
+    /// The ring has fewer than three distinct points.
+    TooFewPoints(usize),
+    /// The ring encloses no area.
+    Degenerate,
+    /// The ring runs clockwise.
+    Clockwise,
+    /// The edges starting at these indices cross or overlap.
+    SelfIntersecting(usize, usize),
+}
+
+impl fmt::Display for PolygonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolygonError::NonFinite(index) => write!(f, "point {index} is not finite"),
+            PolygonError::NotClosed => f.write_str("ring is not closed"),
+            PolygonError::DuplicatePoint(index) => {
+                write!(f, "point {index} is repeated by the next point")
+            }
+            PolygonError::TooFewPoints(count) => {
+                write!(f, "ring has {count} distinct points, at least 3 are needed")
+            }
+            PolygonError::Degenerate => f.write_str("ring encloses no area"),
+            PolygonError::Clockwise => f.write_str("ring is clockwise"),
+            PolygonError::SelfIntersecting(a, b) => {
+                write!(f, "edges {a} and {b} intersect")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PolygonError {}
+
+impl From<PolygonError> for SdkError {
+    fn from(err: PolygonError) -> Self {
+        SdkError::InvalidRequest(ErrorDetails::new(err.to_string()))
+    }
+}
+
+/// Whether the last point of `ring` repeats the first.
+pub fn is_closed(ring: &[Vec2]) -> bool {
+    ring.len() > 1 && ring.first() == ring.last()
+}
+
+/// Signed area of `ring`, positive if it runs counter-clockwise. Works on
+/// open and closed rings alike.
+pub fn signed_area(ring: &[Vec2]) -> f64 {
+    let doubled: f64 = ring
+        .iter()
+        .zip(ring.iter().cycle().skip(1))
+        .map(|(a, b)| a[0] * b[1] - b[0] * a[1])
+        .sum();
+    doubled / 2.0
+}
+
+/// Close `ring` and make it counter-clockwise.
+pub fn normalize(mut ring: Vec<Vec2>) -> Vec<Vec2> {
+    if !ring.is_empty() && !is_closed(&ring) {
+        ring.push(ring[0]);
+    }
+    if signed_area(&ring) < 0.0 {
+        ring.reverse();
+    }
+    ring
+}
+
+/// Check that `ring` is closed, counter-clockwise, encloses some area and
+/// does not cross itself.
+pub fn validate(ring: &[Vec2]) -> Result<(), PolygonError> {
+    if let Some(index) = ring
+        .iter()
+        .position(|point| !point[0].is_finite() || !point[1].is_finite())
+    {
+        return Err(PolygonError::NonFinite(index));
+    }
+    if !is_closed(ring) {
+        return Err(PolygonError::NotClosed);
+    }
+    let edges = ring.len() - 1;
+    if let Some(index) = (0..edges).find(|&i| ring[i] == ring[i + 1]) {
+        return Err(PolygonError::DuplicatePoint(index));
+    }
+    if edges < 3 {
+        return Err(PolygonError::TooFewPoints(edges));
+    }
+    for i in 0..edges {
+        for j in i + 1..edges {
+            let adjacent = j == i + 1 || (i == 0 && j == edges - 1);
+            let (a, b, c, d) = (ring[i], ring[i + 1], ring[j], ring[j + 1]);
+            let crosses = if adjacent {
+                // Adjacent edges share a point; they only clash if they fold
+                // back over each other.
+                let (shared, p, q) = if j == i + 1 { (b, a, d) } else { (a, b, c) };
+                orientation(shared, p, q) == 0.0 && dot(shared, p, q) > 0.0
+            } else {
+                segments_intersect(a, b, c, d)
+            };
+            if crosses {
+                return Err(PolygonError::SelfIntersecting(i, j));
+            }
+        }
+    }
+    let area = signed_area(ring);
+    if area == 0.0 {
+        return Err(PolygonError::Degenerate);
+    }
+    if area < 0.0 {
+        return Err(PolygonError::Clockwise);
+    }
+    Ok(())
+}
+
+/// Whether `point` lies inside `ring` (even-odd rule). Points on the
+/// boundary may go either way.
+pub fn contains(ring: &[Vec2], point: Vec2) -> bool {
+    let [x, y] = point;
+    let mut inside = false;
+    for (a, b) in ring.iter().zip(ring.iter().cycle().skip(1)) {
+        if (a[1] > y) != (b[1] > y) {
+            let crossing = a[0] + (y - a[1]) / (b[1] - a[1]) * (b[0] - a[0]);
+            if x < crossing {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+/// Whether `point` lies inside `ring` or within `tolerance` of its boundary.
+pub fn covers(ring: &[Vec2], point: Vec2, tolerance: f64) -> bool {
+    contains(ring, point)
+        || ring
+            .iter()
+            .zip(ring.iter().cycle().skip(1))
+            .any(|(&a, &b)| segment_distance(a, b, point) <= tolerance)
+}
+
+/// A point strictly inside `ring`, which must not cross itself, or `None` if
+/// it has fewer than three points. Unlike the centre of its bounding box,
+/// this lies inside concave rings too.
+pub fn interior_point(ring: &[Vec2]) -> Option<Vec2> {
+    let points = if is_closed(ring) {
+        &ring[..ring.len() - 1]
+    } else {
+        ring
+    };
+    let n = points.len();
+    if n < 3 {
+        return None;
+    }
+    // The lowest of the leftmost vertices is convex: its corner triangle lies
+    // inside the ring unless other vertices poke into it.
+    let (i, &v) = points
+        .iter()
+        .enumerate()
+        .min_by(|(_, p), (_, q)| p[0].total_cmp(&q[0]).then(p[1].total_cmp(&q[1])))?;
+    let (a, b) = (points[(i + n - 1) % n], points[(i + 1) % n]);
+    let in_corner = |p: Vec2| {
+        let turns = [
+            orientation(a, v, p),
+            orientation(v, b, p),
+            orientation(b, a, p),
+        ];
+        turns.iter().all(|turn| *turn > 0.0) || turns.iter().all(|turn| *turn < 0.0)
+    };
+    // The vertex deepest into the corner sees `v` through the ring's inside.
+    let deepest = points
+        .iter()
+        .copied()
+        .filter(|&p| in_corner(p))
+        .max_by(|&p, &q| {
+            let depth = |p| orientation(a, b, p).abs();
+            depth(p).total_cmp(&depth(q))
+        });
+    Some(match deepest {
+        Some(q) => [(v[0] + q[0]) / 2.0, (v[1] + q[1]) / 2.0],
+        None => [(a[0] + v[0] + b[0]) / 3.0, (a[1] + v[1] + b[1]) / 3.0],
+    })
+}
+
+/// Minimum and maximum corners of the bounding box of `ring`.
+pub fn bounds(ring: &[Vec2]) -> Option<(Vec2, Vec2)> {
+    let first = *ring.first()?;
+    Some(ring.iter().fold((first, first), |(min, max), point| {
+        (
+            [min[0].min(point[0]), min[1].min(point[1])],
+            [max[0].max(point[0]), max[1].max(point[1])],
+        )
+    }))
+}
+
+/// Cross product of `a -> b` and `a -> c`: positive if `c` is left of `a -> b`.
+fn orientation(a: Vec2, b: Vec2, c: Vec2) -> f64 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+/// Dot product of `a -> b` and `a -> c`.
+fn dot(a: Vec2, b: Vec2, c: Vec2) -> f64 {
+    (b[0] - a[0]) * (c[0] - a[0]) + (b[1] - a[1]) * (c[1] - a[1])
+}
+
+/// Whether `p`, known to be collinear with `a -> b`, lies within the segment.
+fn on_segment(a: Vec2, b: Vec2, p: Vec2) -> bool {
+    p[0] >= a[0].min(b[0])
+        && p[0] <= a[0].max(b[0])
+        && p[1] >= a[1].min(b[1])
+        && p[1] <= a[1].max(b[1])
+}
+
+/// Distance from `p` to the segment `a -> b`.
+fn segment_distance(a: Vec2, b: Vec2, p: Vec2) -> f64 {
+    let length = dot(a, b, b);
+    let t = if length > 0.0 {
+        (dot(a, b, p) / length).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let closest = [a[0] + t * (b[0] - a[0]), a[1] + t * (b[1] - a[1])];
+    (p[0] - closest[0]).hypot(p[1] - closest[1])
+}
+
+fn segments_intersect(a: Vec2, b: Vec2, c: Vec2, d: Vec2) -> bool {
+    let (o1, o2) = (orientation(a, b, c), orientation(a, b, d));
+    let (o3, o4) = (orientation(c, d, a), orientation(c, d, b));
+    if o1 * o2 < 0.0 && o3 * o4 < 0.0 {
+        return true;
+    }
+    (o1 == 0.0 && on_segment(a, b, c))
+        || (o2 == 0.0 && on_segment(a, b, d))
+        || (o3 == 0.0 && on_segment(c, d, a))
+        || (o4 == 0.0 && on_segment(c, d, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A U open at the top, with 0.2 m thick walls: the centre of its
+    /// bounding box is in the gap.
+    fn thin_u() -> Vec<Vec2> {
+        normalize(vec![
+            [0.0, 0.0],
+            [10.0, 0.0],
+            [10.0, 10.0],
+            [9.8, 10.0],
+            [9.8, 0.2],
+            [0.2, 0.2],
+            [0.2, 10.0],
+            [0.0, 10.0],
+        ])
+    }
+
+    fn square() -> Vec<Vec2> {
+        vec![[0.0, 0.0], [4.0, 0.0], [4.0, 4.0], [0.0, 4.0], [0.0, 0.0]]
+    }
+
+    #[test]
+    fn validate_accepts_normalized_rings() {
+        assert_eq!(validate(&square()), Ok(()));
+        assert_eq!(validate(&thin_u()), Ok(()));
+        let mut clockwise = square();
+        clockwise.reverse();
+        assert_eq!(normalize(clockwise), square());
+        assert_eq!(normalize(square()[..4].to_vec()), square());
+    }
+
+    #[test]
+    fn validate_rejects_invalid_rings() {
+        let mut clockwise = square();
+        clockwise.reverse();
+        let cases = [
+            (
+                vec![[0.0, 0.0], [f64::NAN, 1.0], [0.0, 0.0]],
+                PolygonError::NonFinite(1),
+            ),
+            (square()[..4].to_vec(), PolygonError::NotClosed),
+            (
+                vec![[0.0, 0.0], [4.0, 0.0], [4.0, 0.0], [0.0, 4.0], [0.0, 0.0]],
+                PolygonError::DuplicatePoint(1),
+            ),
+            (
+                vec![[0.0, 0.0], [4.0, 0.0], [0.0, 0.0]],
+                PolygonError::TooFewPoints(2),
+            ),
+            // Flat: the last edge folds back over the first.
+            (
+                vec![[0.0, 0.0], [2.0, 0.0], [4.0, 0.0], [0.0, 0.0]],
+                PolygonError::SelfIntersecting(0, 2),
+            ),
+            (clockwise, PolygonError::Clockwise),
+            // A bow tie: edges 0 and 2 cross.
+            (
+                vec![[0.0, 0.0], [4.0, 4.0], [4.0, 0.0], [0.0, 4.0], [0.0, 0.0]],
+                PolygonError::SelfIntersecting(0, 2),
+            ),
+        ];
+        for (ring, expected) in cases {
+            assert_eq!(validate(&ring), Err(expected), "{ring:?}");
+        }
+    }
+
+    #[test]
+    fn contains_and_covers() {
+        let square = square();
+        assert!(contains(&square, [1.0, 1.0]));
+        assert!(!contains(&square, [5.0, 1.0]));
+        assert!(!contains(&square, [-1.0, 2.0]));
+        assert!(!contains(&thin_u(), [5.0, 5.0]));
+        assert!(covers(&square, [4.05, 2.0], 0.1));
+        assert!(!covers(&square, [4.5, 2.0], 0.1));
+        assert_eq!(bounds(&thin_u()), Some(([0.0, 0.0], [10.0, 10.0])));
+        assert_eq!(signed_area(&square), 16.0);
+    }
+
+    #[test]
+    fn interior_point_lies_inside_concave_rings() {
+        let u = thin_u();
+        assert!(!contains(&u, [5.0, 5.0]));
+        assert!(contains(&u, interior_point(&u).unwrap()));
+
+        // The corner triangle at the lowest-leftmost vertex crosses the notch.
+        let l = normalize(vec![
+            [0.0, 0.0],
+            [10.0, 0.0],
+            [10.0, 0.5],
+            [0.5, 0.5],
+            [0.5, 10.0],
+            [0.0, 10.0],
+        ]);
+        assert!(contains(&l, interior_point(&l).unwrap()));
+
+        let mut clockwise = thin_u();
+        clockwise.reverse();
+        assert!(contains(&clockwise, interior_point(&clockwise).unwrap()));
+    }
+
+    #[test]
+    fn interior_point_needs_three_points() {
+        assert_eq!(interior_point(&[[0.0, 0.0], [1.0, 1.0], [0.0, 0.0]]), None);
+    }
+}
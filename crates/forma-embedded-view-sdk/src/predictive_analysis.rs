@@ -1,6 +1,9 @@
+use crate::analysis::{GridGeometry, GroundGrid};
+use crate::client::Client;
+use crate::host::HostValue;
+use crate::options::call_options;
 use crate::types::*;
 use crate::Result;
-use wasm_bindgen_futures::JsFuture;
 
 pub(crate) mod js {
     use wasm_bindgen::prelude::*;
@@ -18,31 +21,370 @@ pub(crate) mod js {
             request: &JsValue,
         ) -> ::js_sys::Promise;
     }
+
+    impl PredictiveAnalysisApi {
+        pub(crate) fn dispatch(&self, method: &str, args: &[JsValue]) -> Option<::js_sys::Promise> {
+            Some(match method {
+                "getWindParameters" => self.get_wind_parameters(),
+                "predictWind" => self.predict_wind(&args[0]),
+                _ => return None,
+            })
+        }
+    }
 }
 
 /// Interact with Forma's predictive models for rapid wind analysis.
 pub struct PredictiveAnalysisApi {
-    inner: js::PredictiveAnalysisApi,
+    client: Client,
 }
 
+call_options!(PredictiveAnalysisApi);
+
 impl PredictiveAnalysisApi {
-    pub(crate) fn from_raw(raw: js::PredictiveAnalysisApi) -> Self {
-        Self { inner: raw }
+    pub(crate) fn new(client: Client) -> Self {
+        Self { client }
     }
 
     /// Get the wind parameters (wind rose + surface roughness) used by Forma.
-    pub async fn get_wind_parameters(&self) -> Result<serde_json::Value> {
-        let result = JsFuture::from(self.inner.get_wind_parameters()).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+    pub async fn get_wind_parameters(&self) -> Result<WindParameters> {
+        let result = self.client.call("getWindParameters", vec![]).await?;
+        self.client.response("getWindParameters", result)
+    }
+
+    /// Predict wind conditions using Forma's rapid wind model. Values are
+    /// wind speeds in m/s at pedestrian height.
+    pub async fn predict_wind(&self, request: &PredictWindRequest) -> Result<GroundGrid> {
+        let result = self
+            .client
+            .call("predictWind", vec![HostValue::json(request)?])
+            .await?;
+        GroundGrid::decode(&self.client, "predictWind", result)
     }
 
-    /// Predict wind conditions using Forma's rapid wind model.
-    pub async fn predict_wind(
+    /// Predict wind conditions as untyped JSON.
+    pub async fn predict_wind_raw(
         &self,
         request: &PredictWindRequest,
     ) -> Result<serde_json::Value> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.predict_wind(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self
+            .client
+            .call("predictWind", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("predictWind", result)
+    }
+}
+
+/// Pedestrian wind comfort criteria: activity classes by the highest wind
+/// speed each one tolerates.
+///
+/// Apply them to a [`WindPredictionType::Comfort`] prediction:
+///
+/// ```no_run
+/// use forma_embedded_view_sdk::forma;
+/// use forma_embedded_view_sdk::predictive_analysis::ComfortCriteria;
+/// use forma_embedded_view_sdk::types::{PredictWindRequest, WindPredictionType};
+///
+/// # async fn run() -> forma_embedded_view_sdk::Result<()> {
+/// let wind = forma().predictive_analysis();
+/// let parameters = wind.get_wind_parameters().await?;
+/// let request = PredictWindRequest::new(WindPredictionType::Comfort, &parameters);
+/// let comfort = ComfortCriteria::lawson().classify(&wind.predict_wind(&request).await?);
+/// forma().colorbar().add(&comfort.criteria().colorbar()).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComfortCriteria {
+    pub name: String,
+    /// Classes from calmest to windiest. A speed belongs to the first class
+    /// whose `max_speed` it does not exceed.
+    pub classes: Vec<ComfortClass>,
+}
+
+/// An activity class of [`ComfortCriteria`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComfortClass {
+    pub label: String,
+    /// Highest wind speed in the class, in m/s. `f64::INFINITY` for the
+    /// last class.
+    pub max_speed: f64,
+    /// CSS color used for the class.
+    pub color: String,
+}
+
+impl ComfortClass {
+    pub fn new(label: impl Into<String>, max_speed: f64, color: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            max_speed,
+            color: color.into(),
+        }
+    }
+}
+
+impl ComfortCriteria {
+    /// Lawson (LDDC) criteria, on the wind speed exceeded 5% of the time.
+    pub fn lawson() -> Self {
+        Self {
+            name: "Lawson".into(),
+            classes: vec![
+                ComfortClass::new("Sitting", 4.0, "#1a9850"),
+                ComfortClass::new("Standing", 6.0, "#91cf60"),
+                ComfortClass::new("Strolling", 8.0, "#fee08b"),
+                ComfortClass::new("Business walking", 10.0, "#fc8d59"),
+                ComfortClass::new("Uncomfortable", f64::INFINITY, "#d73027"),
+            ],
+        }
+    }
+
+    /// Davenport criteria, on the wind speed exceeded 1.5% of the time.
+    ///
+    /// Forma's comfort prediction reports the speed exceeded 5% of the time,
+    /// which is lower. Applied to it, these thresholds rate sites as calmer
+    /// than Davenport intends; prefer [`lawson`](Self::lawson) there.
+    pub fn davenport() -> Self {
+        Self {
+            name: "Davenport".into(),
+            classes: vec![
+                ComfortClass::new("Long-term sitting", 3.6, "#1a9850"),
+                ComfortClass::new("Short-term sitting", 5.3, "#91cf60"),
+                ComfortClass::new("Strolling", 7.6, "#fee08b"),
+                ComfortClass::new("Walking fast", 9.8, "#fc8d59"),
+                ComfortClass::new("Uncomfortable", f64::INFINITY, "#d73027"),
+            ],
+        }
+    }
+
+    /// Index of the class `speed` (m/s) belongs to, or `None` if it is above
+    /// every class.
+    pub fn class_of(&self, speed: f64) -> Option<usize> {
+        self.classes
+            .iter()
+            .position(|class| speed <= class.max_speed)
+    }
+
+    /// Classify every unmasked cell of a wind speed grid.
+    pub fn classify(&self, grid: &GroundGrid) -> ComfortGrid {
+        let classes = grid
+            .values
+            .iter()
+            .zip(&grid.mask)
+            .map(|(speed, valid)| valid.then(|| self.class_of(*speed as f64)).flatten())
+            .collect();
+        ComfortGrid {
+            classes,
+            geometry: grid.geometry,
+            criteria: self.clone(),
+        }
+    }
+
+    /// Colorbar with one entry per class, titled with the criteria name.
+    pub fn colorbar(&self) -> ColorbarAddRequest {
+        ColorbarAddRequest {
+            entries: self
+                .classes
+                .iter()
+                .map(|class| ColorbarEntry {
+                    color: class.color.clone(),
+                    label: class.label.clone(),
+                })
+                .collect(),
+            title: Some(format!("Wind comfort ({})", self.name)),
+        }
+    }
+}
+
+/// Wind comfort class of each cell, laid out like the [`GroundGrid`] it was
+/// classified from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComfortGrid {
+    /// Row-major index into `criteria.classes`, `None` for masked cells.
+    classes: Vec<Option<usize>>,
+    geometry: GridGeometry,
+    criteria: ComfortCriteria,
+}
+
+impl ComfortGrid {
+    /// Row-major index of each cell's class in
+    /// [`criteria().classes`](ComfortCriteria::classes), `None` for masked
+    /// cells.
+    pub fn classes(&self) -> &[Option<usize>] {
+        &self.classes
+    }
+
+    pub fn geometry(&self) -> &GridGeometry {
+        &self.geometry
+    }
+
+    /// Criteria the grid was classified with.
+    pub fn criteria(&self) -> &ComfortCriteria {
+        &self.criteria
+    }
+
+    /// Class of cell `(col, row)`, or `None` if it is masked or out of range.
+    pub fn get(&self, col: usize, row: usize) -> Option<&ComfortClass> {
+        let GridGeometry { width, height, .. } = self.geometry;
+        if col >= width || row >= height {
+            return None;
+        }
+        let index = (*self.classes.get(row * width + col)?)?;
+        self.criteria.classes.get(index)
+    }
+
+    /// Class of the cell containing the project coordinates `(x, y)`.
+    pub fn class_at(&self, x: f64, y: f64) -> Option<&ComfortClass> {
+        let (col, row) = self.geometry.cell_at(x, y)?;
+        self.get(col, row)
+    }
+
+    /// Render the classes as an image for a ground texture, one pixel per
+    /// cell in the class color. Masked cells are transparent. Pair with
+    /// [`placement`](Self::placement). Fails for an empty grid.
+    pub fn to_image(&self) -> Result<RgbaImage> {
+        let colors: Vec<[u8; 4]> = self
+            .criteria
+            .classes
+            .iter()
+            .map(|class| parse_color(&class.color).unwrap_or([128, 128, 128, 255]))
+            .collect();
+        self.geometry
+            .render(|index| Some(colors[(*self.classes.get(index)?)?]))
+    }
+
+    /// Placement of a ground texture covering the grid.
+    pub fn placement(&self) -> GroundTexturePlacement {
+        self.geometry.placement()
+    }
+
+    /// Number of cells in each class, parallel to `criteria.classes`.
+    pub fn counts(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.criteria.classes.len()];
+        for index in self.classes.iter().flatten() {
+            counts[*index] += 1;
+        }
+        counts
+    }
+
+    /// Share of the classified cells in each class, parallel to
+    /// `criteria.classes`.
+    pub fn shares(&self) -> Vec<f64> {
+        let counts = self.counts();
+        let total: usize = counts.iter().sum();
+        counts
+            .into_iter()
+            .map(|count| match total {
+                0 => 0.0,
+                total => count as f64 / total as f64,
+            })
+            .collect()
+    }
+}
+
+/// Parse a `#rrggbb` or `#rrggbbaa` CSS color.
+fn parse_color(color: &str) -> Option<[u8; 4]> {
+    let hex = color.strip_prefix('#')?;
+    let channel = |index: usize| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok();
+    match hex.len() {
+        6 => Some([channel(0)?, channel(2)?, channel(4)?, 255]),
+        8 => Some([channel(0)?, channel(2)?, channel(4)?, channel(6)?]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 3x2 grid of wind speeds, with the last cell masked.
+    fn speeds() -> GroundGrid {
+        GroundGrid {
+            values: vec![3.0, 5.0, 7.0, 9.0, 12.0, 1.0],
+            mask: vec![true, true, true, true, true, false],
+            geometry: GridGeometry {
+                width: 3,
+                height: 2,
+                x0: 100.0,
+                y0: 200.0,
+                resolution: 10.0,
+            },
+        }
+    }
+
+    fn labels(grid: &ComfortGrid) -> Vec<Option<&str>> {
+        (0..2)
+            .flat_map(|row| (0..3).map(move |col| (col, row)))
+            .map(|(col, row)| grid.get(col, row).map(|class| class.label.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn lawson_classes_by_upper_bound() {
+        let criteria = ComfortCriteria::lawson();
+        assert_eq!(criteria.class_of(4.0), Some(0));
+        assert_eq!(criteria.class_of(4.01), Some(1));
+        assert_eq!(criteria.class_of(100.0), Some(4));
+
+        let comfort = criteria.classify(&speeds());
+        assert_eq!(
+            labels(&comfort),
+            [
+                Some("Sitting"),
+                Some("Standing"),
+                Some("Strolling"),
+                Some("Business walking"),
+                Some("Uncomfortable"),
+                None,
+            ]
+        );
+        assert_eq!(comfort.counts(), [1, 1, 1, 1, 1]);
+        assert_eq!(comfort.shares(), [0.2; 5]);
+        assert_eq!(
+            comfort
+                .class_at(105.0, 215.0)
+                .map(|class| class.label.as_str()),
+            Some("Business walking")
+        );
+        assert_eq!(comfort.class_at(125.0, 215.0), None);
+        assert_eq!(comfort.class_at(99.0, 205.0), None);
+    }
+
+    #[test]
+    fn davenport_classes_by_upper_bound() {
+        let comfort = ComfortCriteria::davenport().classify(&speeds());
+        assert_eq!(
+            labels(&comfort),
+            [
+                Some("Long-term sitting"),
+                Some("Short-term sitting"),
+                Some("Strolling"),
+                Some("Walking fast"),
+                Some("Uncomfortable"),
+                None,
+            ]
+        );
+        assert_eq!(comfort.criteria().colorbar().entries.len(), 5);
+    }
+
+    #[test]
+    fn to_image_flips_rows_and_leaves_masked_cells_transparent() {
+        let image = ComfortCriteria::lawson()
+            .classify(&speeds())
+            .to_image()
+            .unwrap();
+        // Grid row 0 is the bottom image row.
+        assert_eq!(image.pixel(0, 1), Some([0x1a, 0x98, 0x50, 255]));
+        assert_eq!(image.pixel(1, 0), Some([0xd7, 0x30, 0x27, 255]));
+        assert_eq!(image.pixel(2, 0), Some([0; 4]));
+    }
+
+    #[test]
+    fn grid_shorter_than_its_geometry_does_not_panic() {
+        let mut grid = speeds();
+        grid.values.truncate(2);
+        let comfort = ComfortCriteria::lawson().classify(&grid);
+        assert_eq!(comfort.classes().len(), 2);
+        assert_eq!(comfort.get(2, 1), None);
+        assert_eq!(comfort.counts(), [1, 1, 0, 0, 0]);
+        assert!(comfort.to_image().is_ok());
     }
 }
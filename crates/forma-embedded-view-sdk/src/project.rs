@@ -1,6 +1,7 @@
+use crate::client::Client;
+use crate::options::call_options;
 use crate::types::*;
 use crate::Result;
-use wasm_bindgen_futures::JsFuture;
 
 pub(crate) mod js {
     use wasm_bindgen::prelude::*;
@@ -15,30 +16,46 @@ pub(crate) mod js {
         #[wasm_bindgen(method, js_name = "getGeoLocation")]
         pub fn get_geo_location(this: &ProjectApi) -> ::js_sys::Promise;
     }
+
+    impl ProjectApi {
+        pub(crate) fn dispatch(
+            &self,
+            method: &str,
+            _args: &[JsValue],
+        ) -> Option<::js_sys::Promise> {
+            Some(match method {
+                "get" => self.get(),
+                "getGeoLocation" => self.get_geo_location(),
+                _ => return None,
+            })
+        }
+    }
 }
 
 /// Access project-level metadata.
 pub struct ProjectApi {
-    inner: js::ProjectApi,
+    client: Client,
 }
 
+call_options!(ProjectApi);
+
 impl ProjectApi {
-    pub(crate) fn from_raw(raw: js::ProjectApi) -> Self {
-        Self { inner: raw }
+    pub(crate) fn new(client: Client) -> Self {
+        Self { client }
     }
 
     /// Fetch all project metadata.
     pub async fn get(&self) -> Result<Project> {
-        let result = JsFuture::from(self.inner.get()).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self.client.call("get", vec![]).await?;
+        self.client.response("get", result)
     }
 
     /// Fetch project location as `[latitude, longitude]`, or `None` if not set.
     pub async fn get_geo_location(&self) -> Result<Option<[f64; 2]>> {
-        let result = JsFuture::from(self.inner.get_geo_location()).await?;
-        if result.is_undefined() || result.is_null() {
+        let result = self.client.call("getGeoLocation", vec![]).await?;
+        if result.is_nullish() {
             return Ok(None);
         }
-        Ok(Some(serde_wasm_bindgen::from_value(result)?))
+        Ok(Some(self.client.response("getGeoLocation", result)?))
     }
 }
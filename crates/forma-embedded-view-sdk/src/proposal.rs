@@ -1,10 +1,14 @@
-use crate::subscription::Subscription;
+use crate::client::Client;
+use crate::elements::ElementsApi;
+use crate::host::HostValue;
+use crate::options::call_options;
+use crate::subscription::{EventStream, Subscription};
 use crate::types::*;
 use crate::Result;
-use wasm_bindgen::prelude::*;
-use wasm_bindgen_futures::JsFuture;
+use std::collections::BTreeMap;
 
 pub(crate) mod js {
+    use crate::js_host::optional;
     use wasm_bindgen::prelude::*;
 
     #[wasm_bindgen]
@@ -63,35 +67,74 @@ pub(crate) mod js {
         #[wasm_bindgen(method, js_name = "switch")]
         pub fn switch(this: &ProposalApi, request: &JsValue) -> ::js_sys::Promise;
     }
+
+    impl ProposalApi {
+        pub(crate) fn dispatch(&self, method: &str, args: &[JsValue]) -> Option<::js_sys::Promise> {
+            Some(match method {
+                "getRootUrn" => self.get_root_urn(),
+                "getId" => self.get_id(),
+                "addElement" => self.add_element(&args[0]),
+                "replaceElement" => self.replace_element(&args[0]),
+                "removeElement" => self.remove_element(&args[0]),
+                "replaceTerrain" => self.replace_terrain(&args[0]),
+                "updateElements" => self.update_elements(&args[0]),
+                "awaitProposalPersisted" => self.await_proposal_persisted(),
+                "getAll" => self.get_all(),
+                "get" => self.get(&args[0]),
+                "create" => self.create(&args[0]),
+                "update" => self.update(&args[0]),
+                "delete" => self.delete(&args[0]),
+                "duplicate" => self.duplicate(&args[0]),
+                "switch" => self.switch(&args[0]),
+                _ => return None,
+            })
+        }
+
+        pub(crate) fn listen(
+            &self,
+            event: &str,
+            handler: &Closure<dyn FnMut(JsValue)>,
+            args: &[JsValue],
+        ) -> Option<::js_sys::Promise> {
+            match event {
+                "subscribe" => Some(self.subscribe(handler, optional(&args[0]))),
+                _ => None,
+            }
+        }
+    }
 }
 
 /// Access proposal metadata and modify elements in it.
 pub struct ProposalApi {
-    inner: js::ProposalApi,
+    client: Client,
 }
 
+call_options!(ProposalApi);
+
 impl ProposalApi {
-    pub(crate) fn from_raw(raw: js::ProposalApi) -> Self {
-        Self { inner: raw }
+    pub(crate) fn new(client: Client) -> Self {
+        Self { client }
     }
 
     /// Fetch the top-level URN for the proposal.
     pub async fn get_root_urn(&self) -> Result<String> {
-        let result = JsFuture::from(self.inner.get_root_urn()).await?;
-        Ok(result.as_string().unwrap_or_default())
+        let result = self.client.call("getRootUrn", vec![]).await?;
+        self.client.decode("getRootUrn", "a string", result)
     }
 
     /// Fetch the unique identifier of the proposal.
     pub async fn get_id(&self) -> Result<String> {
-        let result = JsFuture::from(self.inner.get_id()).await?;
-        Ok(result.as_string().unwrap_or_default())
+        let result = self.client.call("getId", vec![]).await?;
+        self.client.decode("getId", "a string", result)
     }
 
     /// Add a new element to the proposal.
     pub async fn add_element(&self, request: &ProposalAddElementRequest) -> Result<PathResult> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.add_element(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self
+            .client
+            .call("addElement", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("addElement", result)
     }
 
     /// Replace an element in the proposal.
@@ -99,15 +142,17 @@ impl ProposalApi {
         &self,
         request: &ProposalReplaceElementRequest,
     ) -> Result<()> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        JsFuture::from(self.inner.replace_element(&js_req)).await?;
+        self.client
+            .call("replaceElement", vec![HostValue::json(request)?])
+            .await?;
         Ok(())
     }
 
     /// Remove an element from the proposal.
     pub async fn remove_element(&self, request: &ProposalRemoveElementRequest) -> Result<()> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        JsFuture::from(self.inner.remove_element(&js_req)).await?;
+        self.client
+            .call("removeElement", vec![HostValue::json(request)?])
+            .await?;
         Ok(())
     }
 
@@ -116,8 +161,9 @@ impl ProposalApi {
         &self,
         request: &ProposalReplaceTerrainRequest,
     ) -> Result<()> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        JsFuture::from(self.inner.replace_terrain(&js_req)).await?;
+        self.client
+            .call("replaceTerrain", vec![HostValue::json(request)?])
+            .await?;
         Ok(())
     }
 
@@ -126,86 +172,202 @@ impl ProposalApi {
         &self,
         request: &ProposalUpdateElementsRequest,
     ) -> Result<()> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        JsFuture::from(self.inner.update_elements(&js_req)).await?;
+        self.client
+            .call("updateElements", vec![HostValue::json(request)?])
+            .await?;
         Ok(())
     }
 
     /// Subscribe to proposal changes.
     pub async fn subscribe(
         &self,
-        mut callback: impl FnMut(ProposalChangeEvent) + 'static,
+        callback: impl FnMut(ProposalChangeEvent) + 'static,
         options: Option<&ProposalSubscribeOptions>,
     ) -> Result<Subscription> {
-        let closure = Closure::wrap(Box::new(move |val: JsValue| {
-            if let Ok(event) = serde_wasm_bindgen::from_value(val) {
-                callback(event);
-            }
-        }) as Box<dyn FnMut(JsValue)>);
         let js_opts = options
-            .map(serde_wasm_bindgen::to_value)
-            .transpose()?;
-        let result =
-            JsFuture::from(self.inner.subscribe(&closure, js_opts.as_ref())).await?;
-        let unsubscribe_fn: ::js_sys::Function =
-            ::js_sys::Reflect::get(&result, &"unsubscribe".into())?.into();
-        Ok(Subscription::new(closure, unsubscribe_fn))
+            .map(HostValue::json)
+            .transpose()?
+            .unwrap_or(HostValue::Undefined);
+        self.client
+            .subscribe_to("subscribe", vec![js_opts], callback)
+            .await
+    }
+
+    /// Proposal changes as a stream. Unsubscribes when dropped.
+    pub async fn changes(
+        &self,
+        options: Option<&ProposalSubscribeOptions>,
+    ) -> Result<EventStream<ProposalChangeEvent>> {
+        let (sender, stream) = EventStream::channel();
+        let subscription = self
+            .subscribe(move |event| sender.send(event), options)
+            .await?;
+        Ok(stream.attach(subscription))
     }
 
     /// Wait until the currently loaded proposal is persisted.
     pub async fn await_proposal_persisted(&self) -> Result<()> {
-        JsFuture::from(self.inner.await_proposal_persisted()).await?;
+        self.client.call("awaitProposalPersisted", vec![]).await?;
         Ok(())
     }
 
     /// Get all proposals for the current project.
-    pub async fn get_all(&self) -> Result<serde_json::Value> {
-        let result = JsFuture::from(self.inner.get_all()).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+    pub async fn get_all(&self) -> Result<Vec<ProposalInfo>> {
+        let result = self.client.call("getAll", vec![]).await?;
+        self.client.response("getAll", result)
+    }
+
+    /// Get all proposals for the current project as untyped JSON.
+    pub async fn get_all_raw(&self) -> Result<serde_json::Value> {
+        let result = self.client.call("getAll", vec![]).await?;
+        self.client.response("getAll", result)
     }
 
     /// Get a proposal by ID and optional revision.
-    pub async fn get(&self, request: &ProposalGetRequest) -> Result<serde_json::Value> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.get(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+    pub async fn get(&self, request: &ProposalGetRequest) -> Result<ProposalInfo> {
+        let result = self
+            .client
+            .call("get", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("get", result)
     }
 
     /// Create a new proposal.
-    pub async fn create(&self, request: &ProposalCreateRequest) -> Result<serde_json::Value> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.create(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+    pub async fn create(&self, request: &ProposalCreateRequest) -> Result<ProposalInfo> {
+        let result = self
+            .client
+            .call("create", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("create", result)
     }
 
     /// Update an existing proposal.
-    pub async fn update(&self, request: &ProposalUpdateRequest) -> Result<serde_json::Value> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.update(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+    pub async fn update(&self, request: &ProposalUpdateRequest) -> Result<ProposalInfo> {
+        let result = self
+            .client
+            .call("update", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("update", result)
     }
 
     /// Delete a proposal (soft delete).
     pub async fn delete(&self, request: &ProposalDeleteRequest) -> Result<()> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        JsFuture::from(self.inner.delete(&js_req)).await?;
+        self.client
+            .call("delete", vec![HostValue::json(request)?])
+            .await?;
         Ok(())
     }
 
     /// Duplicate a proposal.
-    pub async fn duplicate(
-        &self,
-        request: &ProposalDuplicateRequest,
-    ) -> Result<serde_json::Value> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.duplicate(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+    pub async fn duplicate(&self, request: &ProposalDuplicateRequest) -> Result<ProposalInfo> {
+        let result = self
+            .client
+            .call("duplicate", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("duplicate", result)
     }
 
     /// Switch to a different proposal.
     pub async fn switch(&self, request: &ProposalSwitchRequest) -> Result<()> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        JsFuture::from(self.inner.switch(&js_req)).await?;
+        self.client
+            .call("switch", vec![HostValue::json(request)?])
+            .await?;
         Ok(())
     }
+
+    /// Compare two revisions of a proposal by root URN, e.g. the root URN
+    /// before a change event and the event's
+    /// [`root_urn`](ProposalChangeEvent::root_urn).
+    ///
+    /// Paths are compared across the whole element hierarchy: a path is
+    /// replaced when it points to a different element URN (or revision).
+    /// Descendants of added or removed paths are listed too.
+    pub async fn diff(&self, before_root_urn: &str, after_root_urn: &str) -> Result<ProposalDiff> {
+        let elements = ElementsApi::new(self.client.scoped("elements"));
+        let before = elements
+            .get(&GetElementRequest {
+                urn: before_root_urn.to_string(),
+            })
+            .await?;
+        let after = elements
+            .get(&GetElementRequest {
+                urn: after_root_urn.to_string(),
+            })
+            .await?;
+        Ok(ProposalDiff::between(&before, &after))
+    }
+}
+
+impl ProposalDiff {
+    /// Compare the hierarchies of two root elements.
+    pub fn between(before: &ElementResponse, after: &ElementResponse) -> Self {
+        let before = paths(before);
+        let after = paths(after);
+        let mut diff = ProposalDiff::default();
+        for (path, urn) in &after {
+            match before.get(path) {
+                None => diff.added.push(path.clone()),
+                Some(previous) if previous != urn => diff.replaced.push(path.clone()),
+                Some(_) => {}
+            }
+        }
+        diff.removed = before
+            .into_keys()
+            .filter(|path| !after.contains_key(path))
+            .collect();
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.replaced.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl ProposalChangeEvent {
+    /// The new revision, as reported by the host or taken from the root URN.
+    pub fn revision(&self) -> Option<&str> {
+        self.revision
+            .as_deref()
+            .or_else(|| urn_revision(&self.root_urn))
+    }
+}
+
+impl ProposalInfo {
+    /// The revision, as reported by the host or taken from the root URN.
+    pub fn revision(&self) -> Option<&str> {
+        self.revision
+            .as_deref()
+            .or_else(|| urn_revision(&self.root_urn))
+    }
+}
+
+/// Revision segment of an element URN
+/// (`urn:adsk-forma-elements:<system>:<authcontext>:<id>:<revision>`).
+fn urn_revision(urn: &str) -> Option<&str> {
+    let segments: Vec<&str> = urn.split(':').collect();
+    match segments.as_slice() {
+        ["urn", _, _, _, _, revision] if !revision.is_empty() => Some(revision),
+        _ => None,
+    }
+}
+
+/// Element URN at every path below the root (`"root/<key>/<key>"`).
+fn paths(response: &ElementResponse) -> BTreeMap<String, String> {
+    fn walk(
+        response: &ElementResponse,
+        element: &Element,
+        path: &str,
+        out: &mut BTreeMap<String, String>,
+    ) {
+        for child in &element.children {
+            let path = format!("{path}/{}", child.key);
+            out.insert(path.clone(), child.urn.clone());
+            if let Some(element) = response.get(&child.urn) {
+                walk(response, element, &path, out);
+            }
+        }
+    }
+    let mut out = BTreeMap::new();
+    walk(response, &response.element, "root", &mut out);
+    out
 }
@@ -0,0 +1,559 @@
+//! Record SDK traffic to JSON fixtures and replay it without a browser.
+//!
+//! [`Recorder`] wraps any [`FormaHost`] and logs every call made through it:
+//! the sub-API and method, the arguments, the response or error, and timing.
+//! Subscription events delivered while recording are logged with the
+//! subscription that received them. [`Replay`] answers calls from such a log.
+//!
+//! Record a session in Forma:
+//!
+//! ```no_run
+//! use forma_embedded_view_sdk::recording::Recorder;
+//! use forma_embedded_view_sdk::{forma, host};
+//!
+//! let recorder = Recorder::new(forma().host());
+//! let _guard = host::install(recorder.clone());
+//! // ... run the extension flow, then save the log:
+//! let fixture = recorder.recording().to_json()?;
+//! # Ok::<(), forma_embedded_view_sdk::SdkError>(())
+//! ```
+//!
+//! Replay it in CI:
+//!
+//! ```no_run
+//! use forma_embedded_view_sdk::recording::Replay;
+//!
+//! let fixture = std::fs::read_to_string("tests/fixtures/color_selected_buildings.json").unwrap();
+//! let replay = Replay::from_json(&fixture)?;
+//! let _guard = replay.install();
+//! // ... run the same flow against `forma()` ...
+//! replay.flush_events();
+//! assert_eq!(replay.remaining(), 0);
+//! # Ok::<(), forma_embedded_view_sdk::SdkError>(())
+//! ```
+
+use crate::error::ErrorDetails;
+use crate::host::{
+    self, FormaHost, HostCall, HostFuture, HostHandler, HostReplySubscribe, HostSubscribe,
+    HostValue, InstalledHost,
+};
+use crate::subscription::Subscription;
+use crate::{Result, SdkError};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::rc::Rc;
+
+/// Fixture format version written by [`Recording::to_json`].
+const VERSION: u32 = 2;
+
+/// How an exchange reached the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExchangeKind {
+    Call,
+    CallSync,
+    Subscribe,
+    SubscribeSync,
+    SubscribeReply,
+}
+
+/// One recorded call.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Exchange {
+    pub kind: ExchangeKind,
+    pub api: String,
+    pub method: String,
+    pub args: Vec<HostValue>,
+    /// The response, if the call succeeded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<HostValue>,
+    /// The error, if the call failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<SdkError>,
+    /// Events delivered to the handler of a subscription, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<RecordedEvent>,
+    /// Milliseconds from the start of the recording to the call.
+    pub started_ms: f64,
+    /// Milliseconds until the call resolved.
+    pub duration_ms: f64,
+}
+
+/// An event delivered to a recorded subscription.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordedEvent {
+    pub value: HostValue,
+    /// Number of exchanges in the log when the event was delivered.
+    pub exchanges_before: usize,
+}
+
+/// A log of exchanges, serializable to a JSON fixture.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Recording {
+    pub version: u32,
+    pub exchanges: Vec<Exchange>,
+}
+
+impl Recording {
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+struct Log {
+    start_ms: f64,
+    exchanges: Vec<Exchange>,
+}
+
+impl Log {
+    fn begin(&mut self, kind: ExchangeKind, api: &str, method: &str, args: &[HostValue]) -> usize {
+        self.exchanges.push(Exchange {
+            kind,
+            api: api.into(),
+            method: method.into(),
+            args: args.iter().map(recordable).collect(),
+            response: None,
+            error: None,
+            events: Vec::new(),
+            started_ms: now_ms() - self.start_ms,
+            duration_ms: 0.0,
+        });
+        self.exchanges.len() - 1
+    }
+
+    fn event(&mut self, index: usize, value: &HostValue) {
+        let exchanges_before = self.exchanges.len();
+        self.exchanges[index].events.push(RecordedEvent {
+            value: recordable(value),
+            exchanges_before,
+        });
+    }
+
+    fn finish(&mut self, index: usize, result: std::result::Result<HostValue, &SdkError>) {
+        let exchange = &mut self.exchanges[index];
+        exchange.duration_ms = now_ms() - self.start_ms - exchange.started_ms;
+        match result {
+            Ok(response) => exchange.response = Some(response),
+            Err(err) => exchange.error = Some(err.clone()),
+        }
+    }
+}
+
+/// [`FormaHost`] that forwards to another host and logs every exchange.
+/// Clones share the same log.
+#[derive(Clone)]
+pub struct Recorder {
+    inner: Rc<dyn FormaHost>,
+    log: Rc<RefCell<Log>>,
+}
+
+impl Recorder {
+    pub fn new(inner: impl FormaHost + 'static) -> Self {
+        Self {
+            inner: Rc::new(inner),
+            log: Rc::new(RefCell::new(Log {
+                start_ms: now_ms(),
+                exchanges: Vec::new(),
+            })),
+        }
+    }
+
+    /// Snapshot of everything recorded so far.
+    pub fn recording(&self) -> Recording {
+        Recording {
+            version: VERSION,
+            exchanges: self.log.borrow().exchanges.clone(),
+        }
+    }
+
+    /// Log a subscription and wrap its handler to log delivered events.
+    fn begin_subscribe(
+        &self,
+        kind: ExchangeKind,
+        subscribe: HostSubscribe,
+    ) -> (usize, HostSubscribe) {
+        let index =
+            self.log
+                .borrow_mut()
+                .begin(kind, subscribe.api, subscribe.event, &subscribe.args);
+        let log = self.log.clone();
+        let mut handler = subscribe.handler;
+        let subscribe = HostSubscribe {
+            handler: Box::new(move |value| {
+                log.borrow_mut().event(index, &value);
+                handler(value);
+            }),
+            ..subscribe
+        };
+        (index, subscribe)
+    }
+}
+
+impl FormaHost for Recorder {
+    fn call(&self, call: HostCall) -> HostFuture<HostValue> {
+        let index =
+            self.log
+                .borrow_mut()
+                .begin(ExchangeKind::Call, call.api, call.method, &call.args);
+        let log = self.log.clone();
+        let future = self.inner.call(call);
+        Box::pin(async move {
+            let result = future.await;
+            log.borrow_mut()
+                .finish(index, result.as_ref().map(recordable));
+            result
+        })
+    }
+
+    fn call_sync(&self, call: HostCall) -> Result<HostValue> {
+        let index =
+            self.log
+                .borrow_mut()
+                .begin(ExchangeKind::CallSync, call.api, call.method, &call.args);
+        let result = self.inner.call_sync(call);
+        self.log
+            .borrow_mut()
+            .finish(index, result.as_ref().map(recordable));
+        result
+    }
+
+    fn subscribe(&self, subscribe: HostSubscribe) -> HostFuture<Subscription> {
+        let (index, subscribe) = self.begin_subscribe(ExchangeKind::Subscribe, subscribe);
+        let log = self.log.clone();
+        let future = self.inner.subscribe(subscribe);
+        Box::pin(async move {
+            let result = future.await;
+            log.borrow_mut()
+                .finish(index, result.as_ref().map(|_| HostValue::Undefined));
+            result
+        })
+    }
+
+    fn subscribe_sync(&self, subscribe: HostSubscribe) -> Result<Subscription> {
+        let (index, subscribe) = self.begin_subscribe(ExchangeKind::SubscribeSync, subscribe);
+        let result = self.inner.subscribe_sync(subscribe);
+        self.log
+            .borrow_mut()
+            .finish(index, result.as_ref().map(|_| HostValue::Undefined));
+        result
+    }
+
+    fn subscribe_with_reply(&self, subscribe: HostReplySubscribe) -> Result<Subscription> {
+        let index = self.log.borrow_mut().begin(
+            ExchangeKind::SubscribeReply,
+            subscribe.api,
+            subscribe.event,
+            &subscribe.args,
+        );
+        let log = self.log.clone();
+        let mut handler = subscribe.handler;
+        let subscribe = HostReplySubscribe {
+            handler: Box::new(move |value| {
+                log.borrow_mut().event(index, &value);
+                handler(value)
+            }),
+            ..subscribe
+        };
+        let result = self.inner.subscribe_with_reply(subscribe);
+        self.log
+            .borrow_mut()
+            .finish(index, result.as_ref().map(|_| HostValue::Undefined));
+        result
+    }
+}
+
+/// [`FormaHost`] that answers calls from a [`Recording`].
+///
+/// Each call consumes the first unused exchange with the same kind, sub-API,
+/// method and arguments, so concurrent calls may resolve in a different order
+/// than they were recorded. Recorded events are queued when their
+/// subscription is registered and delivered in log order: before the first
+/// call made after the point they were recorded at, or by
+/// [`flush_events`](Self::flush_events) once the flow has no calls left.
+/// Handlers the host waits on (`onEmbeddedViewClosing`) are registered
+/// without being called. Timing is not reproduced.
+#[derive(Clone)]
+pub struct Replay {
+    state: Rc<RefCell<ReplayState>>,
+}
+
+struct ReplayState {
+    exchanges: Vec<Option<Exchange>>,
+    /// Number of exchanges replayed so far.
+    replayed: usize,
+    /// Events not yet delivered, in log order.
+    pending: VecDeque<PendingEvent>,
+}
+
+struct PendingEvent {
+    exchanges_before: usize,
+    value: HostValue,
+    handler: Rc<RefCell<HostHandler>>,
+}
+
+impl Replay {
+    pub fn new(recording: Recording) -> Self {
+        Self {
+            state: Rc::new(RefCell::new(ReplayState {
+                exchanges: recording.exchanges.into_iter().map(Some).collect(),
+                replayed: 0,
+                pending: VecDeque::new(),
+            })),
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(Self::new(Recording::from_json(json)?))
+    }
+
+    /// Make [`forma()`](crate::forma) answer from this recording on the
+    /// current thread until the guard is dropped.
+    pub fn install(&self) -> InstalledHost {
+        host::install(self.clone())
+    }
+
+    /// Number of recorded exchanges that have not been replayed.
+    pub fn remaining(&self) -> usize {
+        self.state.borrow().exchanges.iter().flatten().count()
+    }
+
+    /// Deliver every queued event, including those recorded after the last
+    /// call, and return how many were delivered.
+    pub fn flush_events(&self) -> usize {
+        self.deliver(usize::MAX)
+    }
+
+    /// Deliver queued events recorded when the log held at most `up_to`
+    /// exchanges. Stops at an event whose handler is running, which is
+    /// delivered once that handler returns.
+    fn deliver(&self, up_to: usize) -> usize {
+        let mut delivered = 0;
+        loop {
+            let (value, handler) = {
+                let mut state = self.state.borrow_mut();
+                let ready = state.pending.front().is_some_and(|event| {
+                    event.exchanges_before <= up_to && event.handler.try_borrow_mut().is_ok()
+                });
+                if !ready {
+                    break;
+                }
+                let Some(event) = state.pending.pop_front() else {
+                    break;
+                };
+                (event.value, event.handler)
+            };
+            (handler.borrow_mut())(value);
+            delivered += 1;
+        }
+        delivered
+    }
+
+    fn take(
+        &self,
+        kind: ExchangeKind,
+        api: &str,
+        method: &str,
+        args: &[HostValue],
+    ) -> Result<Exchange> {
+        let replayed = self.state.borrow().replayed;
+        self.deliver(replayed);
+        let args = serde_json::to_value(args.iter().map(recordable).collect::<Vec<_>>())?;
+        let mut state = self.state.borrow_mut();
+        let exchange = state
+            .exchanges
+            .iter_mut()
+            .find(|slot| {
+                slot.as_ref().is_some_and(|exchange| {
+                    exchange.kind == kind
+                        && exchange.api == api
+                        && exchange.method == method
+                        && serde_json::to_value(&exchange.args).ok().as_ref() == Some(&args)
+                })
+            })
+            .and_then(Option::take)
+            .ok_or_else(|| {
+                SdkError::Host(ErrorDetails::new(format!(
+                    "no recorded response for {api}.{method}({args})"
+                )))
+            })?;
+        state.replayed += 1;
+        Ok(exchange)
+    }
+
+    fn call_with(&self, kind: ExchangeKind, call: HostCall) -> Result<HostValue> {
+        let exchange = self.take(kind, call.api, call.method, &call.args)?;
+        match exchange.error {
+            Some(error) => Err(error),
+            None => Ok(exchange.response.unwrap_or(HostValue::Undefined)),
+        }
+    }
+
+    fn replay_subscribe(
+        &self,
+        kind: ExchangeKind,
+        subscribe: HostSubscribe,
+    ) -> Result<Subscription> {
+        let exchange = self.take(kind, subscribe.api, subscribe.event, &subscribe.args)?;
+        if let Some(error) = exchange.error {
+            return Err(error);
+        }
+        let handler = Rc::new(RefCell::new(subscribe.handler));
+        let mut state = self.state.borrow_mut();
+        for event in exchange.events {
+            let at = state
+                .pending
+                .partition_point(|pending| pending.exchanges_before <= event.exchanges_before);
+            state.pending.insert(
+                at,
+                PendingEvent {
+                    exchanges_before: event.exchanges_before,
+                    value: event.value,
+                    handler: handler.clone(),
+                },
+            );
+        }
+        // Unsubscribing drops the events not delivered yet.
+        let state = Rc::downgrade(&self.state);
+        Ok(Subscription::new(move || {
+            if let Some(state) = state.upgrade() {
+                let mut state = state.borrow_mut();
+                state
+                    .pending
+                    .retain(|event| !Rc::ptr_eq(&event.handler, &handler));
+            }
+        }))
+    }
+}
+
+impl FormaHost for Replay {
+    fn call(&self, call: HostCall) -> HostFuture<HostValue> {
+        let result = self.call_with(ExchangeKind::Call, call);
+        Box::pin(async move { result })
+    }
+
+    fn call_sync(&self, call: HostCall) -> Result<HostValue> {
+        self.call_with(ExchangeKind::CallSync, call)
+    }
+
+    fn subscribe(&self, subscribe: HostSubscribe) -> HostFuture<Subscription> {
+        let result = self.replay_subscribe(ExchangeKind::Subscribe, subscribe);
+        Box::pin(async move { result })
+    }
+
+    fn subscribe_sync(&self, subscribe: HostSubscribe) -> Result<Subscription> {
+        self.replay_subscribe(ExchangeKind::SubscribeSync, subscribe)
+    }
+
+    fn subscribe_with_reply(&self, subscribe: HostReplySubscribe) -> Result<Subscription> {
+        let exchange = self.take(
+            ExchangeKind::SubscribeReply,
+            subscribe.api,
+            subscribe.event,
+            &subscribe.args,
+        )?;
+        match exchange.error {
+            Some(error) => Err(error),
+            None => Ok(Subscription::new(|| {})),
+        }
+    }
+}
+
+/// Copy of a value that can be written to a fixture. Opaque handles (canvases,
+/// message ports) are recorded as `Undefined`.
+fn recordable(value: &HostValue) -> HostValue {
+    match value {
+        HostValue::Handle(_) => HostValue::Undefined,
+        HostValue::Object(members) => HostValue::Object(
+            members
+                .iter()
+                .map(|(key, member)| (key.clone(), recordable(member)))
+                .collect::<BTreeMap<_, _>>(),
+        ),
+        other => other.clone(),
+    }
+}
+
+fn now_ms() -> f64 {
+    crate::clock::current().now_ms()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{block_on, FakeForma};
+    use crate::EmbeddedViewSdk;
+
+    type Log = Rc<RefCell<Vec<String>>>;
+
+    /// Log selection changes along with the project id, read from the
+    /// handler.
+    fn watch_selection(host: &(impl FormaHost + Clone + 'static), log: &Log) -> Subscription {
+        let selection = EmbeddedViewSdk::with_host(host.clone()).selection();
+        let sdk = EmbeddedViewSdk::with_host(host.clone());
+        let log = log.clone();
+        let subscribe = selection.subscribe(move |paths| {
+            let project = sdk.get_project_id().unwrap();
+            log.borrow_mut().push(format!("{project}: {paths:?}"));
+        });
+        block_on(subscribe).unwrap()
+    }
+
+    fn get_selection(host: &(impl FormaHost + Clone + 'static), log: &Log) {
+        let sdk = EmbeddedViewSdk::with_host(host.clone());
+        let paths = block_on(sdk.selection().get_selection()).unwrap();
+        log.borrow_mut().push(format!("got {paths:?}"));
+    }
+
+    /// Selection of `root/a`, a call, then a selection of `root/b`.
+    fn record(log: &Log) -> String {
+        let fake = FakeForma::new();
+        fake.set_project_id("pro_1");
+        let recorder = Recorder::new(fake.clone());
+        let _subscription = watch_selection(&recorder, log);
+        fake.set_selection(vec!["root/a".into()]);
+        get_selection(&recorder, log);
+        fake.set_selection(vec!["root/b".into()]);
+        recorder.recording().to_json().unwrap()
+    }
+
+    #[test]
+    fn replays_events_in_recorded_order() {
+        let recorded = Log::default();
+        let replay = Replay::from_json(&record(&recorded)).unwrap();
+
+        let replayed = Log::default();
+        let _subscription = watch_selection(&replay, &replayed);
+        assert!(replayed.borrow().is_empty());
+        get_selection(&replay, &replayed);
+        assert_eq!(replayed.borrow().len(), 2);
+        assert_eq!(replay.flush_events(), 1);
+
+        assert_eq!(*replayed.borrow(), *recorded.borrow());
+        assert_eq!(replay.remaining(), 0);
+    }
+
+    #[test]
+    fn unsubscribing_drops_queued_events() {
+        let replay = Replay::from_json(&record(&Log::default())).unwrap();
+        let replayed = Log::default();
+        watch_selection(&replay, &replayed).unsubscribe();
+        get_selection(&replay, &replayed);
+
+        assert_eq!(replay.flush_events(), 0);
+        assert_eq!(*replayed.borrow(), ["got [\"root/a\"]"]);
+    }
+
+    #[test]
+    fn unrecorded_call_fails() {
+        let replay = Replay::new(Recording::default());
+        let sdk = EmbeddedViewSdk::with_host(replay);
+        assert!(matches!(sdk.get_project_id(), Err(SdkError::Host(_))));
+    }
+}
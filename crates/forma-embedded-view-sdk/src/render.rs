@@ -1,49 +1,40 @@
+use crate::client::Client;
+use crate::host::HostValue;
+use crate::options::call_options;
 use crate::types::*;
 use crate::Result;
-use wasm_bindgen::JsValue;
-use wasm_bindgen_futures::JsFuture;
-
-fn mesh_request_to_js(request: &MeshRequest) -> std::result::Result<JsValue, JsValue> {
-    let obj = ::js_sys::Object::new();
-    ::js_sys::Reflect::set(&obj, &"id".into(), &request.id.clone().into())?;
-
-    let geom = ::js_sys::Object::new();
-    let position = ::js_sys::Float32Array::new_with_length(request.geometry_data.position.len() as u32);
-    position.copy_from(&request.geometry_data.position);
-    ::js_sys::Reflect::set(&geom, &"position".into(), &position)?;
+use std::collections::BTreeMap;
 
+fn mesh_request_to_host(request: &MeshRequest) -> Result<HostValue> {
+    let mut geom = BTreeMap::from([(
+        "position".to_string(),
+        HostValue::Floats(request.geometry_data.position.clone()),
+    )]);
     if let Some(ref color) = request.geometry_data.color {
-        let color_arr = ::js_sys::Uint8Array::new_with_length(color.len() as u32);
-        color_arr.copy_from(color);
-        ::js_sys::Reflect::set(&geom, &"color".into(), &color_arr)?;
+        geom.insert("color".to_string(), HostValue::Bytes(color.clone()));
     }
 
-    ::js_sys::Reflect::set(&obj, &"geometryData".into(), &geom)?;
-
+    let mut obj = BTreeMap::from([
+        ("id".to_string(), HostValue::json(&request.id)?),
+        ("geometryData".to_string(), HostValue::Object(geom)),
+    ]);
     if let Some(ref transform) = request.transform {
-        let t = serde_wasm_bindgen::to_value(transform)
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
-        ::js_sys::Reflect::set(&obj, &"transform".into(), &t)?;
+        obj.insert("transform".to_string(), HostValue::json(transform)?);
     }
 
-    Ok(obj.into())
+    Ok(HostValue::Object(obj))
 }
 
-fn glb_request_to_js(request: &GlbRenderRequest) -> std::result::Result<JsValue, JsValue> {
-    let obj = ::js_sys::Object::new();
-    ::js_sys::Reflect::set(&obj, &"id".into(), &request.id.clone().into())?;
-
-    let buf = ::js_sys::Uint8Array::new_with_length(request.glb.len() as u32);
-    buf.copy_from(&request.glb);
-    ::js_sys::Reflect::set(&obj, &"glb".into(), &buf.buffer())?;
-
+fn glb_request_to_host(request: &GlbRenderRequest) -> Result<HostValue> {
+    let mut obj = BTreeMap::from([
+        ("id".to_string(), HostValue::json(&request.id)?),
+        ("glb".to_string(), HostValue::Buffer(request.glb.clone())),
+    ]);
     if let Some(ref transform) = request.transform {
-        let t = serde_wasm_bindgen::to_value(transform)
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
-        ::js_sys::Reflect::set(&obj, &"transform".into(), &t)?;
+        obj.insert("transform".to_string(), HostValue::json(transform)?);
     }
 
-    Ok(obj.into())
+    Ok(HostValue::Object(obj))
 }
 
 pub(crate) mod js {
@@ -93,6 +84,24 @@ pub(crate) mod js {
         pub fn cleanup(this: &RenderApi) -> ::js_sys::Promise;
     }
 
+    impl RenderApi {
+        pub(crate) fn dispatch(&self, method: &str, args: &[JsValue]) -> Option<::js_sys::Promise> {
+            Some(match method {
+                "hideElement" => self.hide_element(&args[0]),
+                "hideElementsBatch" => self.hide_elements_batch(&args[0]),
+                "unhideElement" => self.unhide_element(&args[0]),
+                "unhideElementsBatch" => self.unhide_elements_batch(&args[0]),
+                "setElementsVisibility" => self.set_elements_visibility(&args[0]),
+                "unhideAllElements" => self.unhide_all_elements(),
+                "addMesh" => self.add_mesh(&args[0]),
+                "updateMesh" => self.update_mesh(&args[0]),
+                "remove" => self.remove(&args[0]),
+                "cleanup" => self.cleanup(),
+                _ => return None,
+            })
+        }
+    }
+
     #[wasm_bindgen]
     extern "C" {
         pub type RenderGlbApi;
@@ -110,6 +119,18 @@ pub(crate) mod js {
         pub fn cleanup(this: &RenderGlbApi) -> ::js_sys::Promise;
     }
 
+    impl RenderGlbApi {
+        pub(crate) fn dispatch(&self, method: &str, args: &[JsValue]) -> Option<::js_sys::Promise> {
+            Some(match method {
+                "add" => self.add(&args[0]),
+                "update" => self.update(&args[0]),
+                "remove" => self.remove(&args[0]),
+                "cleanup" => self.cleanup(),
+                _ => return None,
+            })
+        }
+    }
+
     #[wasm_bindgen]
     extern "C" {
         pub type RenderGeojsonApi;
@@ -127,6 +148,18 @@ pub(crate) mod js {
         pub fn cleanup(this: &RenderGeojsonApi) -> ::js_sys::Promise;
     }
 
+    impl RenderGeojsonApi {
+        pub(crate) fn dispatch(&self, method: &str, args: &[JsValue]) -> Option<::js_sys::Promise> {
+            Some(match method {
+                "add" => self.add(&args[0]),
+                "update" => self.update(&args[0]),
+                "remove" => self.remove(&args[0]),
+                "cleanup" => self.cleanup(),
+                _ => return None,
+            })
+        }
+    }
+
     #[wasm_bindgen]
     extern "C" {
         pub type ElementColorApi;
@@ -140,43 +173,57 @@ pub(crate) mod js {
         #[wasm_bindgen(method, js_name = "clearAll")]
         pub fn clear_all(this: &ElementColorApi) -> ::js_sys::Promise;
     }
+
+    impl ElementColorApi {
+        pub(crate) fn dispatch(&self, method: &str, args: &[JsValue]) -> Option<::js_sys::Promise> {
+            Some(match method {
+                "set" => self.set(&args[0]),
+                "clear" => self.clear(&args[0]),
+                "clearAll" => self.clear_all(),
+                _ => return None,
+            })
+        }
+    }
 }
 
 /// Render or color objects and elements in the 3D scene (non-persistent visual changes).
 pub struct RenderApi {
-    inner: js::RenderApi,
+    client: Client,
 }
 
+call_options!(RenderApi, RenderGlbApi, RenderGeojsonApi, ElementColorApi);
+
 impl RenderApi {
-    pub(crate) fn from_raw(raw: js::RenderApi) -> Self {
-        Self { inner: raw }
+    pub(crate) fn new(client: Client) -> Self {
+        Self { client }
     }
 
     /// Access the GLB render sub-API.
     pub fn glb(&self) -> RenderGlbApi {
         RenderGlbApi {
-            inner: self.inner.glb(),
+            client: self.client.scoped("render.glb"),
         }
     }
 
     /// Access the GeoJSON render sub-API.
     pub fn geojson(&self) -> RenderGeojsonApi {
         RenderGeojsonApi {
-            inner: self.inner.geojson(),
+            client: self.client.scoped("render.geojson"),
         }
     }
 
     /// Access the element color sub-API.
     pub fn element_colors(&self) -> ElementColorApi {
         ElementColorApi {
-            inner: self.inner.element_colors(),
+            client: self.client.scoped("render.elementColors"),
         }
     }
 
     /// Hide an element from the scene.
     pub async fn hide_element(&self, request: &ElementVisibilityRequest) -> Result<()> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        JsFuture::from(self.inner.hide_element(&js_req)).await?;
+        self.client
+            .call("hideElement", vec![HostValue::json(request)?])
+            .await?;
         Ok(())
     }
 
@@ -185,15 +232,17 @@ impl RenderApi {
         &self,
         request: &ElementVisibilityBatchRequest,
     ) -> Result<()> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        JsFuture::from(self.inner.hide_elements_batch(&js_req)).await?;
+        self.client
+            .call("hideElementsBatch", vec![HostValue::json(request)?])
+            .await?;
         Ok(())
     }
 
     /// Unhide an element from the scene.
     pub async fn unhide_element(&self, request: &ElementVisibilityRequest) -> Result<()> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        JsFuture::from(self.inner.unhide_element(&js_req)).await?;
+        self.client
+            .call("unhideElement", vec![HostValue::json(request)?])
+            .await?;
         Ok(())
     }
 
@@ -202,8 +251,9 @@ impl RenderApi {
         &self,
         request: &ElementVisibilityBatchRequest,
     ) -> Result<()> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        JsFuture::from(self.inner.unhide_elements_batch(&js_req)).await?;
+        self.client
+            .call("unhideElementsBatch", vec![HostValue::json(request)?])
+            .await?;
         Ok(())
     }
 
@@ -212,136 +262,145 @@ impl RenderApi {
         &self,
         request: &SetElementsVisibilityRequest,
     ) -> Result<()> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        JsFuture::from(self.inner.set_elements_visibility(&js_req)).await?;
+        self.client
+            .call("setElementsVisibility", vec![HostValue::json(request)?])
+            .await?;
         Ok(())
     }
 
     /// Unhide all elements previously hidden by this API.
     pub async fn unhide_all_elements(&self) -> Result<()> {
-        JsFuture::from(self.inner.unhide_all_elements()).await?;
+        self.client.call("unhideAllElements", vec![]).await?;
         Ok(())
     }
 
     /// Add a mesh to the scene.
     pub async fn add_mesh(&self, request: &MeshRequest) -> Result<IdResult> {
-        let js_req = mesh_request_to_js(request)?;
-        let result = JsFuture::from(self.inner.add_mesh(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let js_req = mesh_request_to_host(request)?;
+        let result = self.client.call("addMesh", vec![js_req]).await?;
+        self.client.response("addMesh", result)
     }
 
     /// Upsert a mesh in the scene.
     pub async fn update_mesh(&self, request: &MeshRequest) -> Result<()> {
-        let js_req = mesh_request_to_js(request)?;
-        JsFuture::from(self.inner.update_mesh(&js_req)).await?;
+        let js_req = mesh_request_to_host(request)?;
+        self.client.call("updateMesh", vec![js_req]).await?;
         Ok(())
     }
 
     /// Remove a mesh from the scene.
     pub async fn remove(&self, request: &RemoveRequest) -> Result<()> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        JsFuture::from(self.inner.remove(&js_req)).await?;
+        self.client
+            .call("remove", vec![HostValue::json(request)?])
+            .await?;
         Ok(())
     }
 
     /// Remove all meshes added by this API from the scene.
     pub async fn cleanup(&self) -> Result<()> {
-        JsFuture::from(self.inner.cleanup()).await?;
+        self.client.call("cleanup", vec![]).await?;
         Ok(())
     }
 }
 
 /// Render GLBs in the 3D scene.
 pub struct RenderGlbApi {
-    inner: js::RenderGlbApi,
+    client: Client,
 }
 
 impl RenderGlbApi {
     /// Add a GLB to the scene.
     pub async fn add(&self, request: &GlbRenderRequest) -> Result<IdResult> {
-        let js_req = glb_request_to_js(request)?;
-        let result = JsFuture::from(self.inner.add(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let js_req = glb_request_to_host(request)?;
+        let result = self.client.call("add", vec![js_req]).await?;
+        self.client.response("add", result)
     }
 
     /// Upsert a GLB in the scene.
     pub async fn update(&self, request: &GlbRenderRequest) -> Result<()> {
-        let js_req = glb_request_to_js(request)?;
-        JsFuture::from(self.inner.update(&js_req)).await?;
+        let js_req = glb_request_to_host(request)?;
+        self.client.call("update", vec![js_req]).await?;
         Ok(())
     }
 
     /// Remove a GLB from the scene.
     pub async fn remove(&self, request: &RemoveRequest) -> Result<()> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        JsFuture::from(self.inner.remove(&js_req)).await?;
+        self.client
+            .call("remove", vec![HostValue::json(request)?])
+            .await?;
         Ok(())
     }
 
     /// Remove all GLBs added by this API from the scene.
     pub async fn cleanup(&self) -> Result<()> {
-        JsFuture::from(self.inner.cleanup()).await?;
+        self.client.call("cleanup", vec![]).await?;
         Ok(())
     }
 }
 
 /// Render GeoJSON in the 3D scene.
 pub struct RenderGeojsonApi {
-    inner: js::RenderGeojsonApi,
+    client: Client,
 }
 
 impl RenderGeojsonApi {
     /// Add GeoJSON to the scene.
     pub async fn add(&self, request: &GeoJsonRenderRequest) -> Result<IdResult> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.add(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self
+            .client
+            .call("add", vec![HostValue::json(request)?])
+            .await?;
+        self.client.response("add", result)
     }
 
     /// Upsert GeoJSON in the scene.
     pub async fn update(&self, request: &GeoJsonRenderRequest) -> Result<()> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        JsFuture::from(self.inner.update(&js_req)).await?;
+        self.client
+            .call("update", vec![HostValue::json(request)?])
+            .await?;
         Ok(())
     }
 
     /// Remove GeoJSON from the scene.
     pub async fn remove(&self, request: &RemoveRequest) -> Result<()> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        JsFuture::from(self.inner.remove(&js_req)).await?;
+        self.client
+            .call("remove", vec![HostValue::json(request)?])
+            .await?;
         Ok(())
     }
 
     /// Remove all GeoJSON added by this API from the scene.
     pub async fn cleanup(&self) -> Result<()> {
-        JsFuture::from(self.inner.cleanup()).await?;
+        self.client.call("cleanup", vec![]).await?;
         Ok(())
     }
 }
 
 /// Set custom colors on elements in the scene.
 pub struct ElementColorApi {
-    inner: js::ElementColorApi,
+    client: Client,
 }
 
 impl ElementColorApi {
     /// Set color override on the specified elements.
     pub async fn set(&self, request: &ElementColorSetRequest) -> Result<()> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        JsFuture::from(self.inner.set(&js_req)).await?;
+        self.client
+            .call("set", vec![HostValue::json(request)?])
+            .await?;
         Ok(())
     }
 
     /// Clear color override on the specified elements.
     pub async fn clear(&self, request: &ElementColorClearRequest) -> Result<()> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        JsFuture::from(self.inner.clear(&js_req)).await?;
+        self.client
+            .call("clear", vec![HostValue::json(request)?])
+            .await?;
         Ok(())
     }
 
     /// Clear all color overrides.
     pub async fn clear_all(&self) -> Result<()> {
-        JsFuture::from(self.inner.clear_all()).await?;
+        self.client.call("clearAll", vec![]).await?;
         Ok(())
     }
 }
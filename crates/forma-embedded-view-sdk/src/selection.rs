@@ -1,7 +1,9 @@
-use crate::subscription::Subscription;
+use crate::client::Client;
+use crate::options::call_options;
+use crate::stream::{Debounce, DistinctUntilChanged, EventStreamExt};
+use crate::subscription::{EventStream, Subscription};
 use crate::Result;
-use wasm_bindgen::prelude::*;
-use wasm_bindgen_futures::JsFuture;
+use std::time::Duration;
 
 pub(crate) mod js {
     use wasm_bindgen::prelude::*;
@@ -19,37 +21,94 @@ pub(crate) mod js {
             callback: &Closure<dyn FnMut(JsValue)>,
         ) -> ::js_sys::Promise;
     }
+
+    impl SelectionApi {
+        pub(crate) fn dispatch(
+            &self,
+            method: &str,
+            _args: &[JsValue],
+        ) -> Option<::js_sys::Promise> {
+            Some(match method {
+                "getSelection" => self.get_selection(),
+                _ => return None,
+            })
+        }
+
+        pub(crate) fn listen(
+            &self,
+            event: &str,
+            handler: &Closure<dyn FnMut(JsValue)>,
+        ) -> Option<::js_sys::Promise> {
+            match event {
+                "subscribe" => Some(self.subscribe(handler)),
+                _ => None,
+            }
+        }
+    }
 }
 
 /// Interact with user's selection (shift-clicked elements in the scene).
 pub struct SelectionApi {
-    inner: js::SelectionApi,
+    client: Client,
 }
 
+call_options!(SelectionApi);
+
 impl SelectionApi {
-    pub(crate) fn from_raw(raw: js::SelectionApi) -> Self {
-        Self { inner: raw }
+    pub(crate) fn new(client: Client) -> Self {
+        Self { client }
     }
 
     /// Get selected element paths.
     pub async fn get_selection(&self) -> Result<Vec<String>> {
-        let result = JsFuture::from(self.inner.get_selection()).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self.client.call("getSelection", vec![]).await?;
+        self.client.response("getSelection", result)
     }
 
     /// Subscribe to selection changes. Callback receives the list of selected paths.
     pub async fn subscribe(
         &self,
-        mut callback: impl FnMut(Vec<String>) + 'static,
+        callback: impl FnMut(Vec<String>) + 'static,
     ) -> Result<Subscription> {
-        let closure = Closure::wrap(Box::new(move |val: JsValue| {
-            if let Ok(paths) = serde_wasm_bindgen::from_value(val) {
-                callback(paths);
-            }
-        }) as Box<dyn FnMut(JsValue)>);
-        let result = JsFuture::from(self.inner.subscribe(&closure)).await?;
-        let unsubscribe_fn: ::js_sys::Function =
-            ::js_sys::Reflect::get(&result, &"unsubscribe".into())?.into();
-        Ok(Subscription::new(closure, unsubscribe_fn))
+        self.client
+            .subscribe_to("subscribe", vec![], callback)
+            .await
+    }
+
+    /// Selection changes as a stream of selected paths. Unsubscribes when dropped.
+    pub async fn changes(&self) -> Result<EventStream<Vec<String>>> {
+        let (sender, stream) = EventStream::channel();
+        let subscription = self.subscribe(move |paths| sender.send(paths)).await?;
+        Ok(stream.attach(subscription))
+    }
+
+    /// Selection changes with bursts coalesced: a selection is yielded once
+    /// no other change has followed it for `quiet`, and only if it differs
+    /// from the previously yielded one.
+    pub async fn coalesced_changes(
+        &self,
+        quiet: Duration,
+    ) -> Result<DistinctUntilChanged<Debounce<EventStream<Vec<String>>>>> {
+        Ok(self
+            .changes()
+            .await?
+            .debounce(quiet)
+            .distinct_until_changed())
+    }
+
+    /// Subscribe to selection changes with bursts coalesced as in
+    /// [`coalesced_changes`](Self::coalesced_changes). Runs on a task spawned
+    /// with [`spawn_local`](crate::spawn_local), so it is only available on
+    /// wasm; elsewhere, drive [`stream::for_each`](crate::stream::for_each)
+    /// over `coalesced_changes` yourself.
+    #[cfg(target_arch = "wasm32")]
+    pub async fn subscribe_coalesced(
+        &self,
+        quiet: Duration,
+        callback: impl FnMut(Vec<String>) + 'static,
+    ) -> Result<Subscription> {
+        let (changes, subscription) = self.changes().await?.detach();
+        crate::stream::drive(changes.debounce(quiet).distinct_until_changed(), callback);
+        Ok(subscription)
     }
 }
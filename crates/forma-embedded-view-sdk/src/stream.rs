@@ -0,0 +1,218 @@
+//! Rate-limiting operators for event streams.
+//!
+//! [`EventStreamExt`] adds [`throttle`](EventStreamExt::throttle),
+//! [`debounce`](EventStreamExt::debounce) and
+//! [`distinct_until_changed`](EventStreamExt::distinct_until_changed) to any
+//! [`Stream`], typically an [`EventStream`](crate::EventStream) returned by
+//! `camera().states()` or `selection().changes()`:
+//!
+//! ```no_run
+//! use forma_embedded_view_sdk::forma;
+//! use forma_embedded_view_sdk::stream::EventStreamExt;
+//! use std::time::Duration;
+//!
+//! # async fn run() -> forma_embedded_view_sdk::Result<()> {
+//! let states = forma()
+//!     .camera()
+//!     .states()
+//!     .await?
+//!     .throttle(Duration::from_millis(100));
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Timers come from the clock installed when the operator is created (see
+//! [`clock::install`](crate::clock::install)), so tests can drive them with
+//! [`ManualClock`](crate::testing::ManualClock).
+
+use crate::clock::{self, Clock, Sleep};
+use futures_core::Stream;
+use std::future::poll_fn;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// Rate-limiting operators for streams of events.
+pub trait EventStreamExt: Stream + Sized {
+    /// Yield the first event right away, then at most one event per `period`:
+    /// the latest one received during the period, at its end.
+    fn throttle(self, period: Duration) -> Throttle<Self> {
+        Throttle {
+            stream: self,
+            period,
+            clock: clock::current(),
+            window: None,
+            pending: None,
+            done: false,
+        }
+    }
+
+    /// Yield an event only once no other event has followed it for `quiet`.
+    fn debounce(self, quiet: Duration) -> Debounce<Self> {
+        Debounce {
+            stream: self,
+            quiet,
+            clock: clock::current(),
+            timer: None,
+            pending: None,
+            done: false,
+        }
+    }
+
+    /// Skip events equal to the previously yielded one.
+    fn distinct_until_changed(self) -> DistinctUntilChanged<Self>
+    where
+        Self::Item: PartialEq + Clone,
+    {
+        DistinctUntilChanged {
+            stream: self,
+            last: None,
+        }
+    }
+}
+
+impl<S: Stream> EventStreamExt for S {}
+
+/// Stream returned by [`EventStreamExt::throttle`].
+pub struct Throttle<S: Stream> {
+    stream: S,
+    period: Duration,
+    clock: Rc<dyn Clock>,
+    window: Option<Sleep>,
+    pending: Option<S::Item>,
+    done: bool,
+}
+
+// Items are never pinned.
+impl<S: Stream + Unpin> Unpin for Throttle<S> {}
+
+impl<S: Stream + Unpin> Stream for Throttle<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        let this = self.get_mut();
+        while !this.done {
+            match Pin::new(&mut this.stream).poll_next(cx) {
+                Poll::Ready(Some(item)) if this.window.is_none() => {
+                    this.window = Some(this.clock.sleep(this.period));
+                    return Poll::Ready(Some(item));
+                }
+                Poll::Ready(Some(item)) => this.pending = Some(item),
+                Poll::Ready(None) => this.done = true,
+                Poll::Pending => break,
+            }
+        }
+        if let Some(window) = this.window.as_mut() {
+            if window.as_mut().poll(cx).is_ready() {
+                this.window = None;
+                if let Some(item) = this.pending.take() {
+                    this.window = Some(this.clock.sleep(this.period));
+                    return Poll::Ready(Some(item));
+                }
+            }
+        }
+        if this.done {
+            return Poll::Ready(this.pending.take());
+        }
+        Poll::Pending
+    }
+}
+
+/// Stream returned by [`EventStreamExt::debounce`].
+pub struct Debounce<S: Stream> {
+    stream: S,
+    quiet: Duration,
+    clock: Rc<dyn Clock>,
+    timer: Option<Sleep>,
+    pending: Option<S::Item>,
+    done: bool,
+}
+
+// Items are never pinned.
+impl<S: Stream + Unpin> Unpin for Debounce<S> {}
+
+impl<S: Stream + Unpin> Stream for Debounce<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        let this = self.get_mut();
+        while !this.done {
+            match Pin::new(&mut this.stream).poll_next(cx) {
+                Poll::Ready(Some(item)) => {
+                    this.pending = Some(item);
+                    this.timer = Some(this.clock.sleep(this.quiet));
+                }
+                Poll::Ready(None) => this.done = true,
+                Poll::Pending => break,
+            }
+        }
+        if this.done {
+            return Poll::Ready(this.pending.take());
+        }
+        if let Some(timer) = this.timer.as_mut() {
+            if timer.as_mut().poll(cx).is_ready() {
+                this.timer = None;
+                if let Some(item) = this.pending.take() {
+                    return Poll::Ready(Some(item));
+                }
+            }
+        }
+        Poll::Pending
+    }
+}
+
+/// Stream returned by [`EventStreamExt::distinct_until_changed`].
+pub struct DistinctUntilChanged<S: Stream> {
+    stream: S,
+    last: Option<S::Item>,
+}
+
+// Items are never pinned.
+impl<S: Stream + Unpin> Unpin for DistinctUntilChanged<S> {}
+
+impl<S> Stream for DistinctUntilChanged<S>
+where
+    S: Stream + Unpin,
+    S::Item: PartialEq + Clone,
+{
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        let this = self.get_mut();
+        loop {
+            match Pin::new(&mut this.stream).poll_next(cx) {
+                Poll::Ready(Some(item)) if this.last.as_ref() == Some(&item) => continue,
+                Poll::Ready(Some(item)) => {
+                    this.last = Some(item.clone());
+                    return Poll::Ready(Some(item));
+                }
+                other => return other,
+            }
+        }
+    }
+}
+
+/// Call `callback` for every item of `stream`, completing when the stream
+/// ends.
+///
+/// The returned future does nothing until polled, so it can be driven by any
+/// executor: `spawn_local` in the browser, or a test's own `block_on` loop
+/// under [`FakeForma`](crate::testing::FakeForma).
+pub async fn for_each<S>(mut stream: S, mut callback: impl FnMut(S::Item))
+where
+    S: Stream + Unpin,
+{
+    while let Some(item) = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+        callback(item);
+    }
+}
+
+/// Run [`for_each`] on a task spawned with [`spawn_local`](crate::spawn_local).
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn drive<S>(stream: S, callback: impl FnMut(S::Item) + 'static)
+where
+    S: Stream + Unpin + 'static,
+{
+    crate::spawn_local(for_each(stream, callback));
+}
@@ -1,4 +1,10 @@
-use wasm_bindgen::prelude::*;
+use crate::SdkError;
+use futures_core::Stream;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
 
 pub(crate) mod js {
     use wasm_bindgen::prelude::*;
@@ -14,21 +20,66 @@ pub(crate) mod js {
 }
 
 /// A handle to an active subscription. Unsubscribes automatically on drop.
+///
+/// Events that cannot be decoded into the callback's type are not passed to
+/// the callback. They are counted in [`dropped_events`](Self::dropped_events)
+/// and reported to the hook set with [`on_error`](Self::on_error).
 pub struct Subscription {
-    _closure: Closure<dyn FnMut(JsValue)>,
-    unsubscribe_fn: js_sys::Function,
+    unsubscribe_fn: RefCell<Option<Box<dyn FnOnce()>>>,
+    errors: Rc<EventErrors>,
+}
+
+type ErrorHook = Box<dyn FnMut(SdkError)>;
+
+/// Decoding failures of a subscription's events, shared with its callback.
+#[derive(Default)]
+pub(crate) struct EventErrors {
+    dropped: Cell<u64>,
+    hook: RefCell<Option<ErrorHook>>,
+}
+
+impl EventErrors {
+    pub(crate) fn report(&self, err: SdkError) {
+        self.dropped.set(self.dropped.get() + 1);
+        // Take the hook out while it runs so that it may replace itself.
+        let hook = self.hook.borrow_mut().take();
+        if let Some(mut hook) = hook {
+            hook(err);
+            self.hook.borrow_mut().get_or_insert(hook);
+        }
+    }
 }
 
 impl Subscription {
-    pub(crate) fn new(closure: Closure<dyn FnMut(JsValue)>, unsubscribe_fn: js_sys::Function) -> Self {
+    /// Create a subscription handle that runs `unsubscribe` once, either when
+    /// [`unsubscribe`](Self::unsubscribe) is called or when dropped.
+    pub fn new(unsubscribe: impl FnOnce() + 'static) -> Self {
         Self {
-            _closure: closure,
-            unsubscribe_fn,
+            unsubscribe_fn: RefCell::new(Some(Box::new(unsubscribe))),
+            errors: Rc::default(),
         }
     }
 
+    pub(crate) fn with_errors(mut self, errors: Rc<EventErrors>) -> Self {
+        self.errors = errors;
+        self
+    }
+
+    /// Number of events dropped so far because they could not be decoded.
+    pub fn dropped_events(&self) -> u64 {
+        self.errors.dropped.get()
+    }
+
+    /// Call `hook` with the error for every event dropped from now on.
+    pub fn on_error(&self, hook: impl FnMut(SdkError) + 'static) {
+        *self.errors.hook.borrow_mut() = Some(Box::new(hook));
+    }
+
     pub fn unsubscribe(&self) {
-        let _ = self.unsubscribe_fn.call0(&JsValue::NULL);
+        let unsubscribe_fn = self.unsubscribe_fn.borrow_mut().take();
+        if let Some(unsubscribe_fn) = unsubscribe_fn {
+            unsubscribe_fn();
+        }
     }
 }
 
@@ -38,27 +89,166 @@ impl Drop for Subscription {
     }
 }
 
+impl std::fmt::Debug for Subscription {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Subscription")
+            .field("active", &self.unsubscribe_fn.borrow().is_some())
+            .field("dropped_events", &self.dropped_events())
+            .finish()
+    }
+}
+
 /// A handle to an active subscription using a no-arg callback.
-pub struct VoidSubscription {
-    _closure: Closure<dyn FnMut()>,
-    unsubscribe_fn: js_sys::Function,
+pub type VoidSubscription = Subscription;
+
+struct EventQueue<T> {
+    events: VecDeque<T>,
+    waker: Option<Waker>,
+    closed: bool,
 }
 
-impl VoidSubscription {
-    pub(crate) fn new(closure: Closure<dyn FnMut()>, unsubscribe_fn: js_sys::Function) -> Self {
-        Self {
-            _closure: closure,
-            unsubscribe_fn,
-        }
+/// End the stream reading from `queue` once its buffered events are taken.
+fn close<T>(queue: &RefCell<EventQueue<T>>) {
+    let waker = {
+        let mut queue = queue.borrow_mut();
+        queue.closed = true;
+        queue.waker.take()
+    };
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
+
+/// Events of a subscription as a [`Stream`]. Unsubscribes when dropped.
+///
+/// Events are buffered until polled. The stream ends if the host drops the
+/// subscription.
+pub struct EventStream<T> {
+    queue: Rc<RefCell<EventQueue<T>>>,
+    subscription: Option<Subscription>,
+}
+
+/// Sending half of an [`EventStream`], owned by the subscription callback.
+pub(crate) struct EventSender<T> {
+    queue: Rc<RefCell<EventQueue<T>>>,
+}
+
+impl<T> EventStream<T> {
+    pub(crate) fn channel() -> (EventSender<T>, Self) {
+        let queue = Rc::new(RefCell::new(EventQueue {
+            events: VecDeque::new(),
+            waker: None,
+            closed: false,
+        }));
+        let sender = EventSender {
+            queue: queue.clone(),
+        };
+        let stream = Self {
+            queue,
+            subscription: None,
+        };
+        (sender, stream)
     }
 
+    /// Keep `subscription` alive for as long as the stream.
+    pub(crate) fn attach(mut self, subscription: Subscription) -> Self {
+        self.subscription = Some(subscription);
+        self
+    }
+
+    /// Split off the subscription, for streams consumed by a spawned task.
+    /// Unsubscribing through the returned handle, or dropping it, ends the
+    /// stream.
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) fn detach(mut self) -> (Self, Subscription)
+    where
+        T: 'static,
+    {
+        let inner = self.subscription.take();
+        let errors = inner
+            .as_ref()
+            .map(|subscription| subscription.errors.clone())
+            .unwrap_or_default();
+        let queue = self.queue.clone();
+        let subscription = Subscription::new(move || {
+            drop(inner);
+            close(&queue);
+        });
+        (self, subscription.with_errors(errors))
+    }
+
+    /// Unsubscribe now. Events already received can still be polled.
     pub fn unsubscribe(&self) {
-        let _ = self.unsubscribe_fn.call0(&JsValue::NULL);
+        if let Some(subscription) = &self.subscription {
+            subscription.unsubscribe();
+        }
+        close(&self.queue);
+    }
+
+    /// Number of events dropped so far because they could not be decoded.
+    pub fn dropped_events(&self) -> u64 {
+        self.subscription
+            .as_ref()
+            .map_or(0, Subscription::dropped_events)
+    }
+
+    /// Call `hook` with the error for every event dropped from now on.
+    pub fn on_error(&self, hook: impl FnMut(SdkError) + 'static) {
+        if let Some(subscription) = &self.subscription {
+            subscription.on_error(hook);
+        }
+    }
+}
+
+impl<T> Stream for EventStream<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let mut queue = self.queue.borrow_mut();
+        if let Some(event) = queue.events.pop_front() {
+            return Poll::Ready(Some(event));
+        }
+        if queue.closed {
+            return Poll::Ready(None);
+        }
+        queue.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.queue.borrow().events.len(), None)
     }
 }
 
-impl Drop for VoidSubscription {
+impl<T> std::fmt::Debug for EventStream<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let queue = self.queue.borrow();
+        f.debug_struct("EventStream")
+            .field("buffered", &queue.events.len())
+            .field("closed", &queue.closed)
+            .field("dropped_events", &self.dropped_events())
+            .finish()
+    }
+}
+
+impl<T> EventSender<T> {
+    pub(crate) fn send(&self, event: T) {
+        let waker = {
+            let mut queue = self.queue.borrow_mut();
+            if queue.closed {
+                return;
+            }
+            queue.events.push_back(event);
+            queue.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Drop for EventSender<T> {
     fn drop(&mut self) {
-        self.unsubscribe();
+        close(&self.queue);
     }
 }
@@ -1,7 +1,9 @@
+use crate::client::Client;
+use crate::host::HostValue;
+use crate::options::call_options;
 use crate::types::*;
 use crate::Result;
-use wasm_bindgen::JsValue;
-use wasm_bindgen_futures::JsFuture;
+use std::collections::BTreeMap;
 
 pub(crate) mod js {
     use wasm_bindgen::prelude::*;
@@ -16,31 +18,45 @@ pub(crate) mod js {
         #[wasm_bindgen(method, js_name = "setDate")]
         pub fn set_date(this: &SunApi, request: &JsValue) -> ::js_sys::Promise;
     }
+
+    impl SunApi {
+        pub(crate) fn dispatch(&self, method: &str, args: &[JsValue]) -> Option<::js_sys::Promise> {
+            Some(match method {
+                "getDate" => self.get_date(),
+                "setDate" => self.set_date(&args[0]),
+                _ => return None,
+            })
+        }
+    }
 }
 
 /// Interact with the sun object in the 3D scene.
 pub struct SunApi {
-    inner: js::SunApi,
+    client: Client,
 }
 
+call_options!(SunApi);
+
 impl SunApi {
-    pub(crate) fn from_raw(raw: js::SunApi) -> Self {
-        Self { inner: raw }
+    pub(crate) fn new(client: Client) -> Self {
+        Self { client }
     }
 
     /// Fetch the ISO date string corresponding to the current sun position.
     pub async fn get_date(&self) -> Result<String> {
-        let result = JsFuture::from(self.inner.get_date()).await?;
-        let date: &::js_sys::Date = wasm_bindgen::JsCast::unchecked_ref(&result);
-        Ok(date.to_iso_string().into())
+        match self.client.call("getDate", vec![]).await? {
+            HostValue::Date(date) => Ok(date),
+            other => self.client.response("getDate", other),
+        }
     }
 
     /// Set the position of the sun in the scene by date (ISO 8601 string).
     pub async fn set_date(&self, request: &SunDateRequest) -> Result<()> {
-        let date = ::js_sys::Date::new(&JsValue::from_str(&request.date));
-        let obj = ::js_sys::Object::new();
-        ::js_sys::Reflect::set(&obj, &"date".into(), &date)?;
-        JsFuture::from(self.inner.set_date(&obj)).await?;
+        let obj = HostValue::Object(BTreeMap::from([(
+            "date".to_string(),
+            HostValue::Date(request.date.clone()),
+        )]));
+        self.client.call("setDate", vec![obj]).await?;
         Ok(())
     }
 }
@@ -1,6 +1,10 @@
+use crate::client::{self, Client, LocalFuture};
+use crate::host::HostValue;
+use crate::options::call_options;
+use crate::polygon;
 use crate::types::*;
-use crate::Result;
-use wasm_bindgen_futures::JsFuture;
+use crate::{ErrorDetails, Result, SdkError};
+use std::collections::BTreeMap;
 
 pub(crate) mod js {
     use wasm_bindgen::prelude::*;
@@ -28,6 +32,19 @@ pub(crate) mod js {
         pub fn apply_pads(this: &TerrainApi, pads: &JsValue) -> ::js_sys::Promise;
     }
 
+    impl TerrainApi {
+        pub(crate) fn dispatch(&self, method: &str, args: &[JsValue]) -> Option<::js_sys::Promise> {
+            Some(match method {
+                "getBbox" => self.get_bbox(),
+                "getElevationAt" => self.get_elevation_at(&args[0]),
+                "getPads" => self.get_pads(),
+                "addPads" => self.add_pads(&args[0]),
+                "applyPads" => self.apply_pads(&args[0]),
+                _ => return None,
+            })
+        }
+    }
+
     #[wasm_bindgen]
     extern "C" {
         pub type GroundTextureApi;
@@ -47,79 +64,299 @@ pub(crate) mod js {
         #[wasm_bindgen(method)]
         pub fn remove(this: &GroundTextureApi, request: &JsValue) -> ::js_sys::Promise;
     }
+
+    impl GroundTextureApi {
+        pub(crate) fn dispatch(&self, method: &str, args: &[JsValue]) -> Option<::js_sys::Promise> {
+            Some(match method {
+                "add" => self.add(&args[0]),
+                "updateTextureData" => self.update_texture_data(&args[0]),
+                "updatePosition" => self.update_position(&args[0]),
+                "remove" => self.remove(&args[0]),
+                _ => return None,
+            })
+        }
+    }
 }
 
 /// Interact with the terrain in the 3D scene.
 pub struct TerrainApi {
-    inner: js::TerrainApi,
+    client: Client,
 }
 
+call_options!(TerrainApi, GroundTextureApi);
+
 impl TerrainApi {
-    pub(crate) fn from_raw(raw: js::TerrainApi) -> Self {
-        Self { inner: raw }
+    pub(crate) fn new(client: Client) -> Self {
+        Self { client }
     }
 
     /// Access the ground texture sub-API.
     pub fn ground_texture(&self) -> GroundTextureApi {
         GroundTextureApi {
-            inner: self.inner.ground_texture(),
+            client: self.client.scoped("terrain.groundTexture"),
         }
     }
 
     /// Fetch the bounding box for the terrain.
     pub async fn get_bbox(&self) -> Result<TerrainBbox> {
-        let result = JsFuture::from(self.inner.get_bbox()).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self.client.call("getBbox", vec![]).await?;
+        self.client.response("getBbox", result)
     }
 
     /// Get the elevation at a specific (x, y) point. Returns meters above sea level.
     pub async fn get_elevation_at(&self, request: &ElevationRequest) -> Result<f64> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.get_elevation_at(&js_req)).await?;
-        Ok(result.as_f64().unwrap_or(0.0))
+        let result = self
+            .client
+            .call("getElevationAt", vec![HostValue::json(request)?])
+            .await?;
+        self.client.decode("getElevationAt", "a number", result)
     }
 
     /// Retrieve all terrain pads defined in the current terrain.
     pub async fn get_pads(&self) -> Result<Vec<TerrainPad>> {
-        let result = JsFuture::from(self.inner.get_pads()).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+        let result = self.client.call("getPads", vec![]).await?;
+        self.client.response("getPads", result)
     }
 
-    /// Add new terrain pads to the existing pads.
+    /// Add new terrain pads to the existing pads. Fails with
+    /// [`SdkError::InvalidRequest`] without calling the host if a pad does
+    /// not [`validate`](TerrainPad::validate).
     pub async fn add_pads(&self, pads: &[TerrainPad]) -> Result<()> {
-        let js_pads = serde_wasm_bindgen::to_value(pads)?;
-        JsFuture::from(self.inner.add_pads(&js_pads)).await?;
+        validate_pads(pads).map_err(|err| self.client.in_method("addPads", err))?;
+        self.client
+            .call("addPads", vec![HostValue::json(pads)?])
+            .await?;
         Ok(())
     }
 
-    /// Replace all existing terrain pads with the provided array.
+    /// Replace all existing terrain pads with the provided array. Pads are
+    /// validated as in [`add_pads`](Self::add_pads).
     pub async fn apply_pads(&self, pads: &[TerrainPad]) -> Result<()> {
-        let js_pads = serde_wasm_bindgen::to_value(pads)?;
-        JsFuture::from(self.inner.apply_pads(&js_pads)).await?;
+        validate_pads(pads).map_err(|err| self.client.in_method("applyPads", err))?;
+        self.client
+            .call("applyPads", vec![HostValue::json(pads)?])
+            .await?;
+        Ok(())
+    }
+
+    /// Estimate the earthwork needed to grade the terrain to `pad`, by
+    /// sampling the terrain elevation at the centre of every `spacing` x
+    /// `spacing` metre cell inside the pad. Embankments are not included.
+    ///
+    /// Each sample is one host call, with up to [`CUT_FILL_CONCURRENCY`] in
+    /// flight. Fails with [`SdkError::InvalidRequest`] if the pad's bounding
+    /// box holds more than [`MAX_CUT_FILL_SAMPLES`] cells, so keep `spacing`
+    /// coarse for large pads:
+    ///
+    /// ```no_run
+    /// use forma_embedded_view_sdk::forma;
+    /// use forma_embedded_view_sdk::types::TerrainPad;
+    ///
+    /// # async fn run() -> forma_embedded_view_sdk::Result<()> {
+    /// let pad = TerrainPad::new(vec![[0.0, 0.0], [40.0, 0.0], [40.0, 25.0], [0.0, 25.0]], 12.0);
+    /// let terrain = forma().terrain();
+    /// let estimate = terrain.estimate_cut_fill(&pad, 2.0).await?;
+    /// if estimate.net() >= 0.0 {
+    ///     terrain.add_pads(&[pad]).await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn estimate_cut_fill(&self, pad: &TerrainPad, spacing: f64) -> Result<CutFill> {
+        pad.validate()?;
+        if !(spacing.is_finite() && spacing > 0.0) {
+            return Err(SdkError::InvalidRequest(ErrorDetails::new(format!(
+                "sample spacing must be positive, got {spacing}"
+            ))));
+        }
+        let ([x0, y0], [x1, y1]) = polygon::bounds(&pad.polygon).unwrap_or_default();
+        let columns = ((x1 - x0) / spacing).ceil();
+        let rows = ((y1 - y0) / spacing).ceil();
+        if columns * rows > MAX_CUT_FILL_SAMPLES as f64 {
+            return Err(SdkError::InvalidRequest(ErrorDetails::new(format!(
+                "{columns}x{rows} samples at {spacing} m spacing exceed the maximum of \
+                 {MAX_CUT_FILL_SAMPLES}; use a coarser spacing"
+            ))));
+        }
+        let (columns, rows) = (columns as usize, rows as usize);
+        let mut points: Vec<Vec2> = (0..rows)
+            .flat_map(|row| (0..columns).map(move |col| (col, row)))
+            .map(|(col, row)| {
+                [
+                    x0 + (col as f64 + 0.5) * spacing,
+                    y0 + (row as f64 + 0.5) * spacing,
+                ]
+            })
+            .filter(|point| polygon::contains(&pad.polygon, *point))
+            .collect();
+        let mut cell_area = spacing * spacing;
+        if points.is_empty() {
+            // Pad narrower than a cell: one sample for the whole pad.
+            points.extend(polygon::interior_point(&pad.polygon));
+            cell_area = polygon::signed_area(&pad.polygon).abs();
+        }
+        let mut estimate = CutFill::default();
+        let mut points = points.into_iter();
+        let mut running: Vec<LocalFuture<'_, Result<f64>>> = Vec::new();
+        loop {
+            while running.len() < CUT_FILL_CONCURRENCY {
+                let Some([x, y]) = points.next() else {
+                    break;
+                };
+                running.push(Box::pin(async move {
+                    self.get_elevation_at(&ElevationRequest { x, y }).await
+                }));
+            }
+            if running.is_empty() {
+                break;
+            }
+            let ground = client::first_ready(&mut running).await?;
+            let depth = ground - pad.elevation;
+            if depth > 0.0 {
+                estimate.cut += depth * cell_area;
+            } else {
+                estimate.fill -= depth * cell_area;
+            }
+            estimate.area += cell_area;
+            estimate.samples += 1;
+        }
+        Ok(estimate)
+    }
+}
+
+impl TerrainPad {
+    /// A pad at `elevation` over `polygon`, which is closed and made
+    /// counter-clockwise if needed.
+    pub fn new(polygon: Vec<Vec2>, elevation: f64) -> Self {
+        Self {
+            id: None,
+            polygon: polygon::normalize(polygon),
+            elevation,
+            slope_angle: None,
+            embankment_width: None,
+            extra: serde_json::Map::new(),
+        }
+    }
+
+    pub fn with_id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Set the embankment angle from horizontal, in degrees.
+    pub fn with_slope_angle(mut self, degrees: f64) -> Self {
+        self.slope_angle = Some(degrees);
+        self
+    }
+
+    /// Set the maximum horizontal width of the embankments, in metres.
+    pub fn with_embankment_width(mut self, width: f64) -> Self {
+        self.embankment_width = Some(width);
+        self
+    }
+
+    /// Check that the polygon is a closed, counter-clockwise ring that does
+    /// not cross itself (see [`polygon::validate`]), the elevation is finite,
+    /// the slope angle is strictly between 0 and 90 degrees and the
+    /// embankment width is not negative.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |message: String| SdkError::InvalidRequest(ErrorDetails::new(message));
+        polygon::validate(&self.polygon).map_err(|err| invalid(format!("pad polygon: {err}")))?;
+        if !self.elevation.is_finite() {
+            return Err(invalid(format!("pad elevation is {}", self.elevation)));
+        }
+        if let Some(angle) = self.slope_angle {
+            if !(angle > 0.0 && angle < 90.0) {
+                return Err(invalid(format!(
+                    "pad slope angle must be between 0 and 90 degrees, got {angle}"
+                )));
+            }
+        }
+        if let Some(width) = self.embankment_width {
+            if !(width >= 0.0 && width.is_finite()) {
+                return Err(invalid(format!(
+                    "pad embankment width must not be negative, got {width}"
+                )));
+            }
+        }
         Ok(())
     }
 }
 
+impl CutFill {
+    /// Cut minus fill, in cubic metres: positive when excavated material is
+    /// left over, negative when material must be brought in.
+    pub fn net(&self) -> f64 {
+        self.cut - self.fill
+    }
+}
+
+/// Most grid cells [`TerrainApi::estimate_cut_fill`] samples for one pad.
+pub const MAX_CUT_FILL_SAMPLES: usize = 10_000;
+
+/// Elevation requests [`TerrainApi::estimate_cut_fill`] keeps in flight.
+pub const CUT_FILL_CONCURRENCY: usize = 8;
+
+/// Validate every pad, naming the first invalid one by index.
+fn validate_pads(pads: &[TerrainPad]) -> Result<()> {
+    for (index, pad) in pads.iter().enumerate() {
+        pad.validate()
+            .map_err(|err| err.at(&format!("pads[{index}]")))?;
+    }
+    Ok(())
+}
+
 /// Manage ground textures applied to the terrain in the 3D scene.
 pub struct GroundTextureApi {
-    inner: js::GroundTextureApi,
+    client: Client,
 }
 
 impl GroundTextureApi {
-    /// Add a ground texture to the terrain.
-    pub async fn add(&self, request: &GroundTextureAddRequest) -> Result<serde_json::Value> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        let result = JsFuture::from(self.inner.add(&js_req)).await?;
-        Ok(serde_wasm_bindgen::from_value(result)?)
+    /// Drape `image` on the terrain at `placement`.
+    ///
+    /// ```no_run
+    /// use forma_embedded_view_sdk::forma;
+    /// use forma_embedded_view_sdk::types::*;
+    ///
+    /// # async fn run() -> forma_embedded_view_sdk::Result<()> {
+    /// let mut image = RgbaImage::filled(64, 64, [0, 0, 255, 96])?;
+    /// image.set_pixel(32, 32, [255, 0, 0, 255]);
+    /// forma()
+    ///     .terrain()
+    ///     .ground_texture()
+    ///     .add(&GroundTextureAddRequest {
+    ///         id: "heatmap".into(),
+    ///         image,
+    ///         placement: GroundTexturePlacement {
+    ///             center: [0.0, 0.0],
+    ///             size: [128.0, 128.0],
+    ///             rotation: 0.0,
+    ///         },
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn add(&self, request: &GroundTextureAddRequest) -> Result<()> {
+        let mut members = placement_members(&request.placement)?;
+        members.insert("id".to_string(), HostValue::json(&request.id)?);
+        members.insert("canvas".to_string(), request.image.to_host());
+        self.client
+            .call("add", vec![HostValue::Object(members)])
+            .await?;
+        Ok(())
     }
 
-    /// Update the texture data for an existing ground texture.
+    /// Replace the image of an existing ground texture.
     pub async fn update_texture_data(
         &self,
         request: &GroundTextureUpdateDataRequest,
     ) -> Result<()> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        JsFuture::from(self.inner.update_texture_data(&js_req)).await?;
+        let js_req = HostValue::Object(BTreeMap::from([
+            ("id".to_string(), HostValue::json(&request.id)?),
+            ("canvas".to_string(), request.image.to_host()),
+        ]));
+        self.client.call("updateTextureData", vec![js_req]).await?;
         Ok(())
     }
 
@@ -128,15 +365,69 @@ impl GroundTextureApi {
         &self,
         request: &GroundTextureUpdatePositionRequest,
     ) -> Result<()> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        JsFuture::from(self.inner.update_position(&js_req)).await?;
+        self.client
+            .call("updatePosition", vec![HostValue::json(request)?])
+            .await?;
         Ok(())
     }
 
     /// Remove an existing ground texture.
     pub async fn remove(&self, request: &GroundTextureRemoveRequest) -> Result<()> {
-        let js_req = serde_wasm_bindgen::to_value(request)?;
-        JsFuture::from(self.inner.remove(&js_req)).await?;
+        self.client
+            .call("remove", vec![HostValue::json(request)?])
+            .await?;
         Ok(())
     }
 }
+
+/// Placement fields of a ground texture request.
+fn placement_members(placement: &GroundTexturePlacement) -> Result<BTreeMap<String, HostValue>> {
+    match serde_json::to_value(placement)? {
+        serde_json::Value::Object(members) => Ok(members
+            .into_iter()
+            .map(|(key, value)| (key, HostValue::Json(value)))
+            .collect()),
+        _ => unreachable!("placement serializes to an object"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{block_on, FakeForma};
+
+    #[test]
+    fn pad_narrower_than_a_cell_is_sampled_inside() {
+        // A U whose walls are thinner than the sample spacing.
+        let pad = TerrainPad::new(
+            vec![
+                [0.0, 0.0],
+                [10.0, 0.0],
+                [10.0, 10.0],
+                [9.8, 10.0],
+                [9.8, 0.2],
+                [0.2, 0.2],
+                [0.2, 10.0],
+                [0.0, 10.0],
+            ],
+            0.0,
+        );
+        let fake = FakeForma::new();
+        let polygon = pad.polygon.clone();
+        // Ground 2 m above the pad under it, 50 m elsewhere.
+        fake.set_elevation(move |x, y| {
+            if polygon::contains(&polygon, [x, y]) {
+                2.0
+            } else {
+                50.0
+            }
+        });
+        let estimate = block_on(fake.sdk().terrain().estimate_cut_fill(&pad, 20.0)).unwrap();
+
+        let area = polygon::signed_area(&pad.polygon);
+        assert_eq!(estimate.samples, 1);
+        assert_eq!(estimate.area, area);
+        assert_eq!(estimate.cut, 2.0 * area);
+        assert_eq!(estimate.fill, 0.0);
+    }
+}
@@ -0,0 +1,1435 @@
+//! In-memory Forma scene for running extension code natively.
+//!
+//! [`FakeForma`] implements [`FormaHost`] on top of a small stateful model of
+//! a Forma project: an element tree addressable by path and URN, the current
+//! proposal, the selection, the camera, the render registry, floor stack
+//! buildings, Integrate elements and uploads, the terrain (pads and ground
+//! textures) and extension storage. [`request_close`](FakeForma::request_close)
+//! simulates the user closing the embedded view.
+//! Seed it, run extension code against [`sdk()`](FakeForma::sdk) or
+//! [`install()`](FakeForma::install), then assert on the resulting scene.
+//!
+//! ```
+//! use forma_embedded_view_sdk::testing::{FakeElement, FakeForma};
+//!
+//! let fake = FakeForma::new();
+//! let path = fake.add_element(FakeElement::new("urn:building").category("building"));
+//! fake.set_selection(vec![path.clone()]);
+//!
+//! let _guard = fake.install();
+//! // ... run code that calls `forma()` ...
+//! assert_eq!(fake.selection(), vec![path]);
+//! ```
+//!
+//! Calls the fake does not model return [`SdkError::Unsupported`].
+
+use crate::clock::{self, Clock, InstalledClock, Sleep};
+use crate::error::ErrorDetails;
+use crate::host::{
+    self, unsupported, FormaHost, HostCall, HostFuture, HostHandler, HostReplyHandler,
+    HostReplySubscribe, HostSubscribe, HostValue, InstalledHost,
+};
+use crate::subscription::Subscription;
+use crate::types::*;
+use crate::{EmbeddedViewSdk, Result, SdkError};
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::{Rc, Weak};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+const IDENTITY: Transform = [
+    1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+];
+
+/// An element to seed into a [`FakeForma`] scene.
+#[derive(Debug, Clone)]
+pub struct FakeElement {
+    urn: String,
+    properties: Value,
+    transform: Option<Transform>,
+    triangles: Vec<f32>,
+    children: Vec<FakeElement>,
+}
+
+impl FakeElement {
+    pub fn new(urn: impl Into<String>) -> Self {
+        Self {
+            urn: urn.into(),
+            properties: json!({}),
+            transform: None,
+            triangles: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    /// Set the `category` property used by `geometry.getPathsByCategory`.
+    pub fn category(self, category: &str) -> Self {
+        self.property("category", category)
+    }
+
+    /// Set a single element property.
+    pub fn property(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.properties[key] = value.into();
+        self
+    }
+
+    /// Transform of this element relative to its parent.
+    pub fn transform(mut self, transform: Transform) -> Self {
+        self.transform = Some(transform);
+        self
+    }
+
+    /// Triangle vertex positions returned by `geometry.getTriangles`.
+    pub fn triangles(mut self, triangles: Vec<f32>) -> Self {
+        self.triangles = triangles;
+        self
+    }
+
+    pub fn child(mut self, child: FakeElement) -> Self {
+        self.children.push(child);
+        self
+    }
+}
+
+/// A texture draped through `terrain.groundTexture.add`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FakeGroundTexture {
+    pub image: RgbaImage,
+    pub placement: GroundTexturePlacement,
+}
+
+/// A mesh registered through `render.addMesh` / `render.updateMesh`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FakeMesh {
+    pub position: Vec<f32>,
+    pub color: Option<Vec<u8>>,
+    pub transform: Option<Transform>,
+}
+
+struct Subscriber {
+    id: u64,
+    api: &'static str,
+    event: &'static str,
+    handler: Rc<RefCell<HostHandler>>,
+}
+
+struct ClosingHandler {
+    id: u64,
+    handler: Rc<RefCell<HostReplyHandler>>,
+}
+
+struct Scene {
+    project_id: String,
+    extension_id: String,
+    region: String,
+    embedded_view_id: String,
+    origin: String,
+    can_edit: bool,
+    proposal_id: String,
+    root_urn: String,
+    /// Element JSON by URN, in the shape returned by `elements.get`.
+    elements: HashMap<String, Value>,
+    triangles: HashMap<String, Vec<f32>>,
+    next_key: u64,
+    selection: Vec<String>,
+    camera: CameraState,
+    meshes: BTreeMap<String, FakeMesh>,
+    glbs: BTreeMap<String, Vec<u8>>,
+    geojson: BTreeMap<String, FeatureCollection>,
+    element_colors: BTreeMap<String, String>,
+    hidden: BTreeSet<String>,
+    terrain_bbox: TerrainBbox,
+    elevation: Rc<dyn Fn(f64, f64) -> f64>,
+    pads: Vec<TerrainPad>,
+    ground_textures: BTreeMap<String, FakeGroundTexture>,
+    floor_stacks: BTreeMap<String, CreateFromFloorsRequest>,
+    integrate_elements: BTreeMap<String, ElementV2>,
+    uploads: BTreeMap<String, Vec<u8>>,
+    next_urn: u64,
+    storage: BTreeMap<String, String>,
+    subscribers: Vec<Subscriber>,
+    closing_handlers: Vec<ClosingHandler>,
+    next_subscriber: u64,
+    calls: Vec<String>,
+}
+
+/// Stateful in-memory [`FormaHost`]. Clones share the same scene.
+#[derive(Clone)]
+pub struct FakeForma {
+    scene: Rc<RefCell<Scene>>,
+}
+
+impl Default for FakeForma {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FakeForma {
+    /// An empty proposal with flat terrain at elevation 0.
+    pub fn new() -> Self {
+        let root_urn = "urn:adsk-forma-elements:proposal:fake-project:root:1".to_string();
+        let elements = HashMap::from([(
+            root_urn.clone(),
+            json!({ "urn": root_urn, "properties": {}, "children": [] }),
+        )]);
+        let scene = Scene {
+            project_id: "fake-project".into(),
+            extension_id: "fake-extension".into(),
+            region: "EMEA".into(),
+            embedded_view_id: "fake-embedded-view".into(),
+            origin: "https://app.autodeskforma.eu".into(),
+            can_edit: true,
+            proposal_id: "fake-proposal".into(),
+            root_urn,
+            elements,
+            triangles: HashMap::new(),
+            next_key: 0,
+            selection: Vec::new(),
+            camera: CameraState {
+                position: [0.0, -100.0, 100.0],
+                target: [0.0, 0.0, 0.0],
+                camera_type: "perspective".into(),
+            },
+            meshes: BTreeMap::new(),
+            glbs: BTreeMap::new(),
+            geojson: BTreeMap::new(),
+            element_colors: BTreeMap::new(),
+            hidden: BTreeSet::new(),
+            terrain_bbox: TerrainBbox {
+                min: [-500.0, -500.0, 0.0],
+                max: [500.0, 500.0, 0.0],
+            },
+            elevation: Rc::new(|_, _| 0.0),
+            pads: Vec::new(),
+            ground_textures: BTreeMap::new(),
+            floor_stacks: BTreeMap::new(),
+            integrate_elements: BTreeMap::new(),
+            uploads: BTreeMap::new(),
+            next_urn: 0,
+            storage: BTreeMap::new(),
+            subscribers: Vec::new(),
+            closing_handlers: Vec::new(),
+            next_subscriber: 0,
+            calls: Vec::new(),
+        };
+        Self {
+            scene: Rc::new(RefCell::new(scene)),
+        }
+    }
+
+    /// SDK handle dispatching to this scene.
+    pub fn sdk(&self) -> EmbeddedViewSdk {
+        EmbeddedViewSdk::with_host(self.clone())
+    }
+
+    /// Make [`forma()`](crate::forma) return handles to this scene on the
+    /// current thread until the guard is dropped.
+    pub fn install(&self) -> InstalledHost {
+        host::install(self.clone())
+    }
+
+    // --- Seeding ---
+
+    pub fn set_project_id(&self, project_id: &str) {
+        self.scene.borrow_mut().project_id = project_id.into();
+    }
+
+    pub fn set_can_edit(&self, can_edit: bool) {
+        self.scene.borrow_mut().can_edit = can_edit;
+    }
+
+    /// Add an element (and its children) under the proposal root.
+    /// Returns the path of the new element.
+    pub fn add_element(&self, element: FakeElement) -> String {
+        let mut scene = self.scene.borrow_mut();
+        let root = scene.root_urn.clone();
+        let transform = element.transform;
+        let urn = scene.insert_element(element);
+        scene.attach("root", &root, &urn, transform)
+    }
+
+    /// Replace the selection and notify selection subscribers.
+    pub fn set_selection(&self, paths: Vec<String>) {
+        self.scene.borrow_mut().selection = paths.clone();
+        self.emit("selection", "subscribe", json!(paths));
+    }
+
+    /// Replace the camera state and notify camera subscribers.
+    pub fn set_camera(&self, camera: CameraState) {
+        self.scene.borrow_mut().camera = camera;
+        self.emit_camera();
+    }
+
+    /// Notify `onEmbeddedViewStateChange` subscribers.
+    pub fn set_embedded_view_state(&self, state: EmbeddedViewState) {
+        let state = serde_json::to_value(state).expect("embedded view state is JSON");
+        self.emit("sdk", "onEmbeddedViewStateChange", state);
+    }
+
+    /// Notify `onLocaleUpdate` subscribers.
+    pub fn set_locale(&self, update: LocaleUpdate) {
+        let update = serde_json::to_value(update).expect("locale update is JSON");
+        self.emit("sdk", "onLocaleUpdate", update);
+    }
+
+    /// Simulate the user closing the embedded view. Runs the
+    /// `onEmbeddedViewClosing` handlers in registration order and resolves to
+    /// whether the view may close: `false` as soon as a handler vetoes or
+    /// fails.
+    pub async fn request_close(&self) -> bool {
+        let handlers: Vec<_> = self
+            .scene
+            .borrow()
+            .closing_handlers
+            .iter()
+            .map(|h| h.handler.clone())
+            .collect();
+        for handler in handlers {
+            let reply = (handler.borrow_mut())(HostValue::Undefined);
+            let allow = reply.await.and_then(|value| value.deserialize::<bool>());
+            if !matches!(allow, Ok(true)) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Elevation returned by `terrain.getElevationAt` for an `(x, y)` point.
+    pub fn set_elevation(&self, elevation: impl Fn(f64, f64) -> f64 + 'static) {
+        self.scene.borrow_mut().elevation = Rc::new(elevation);
+    }
+
+    pub fn set_terrain_bbox(&self, bbox: TerrainBbox) {
+        self.scene.borrow_mut().terrain_bbox = bbox;
+    }
+
+    pub fn set_storage_object(&self, key: &str, data: &str) {
+        self.scene
+            .borrow_mut()
+            .storage
+            .insert(key.into(), data.into());
+    }
+
+    // --- Assertions ---
+
+    pub fn root_urn(&self) -> String {
+        self.scene.borrow().root_urn.clone()
+    }
+
+    /// Element JSON by URN.
+    pub fn element(&self, urn: &str) -> Option<Value> {
+        self.scene.borrow().elements.get(urn).cloned()
+    }
+
+    /// Element JSON at a path such as `"root/e1"`.
+    pub fn element_at(&self, path: &str) -> Option<Value> {
+        let scene = self.scene.borrow();
+        let (urn, _) = scene.resolve(path).ok()?;
+        scene.elements.get(&urn).cloned()
+    }
+
+    /// Paths of all elements below the proposal root, depth first.
+    pub fn paths(&self) -> Vec<String> {
+        let scene = self.scene.borrow();
+        let mut paths = Vec::new();
+        scene.walk("root", &scene.root_urn, &mut |path, _| {
+            if path != "root" {
+                paths.push(path.to_string());
+            }
+        });
+        paths
+    }
+
+    pub fn selection(&self) -> Vec<String> {
+        self.scene.borrow().selection.clone()
+    }
+
+    pub fn camera(&self) -> CameraState {
+        self.scene.borrow().camera.clone()
+    }
+
+    pub fn meshes(&self) -> BTreeMap<String, FakeMesh> {
+        self.scene.borrow().meshes.clone()
+    }
+
+    pub fn mesh(&self, id: &str) -> Option<FakeMesh> {
+        self.scene.borrow().meshes.get(id).cloned()
+    }
+
+    pub fn glb_ids(&self) -> Vec<String> {
+        self.scene.borrow().glbs.keys().cloned().collect()
+    }
+
+    pub fn geojson_ids(&self) -> Vec<String> {
+        self.scene.borrow().geojson.keys().cloned().collect()
+    }
+
+    /// Color overrides set through `render.elementColors`, by path.
+    pub fn element_colors(&self) -> BTreeMap<String, String> {
+        self.scene.borrow().element_colors.clone()
+    }
+
+    pub fn hidden_paths(&self) -> Vec<String> {
+        self.scene.borrow().hidden.iter().cloned().collect()
+    }
+
+    pub fn pads(&self) -> Vec<TerrainPad> {
+        self.scene.borrow().pads.clone()
+    }
+
+    /// Ground textures by id.
+    pub fn ground_textures(&self) -> BTreeMap<String, FakeGroundTexture> {
+        self.scene.borrow().ground_textures.clone()
+    }
+
+    /// Requests of the buildings created through `elements.floorStack`, by
+    /// URN.
+    pub fn floor_stacks(&self) -> BTreeMap<String, CreateFromFloorsRequest> {
+        self.scene.borrow().floor_stacks.clone()
+    }
+
+    /// Elements created or updated through `integrateElements`, by URN.
+    pub fn integrate_elements(&self) -> BTreeMap<String, ElementV2> {
+        self.scene.borrow().integrate_elements.clone()
+    }
+
+    /// Files uploaded through `integrateElements.uploadFile`, by blob id.
+    pub fn uploads(&self) -> BTreeMap<String, Vec<u8>> {
+        self.scene.borrow().uploads.clone()
+    }
+
+    pub fn storage_object(&self, key: &str) -> Option<String> {
+        self.scene.borrow().storage.get(key).cloned()
+    }
+
+    /// Every call made so far, as `"api.method"`.
+    pub fn calls(&self) -> Vec<String> {
+        self.scene.borrow().calls.clone()
+    }
+
+    // --- Dispatch ---
+
+    fn handle(&self, api: &str, method: &str, args: Vec<HostValue>) -> Result<HostValue> {
+        self.scene
+            .borrow_mut()
+            .calls
+            .push(format!("{api}.{method}"));
+        let mut args = args.into_iter();
+        let mut arg = || args.next().unwrap_or(HostValue::Undefined);
+        match (api, method) {
+            ("sdk", "getCanEdit") | ("sdk", "getCanViewHub") | ("sdk", "getCanEditHub") => {
+                Ok(json!(self.scene.borrow().can_edit).into())
+            }
+            ("sdk", "getPresentationUnitSystem") => Ok("metric".into()),
+            ("sdk", "ping") => Ok(HostValue::Undefined),
+
+            ("proposal", "getRootUrn") => Ok(self.root_urn().as_str().into()),
+            ("proposal", "getId") => Ok(self.scene.borrow().proposal_id.as_str().into()),
+            ("proposal", "addElement") => {
+                let path = self.proposal_add(arg().deserialize()?)?;
+                self.emit_proposal(ProposalDiff {
+                    added: vec![path.clone()],
+                    ..ProposalDiff::default()
+                });
+                HostValue::json(&PathResult { path })
+            }
+            ("proposal", "replaceElement") => {
+                let request: ProposalReplaceElementRequest = arg().deserialize()?;
+                let path = request.path.clone();
+                self.proposal_replace(request)?;
+                self.emit_proposal(ProposalDiff {
+                    replaced: vec![path],
+                    ..ProposalDiff::default()
+                });
+                Ok(HostValue::Undefined)
+            }
+            ("proposal", "removeElement") => {
+                let request: ProposalRemoveElementRequest = arg().deserialize()?;
+                let path = request.path.clone();
+                self.proposal_remove(request)?;
+                self.emit_proposal(ProposalDiff {
+                    removed: vec![path],
+                    ..ProposalDiff::default()
+                });
+                Ok(HostValue::Undefined)
+            }
+            ("proposal", "updateElements") => {
+                let request: ProposalUpdateElementsRequest = arg().deserialize()?;
+                let mut diff = ProposalDiff::default();
+                for remove in request.remove.unwrap_or_default() {
+                    diff.removed.push(remove.path.clone());
+                    self.proposal_remove(remove)?;
+                }
+                for replace in request.replace.unwrap_or_default() {
+                    diff.replaced.push(replace.path.clone());
+                    self.proposal_replace(replace)?;
+                }
+                for add in request.add.unwrap_or_default() {
+                    diff.added.push(self.proposal_add(add)?);
+                }
+                self.emit_proposal(diff);
+                Ok(HostValue::Undefined)
+            }
+            ("proposal", "awaitProposalPersisted") => Ok(HostValue::Undefined),
+
+            ("elements", "get") => {
+                let request: GetElementRequest = arg().deserialize()?;
+                self.scene
+                    .borrow()
+                    .element_response(&request.urn)
+                    .map(Into::into)
+            }
+            ("elements", "getByPath") => {
+                let request: GetElementByPathRequest = arg().deserialize()?;
+                let scene = self.scene.borrow();
+                let (urn, _) = scene.resolve(&request.path)?;
+                scene.element_response(&urn).map(Into::into)
+            }
+            ("elements", "getWorldTransform") => {
+                let request: GetWorldTransformRequest = arg().deserialize()?;
+                let (_, transform) = self.scene.borrow().resolve(&request.path)?;
+                HostValue::json(&transform)
+            }
+            ("elements", "editProperties") => {
+                let request: EditPropertiesRequest = arg().deserialize()?;
+                let mut scene = self.scene.borrow_mut();
+                let (urn, _) = scene.resolve(&request.path)?;
+                let element = scene.elements.get_mut(&urn).expect("resolved element");
+                merge_patch(&mut element["properties"], request.properties);
+                Ok(HostValue::Undefined)
+            }
+
+            ("geometry", "getPathsByCategory") => {
+                let request: GetPathsByCategoryRequest = arg().deserialize()?;
+                let category = Value::String(request.category);
+                Ok(json!(self.paths_where(|element| element["properties"]["category"] == category)).into())
+            }
+            ("geometry", "getPathsForVirtualElements") => Ok(json!(
+                self.paths_where(|element| element["properties"]["virtual"] == json!(true))
+            )
+            .into()),
+            ("geometry", "getTriangles") => {
+                let request = arg();
+                let path = if request.is_nullish() {
+                    "root".to_string()
+                } else {
+                    request.deserialize::<GetTrianglesRequest>()?.path
+                };
+                let scene = self.scene.borrow();
+                let (urn, _) = scene.resolve(&path)?;
+                let mut triangles = Vec::new();
+                scene.walk(&path, &urn, &mut |_, urn| {
+                    triangles.extend(scene.triangles.get(urn).into_iter().flatten())
+                });
+                Ok(HostValue::Floats(triangles))
+            }
+
+            ("selection", "getSelection") => Ok(json!(self.selection()).into()),
+
+            ("camera", "getCurrent") => HostValue::json(&self.camera()),
+            ("camera", "move") => {
+                let request: CameraMoveRequest = arg().deserialize()?;
+                {
+                    let mut scene = self.scene.borrow_mut();
+                    if let Some(position) = request.position {
+                        scene.camera.position = position;
+                    }
+                    if let Some(target) = request.target {
+                        scene.camera.target = target;
+                    }
+                }
+                self.emit_camera();
+                Ok(HostValue::Undefined)
+            }
+            ("camera", "switchPerspective") => {
+                {
+                    let mut scene = self.scene.borrow_mut();
+                    scene.camera.camera_type = match scene.camera.camera_type.as_str() {
+                        "perspective" => "orthographic".into(),
+                        _ => "perspective".into(),
+                    };
+                }
+                self.emit_camera();
+                Ok(HostValue::Undefined)
+            }
+
+            ("render", "addMesh") | ("render", "updateMesh") => {
+                let mut request = arg();
+                let id: String = request.take("id").deserialize()?;
+                let mut geometry = request.take("geometryData");
+                let color = geometry.take("color");
+                let mesh = FakeMesh {
+                    position: geometry.take("position").into_floats()?,
+                    color: (!color.is_nullish())
+                        .then(|| color.into_bytes())
+                        .transpose()?,
+                    transform: request.take("transform").deserialize()?,
+                };
+                self.scene.borrow_mut().meshes.insert(id.clone(), mesh);
+                HostValue::json(&IdResult { id })
+            }
+            ("render", "remove") => {
+                let request: RemoveRequest = arg().deserialize()?;
+                self.scene.borrow_mut().meshes.remove(&request.id);
+                Ok(HostValue::Undefined)
+            }
+            ("render", "cleanup") => {
+                self.scene.borrow_mut().meshes.clear();
+                Ok(HostValue::Undefined)
+            }
+            ("render", "hideElement") | ("render", "unhideElement") => {
+                let request: ElementVisibilityRequest = arg().deserialize()?;
+                self.set_visibility(vec![request.path], method == "unhideElement");
+                Ok(HostValue::Undefined)
+            }
+            ("render", "hideElementsBatch") | ("render", "unhideElementsBatch") => {
+                let request: ElementVisibilityBatchRequest = arg().deserialize()?;
+                self.set_visibility(request.paths, method == "unhideElementsBatch");
+                Ok(HostValue::Undefined)
+            }
+            ("render", "setElementsVisibility") => {
+                let request: SetElementsVisibilityRequest = arg().deserialize()?;
+                self.set_visibility(request.paths, request.visible);
+                Ok(HostValue::Undefined)
+            }
+            ("render", "unhideAllElements") => {
+                self.scene.borrow_mut().hidden.clear();
+                Ok(HostValue::Undefined)
+            }
+
+            ("render.glb", "add") | ("render.glb", "update") => {
+                let mut request = arg();
+                let id: String = request.take("id").deserialize()?;
+                let glb = request.take("glb").into_bytes()?;
+                self.scene.borrow_mut().glbs.insert(id.clone(), glb);
+                HostValue::json(&IdResult { id })
+            }
+            ("render.glb", "remove") => {
+                let request: RemoveRequest = arg().deserialize()?;
+                self.scene.borrow_mut().glbs.remove(&request.id);
+                Ok(HostValue::Undefined)
+            }
+            ("render.glb", "cleanup") => {
+                self.scene.borrow_mut().glbs.clear();
+                Ok(HostValue::Undefined)
+            }
+
+            ("render.geojson", "add") | ("render.geojson", "update") => {
+                let request: GeoJsonRenderRequest = arg().deserialize()?;
+                let id = request.id;
+                self.scene
+                    .borrow_mut()
+                    .geojson
+                    .insert(id.clone(), request.data);
+                HostValue::json(&IdResult { id })
+            }
+            ("render.geojson", "remove") => {
+                let request: RemoveRequest = arg().deserialize()?;
+                self.scene.borrow_mut().geojson.remove(&request.id);
+                Ok(HostValue::Undefined)
+            }
+            ("render.geojson", "cleanup") => {
+                self.scene.borrow_mut().geojson.clear();
+                Ok(HostValue::Undefined)
+            }
+
+            ("render.elementColors", "set") => {
+                let request: ElementColorSetRequest = arg().deserialize()?;
+                let mut scene = self.scene.borrow_mut();
+                for path in request.paths {
+                    scene.element_colors.insert(path, request.color.clone());
+                }
+                Ok(HostValue::Undefined)
+            }
+            ("render.elementColors", "clear") => {
+                let request: ElementColorClearRequest = arg().deserialize()?;
+                let mut scene = self.scene.borrow_mut();
+                for path in request.paths {
+                    scene.element_colors.remove(&path);
+                }
+                Ok(HostValue::Undefined)
+            }
+            ("render.elementColors", "clearAll") => {
+                self.scene.borrow_mut().element_colors.clear();
+                Ok(HostValue::Undefined)
+            }
+
+            ("elements.floorStack", "createFromFloors") => {
+                let urn = self
+                    .scene
+                    .borrow_mut()
+                    .create_floor_stack(arg().deserialize()?);
+                HostValue::json(&UrnResult { urn })
+            }
+            ("elements.floorStack", "createFromFloorsBatch") => {
+                let requests: Vec<CreateFromFloorsRequest> = arg().deserialize()?;
+                let mut scene = self.scene.borrow_mut();
+                let urns: Vec<_> = requests
+                    .into_iter()
+                    .map(|request| UrnResult {
+                        urn: scene.create_floor_stack(request),
+                    })
+                    .collect();
+                HostValue::json(&urns)
+            }
+
+            ("integrateElements", "uploadFile") => {
+                let data = arg().take("data").into_bytes()?;
+                let mut scene = self.scene.borrow_mut();
+                let index = scene.uploads.len() + 1;
+                let result = FileUploadResult {
+                    file_id: format!("file{index}"),
+                    blob_id: format!("blob{index}"),
+                };
+                scene.uploads.insert(result.blob_id.clone(), data);
+                HostValue::json(&result)
+            }
+            ("integrateElements", "createElementV2") => {
+                let request: CreateElementV2Request = arg().deserialize()?;
+                let mut scene = self.scene.borrow_mut();
+                let urn = match request.urn {
+                    Some(urn) => urn,
+                    None => scene.create_urn(&request.authcontext),
+                };
+                scene.ingest(&urn, request.element)?;
+                HostValue::json(&UrnResult { urn })
+            }
+            ("integrateElements", "updateElementV2") => {
+                let request: UpdateElementV2Request = arg().deserialize()?;
+                let mut scene = self.scene.borrow_mut();
+                let mut element = serde_json::to_value(
+                    scene
+                        .integrate_elements
+                        .get(&request.urn)
+                        .ok_or_else(|| not_found("element", &request.urn))?,
+                )?;
+                merge_patch(&mut element, serde_json::to_value(request.element)?);
+                scene.ingest(&request.urn, serde_json::from_value(element)?)?;
+                HostValue::json(&UrnResult { urn: request.urn })
+            }
+            ("integrateElements", "batchIngestElementsV2") => {
+                let request: BatchIngestElementsV2Request = arg().deserialize()?;
+                let mut scene = self.scene.borrow_mut();
+                // Batches are all or nothing.
+                let mut batch = BTreeSet::new();
+                for element in &request.elements {
+                    scene.check_ingest(&element.element, &batch)?;
+                    batch.insert(element.urn.as_str());
+                }
+                let mut urns = Vec::with_capacity(request.elements.len());
+                for element in request.elements {
+                    scene.ingest(&element.urn, element.element)?;
+                    urns.push(element.urn);
+                }
+                Ok(json!({ "urns": urns }).into())
+            }
+
+            ("terrain", "getBbox") => HostValue::json(&self.scene.borrow().terrain_bbox),
+            ("terrain", "getElevationAt") => {
+                let request: ElevationRequest = arg().deserialize()?;
+                let elevation = self.scene.borrow().elevation.clone();
+                Ok(json!(elevation(request.x, request.y)).into())
+            }
+            ("terrain", "getPads") => HostValue::json(&self.pads()),
+            ("terrain", "addPads") => {
+                let pads: Vec<TerrainPad> = arg().deserialize()?;
+                self.scene.borrow_mut().pads.extend(pads);
+                Ok(HostValue::Undefined)
+            }
+            ("terrain", "applyPads") => {
+                self.scene.borrow_mut().pads = arg().deserialize()?;
+                Ok(HostValue::Undefined)
+            }
+            ("terrain.groundTexture", "add") => {
+                let mut request = arg();
+                let id: String = request.take("id").deserialize()?;
+                let texture = FakeGroundTexture {
+                    image: image(request.take("canvas"))?,
+                    placement: GroundTexturePlacement {
+                        center: request.take("center").deserialize()?,
+                        size: request.take("size").deserialize()?,
+                        rotation: request.take("rotation").deserialize()?,
+                    },
+                };
+                self.scene.borrow_mut().ground_textures.insert(id, texture);
+                Ok(HostValue::Undefined)
+            }
+            ("terrain.groundTexture", "updateTextureData") => {
+                let mut request = arg();
+                let id: String = request.take("id").deserialize()?;
+                let image = image(request.take("canvas"))?;
+                let mut scene = self.scene.borrow_mut();
+                let texture = scene
+                    .ground_textures
+                    .get_mut(&id)
+                    .ok_or_else(|| not_found("ground texture", &id))?;
+                texture.image = image;
+                Ok(HostValue::Undefined)
+            }
+            ("terrain.groundTexture", "updatePosition") => {
+                let request: GroundTextureUpdatePositionRequest = arg().deserialize()?;
+                let mut scene = self.scene.borrow_mut();
+                let texture = scene
+                    .ground_textures
+                    .get_mut(&request.id)
+                    .ok_or_else(|| not_found("ground texture", &request.id))?;
+                texture.placement = request.placement;
+                Ok(HostValue::Undefined)
+            }
+            ("terrain.groundTexture", "remove") => {
+                let request: GroundTextureRemoveRequest = arg().deserialize()?;
+                self.scene.borrow_mut().ground_textures.remove(&request.id);
+                Ok(HostValue::Undefined)
+            }
+
+            ("extensions.storage", "setObject") => {
+                let request: StorageSetObjectRequest = arg().deserialize()?;
+                self.set_storage_object(&request.key, &request.data);
+                Ok(HostValue::Undefined)
+            }
+            ("extensions.storage", "getTextObject") => {
+                let request: StorageGetTextObjectRequest = arg().deserialize()?;
+                Ok(match self.storage_object(&request.key) {
+                    Some(data) => json!({ "data": data }).into(),
+                    None => HostValue::Undefined,
+                })
+            }
+            ("extensions.storage", "getBinaryObject") => {
+                let request: StorageGetBinaryObjectRequest = arg().deserialize()?;
+                let data = self
+                    .storage_object(&request.key)
+                    .ok_or_else(|| not_found("storage object", &request.key))?;
+                Ok(HostValue::Buffer(data.into_bytes()))
+            }
+            ("extensions.storage", "listObjects") => {
+                let request = arg();
+                let prefix = if request.is_nullish() {
+                    None
+                } else {
+                    request.deserialize::<StorageListObjectsRequest>()?.prefix
+                };
+                let scene = self.scene.borrow();
+                let results: Vec<Value> = scene
+                    .storage
+                    .iter()
+                    .filter(|(key, _)| prefix.as_deref().is_none_or(|p| key.starts_with(p)))
+                    .map(|(key, data)| json!({ "key": key, "size": data.len() }))
+                    .collect();
+                Ok(json!({ "results": results }).into())
+            }
+            ("extensions.storage", "deleteObject") => {
+                let request: StorageDeleteObjectRequest = arg().deserialize()?;
+                self.scene.borrow_mut().storage.remove(&request.key);
+                Ok(HostValue::Undefined)
+            }
+
+            _ => Err(unsupported(api, method)),
+        }
+    }
+
+    fn proposal_add(&self, request: ProposalAddElementRequest) -> Result<String> {
+        let transform = request.transform.or_else(|| {
+            request.position.map(|[x, y, z]| {
+                let mut transform = IDENTITY;
+                transform[12..15].copy_from_slice(&[x, y, z]);
+                transform
+            })
+        });
+        let mut scene = self.scene.borrow_mut();
+        scene.ensure_element(&request.urn);
+        let root = scene.root_urn.clone();
+        Ok(scene.attach("root", &root, &request.urn, transform))
+    }
+
+    fn proposal_replace(&self, request: ProposalReplaceElementRequest) -> Result<()> {
+        let mut scene = self.scene.borrow_mut();
+        scene.ensure_element(&request.urn);
+        let child = scene.child_mut(&request.path)?;
+        child["urn"] = request.urn.into();
+        Ok(())
+    }
+
+    /// Remove the element at `request.path` and deselect it and its
+    /// descendants, notifying selection subscribers if the selection changed.
+    fn proposal_remove(&self, request: ProposalRemoveElementRequest) -> Result<()> {
+        let selection = {
+            let mut scene = self.scene.borrow_mut();
+            let (parent_path, key) = split_path(&request.path)?;
+            let (parent_urn, _) = scene.resolve(parent_path)?;
+            let parent = scene
+                .elements
+                .get_mut(&parent_urn)
+                .expect("resolved element");
+            let children = parent["children"].as_array_mut().expect("children array");
+            let count = children.len();
+            children.retain(|child| child["key"] != key);
+            if children.len() == count {
+                return Err(not_found("path", &request.path));
+            }
+            let selected = scene.selection.len();
+            let descendants = format!("{}/", request.path);
+            scene
+                .selection
+                .retain(|path| path != &request.path && !path.starts_with(&descendants));
+            (scene.selection.len() != selected).then(|| scene.selection.clone())
+        };
+        if let Some(selection) = selection {
+            self.emit("selection", "subscribe", json!(selection));
+        }
+        Ok(())
+    }
+
+    fn paths_where(&self, predicate: impl Fn(&Value) -> bool) -> Vec<String> {
+        let scene = self.scene.borrow();
+        let mut paths = Vec::new();
+        scene.walk("root", &scene.root_urn, &mut |path, urn| {
+            if path != "root" && predicate(&scene.elements[urn]) {
+                paths.push(path.to_string());
+            }
+        });
+        paths
+    }
+
+    fn set_visibility(&self, paths: Vec<String>, visible: bool) {
+        let mut scene = self.scene.borrow_mut();
+        for path in paths {
+            if visible {
+                scene.hidden.remove(&path);
+            } else {
+                scene.hidden.insert(path);
+            }
+        }
+    }
+
+    fn emit_camera(&self) {
+        let camera = serde_json::to_value(self.camera()).expect("camera state is JSON");
+        self.emit("camera", "subscribe", camera);
+    }
+
+    fn emit_proposal(&self, diff: ProposalDiff) {
+        let event = ProposalChangeEvent {
+            root_urn: self.root_urn(),
+            revision: None,
+            diff,
+            extra: Default::default(),
+        };
+        let event = serde_json::to_value(event).expect("proposal change event is JSON");
+        self.emit("proposal", "subscribe", event);
+    }
+
+    /// Deliver an event to every subscriber of `api.event`. Handlers run with
+    /// the scene unborrowed so they can call back into the SDK.
+    fn emit(&self, api: &str, event: &str, payload: Value) {
+        let handlers: Vec<_> = self
+            .scene
+            .borrow()
+            .subscribers
+            .iter()
+            .filter(|s| s.api == api && s.event == event)
+            .map(|s| s.handler.clone())
+            .collect();
+        for handler in handlers {
+            (handler.borrow_mut())(HostValue::Json(payload.clone()));
+        }
+    }
+
+    fn register(&self, subscribe: HostSubscribe) -> Result<Subscription> {
+        match (subscribe.api, subscribe.event) {
+            ("selection", "subscribe")
+            | ("camera", "subscribe")
+            | ("proposal", "subscribe")
+            | ("sdk", "onEmbeddedViewStateChange")
+            | ("sdk", "onLocaleUpdate") => {}
+            (api, event) => return Err(unsupported(api, event)),
+        }
+        let mut scene = self.scene.borrow_mut();
+        let id = scene.next_subscriber;
+        scene.next_subscriber += 1;
+        scene.subscribers.push(Subscriber {
+            id,
+            api: subscribe.api,
+            event: subscribe.event,
+            handler: Rc::new(RefCell::new(subscribe.handler)),
+        });
+        let weak: Weak<RefCell<Scene>> = Rc::downgrade(&self.scene);
+        Ok(Subscription::new(move || {
+            if let Some(scene) = weak.upgrade() {
+                scene.borrow_mut().subscribers.retain(|s| s.id != id);
+            }
+        }))
+    }
+}
+
+impl FormaHost for FakeForma {
+    fn call(&self, call: HostCall) -> HostFuture<HostValue> {
+        let result = self.handle(call.api, call.method, call.args);
+        Box::pin(async move { result })
+    }
+
+    fn call_sync(&self, call: HostCall) -> Result<HostValue> {
+        let mut scene = self.scene.borrow_mut();
+        match (call.api, call.method) {
+            ("integrateElements", "createUrn") => {
+                let authcontext: String = call
+                    .args
+                    .into_iter()
+                    .next()
+                    .unwrap_or(HostValue::Undefined)
+                    .deserialize()?;
+                Ok(scene.create_urn(&authcontext).as_str().into())
+            }
+            ("sdk", "origin") => Ok(scene.origin.as_str().into()),
+            ("sdk", "getProjectId") => Ok(scene.project_id.as_str().into()),
+            ("sdk", "getExtensionId") => Ok(scene.extension_id.as_str().into()),
+            ("sdk", "getRegion") => Ok(scene.region.as_str().into()),
+            ("sdk", "getEmbeddedViewId") => Ok(scene.embedded_view_id.as_str().into()),
+            ("auth", "configure") => Ok(HostValue::Undefined),
+            (api, method) => Err(unsupported(api, method)),
+        }
+    }
+
+    fn subscribe(&self, subscribe: HostSubscribe) -> HostFuture<Subscription> {
+        let result = self.register(subscribe);
+        Box::pin(async move { result })
+    }
+
+    fn subscribe_sync(&self, subscribe: HostSubscribe) -> Result<Subscription> {
+        Err(unsupported(subscribe.api, subscribe.event))
+    }
+
+    fn subscribe_with_reply(&self, subscribe: HostReplySubscribe) -> Result<Subscription> {
+        if (subscribe.api, subscribe.event) != ("sdk", "onEmbeddedViewClosing") {
+            return Err(unsupported(subscribe.api, subscribe.event));
+        }
+        let mut scene = self.scene.borrow_mut();
+        let id = scene.next_subscriber;
+        scene.next_subscriber += 1;
+        scene.closing_handlers.push(ClosingHandler {
+            id,
+            handler: Rc::new(RefCell::new(subscribe.handler)),
+        });
+        let weak: Weak<RefCell<Scene>> = Rc::downgrade(&self.scene);
+        Ok(Subscription::new(move || {
+            if let Some(scene) = weak.upgrade() {
+                scene.borrow_mut().closing_handlers.retain(|h| h.id != id);
+            }
+        }))
+    }
+}
+
+impl Scene {
+    /// Store `element` and its descendants, returning the element's URN.
+    fn insert_element(&mut self, element: FakeElement) -> String {
+        let FakeElement {
+            urn,
+            properties,
+            triangles,
+            children,
+            ..
+        } = element;
+        self.ensure_element(&urn);
+        self.elements.get_mut(&urn).expect("element")["properties"] = properties;
+        if !triangles.is_empty() {
+            self.triangles.insert(urn.clone(), triangles);
+        }
+        for child in children {
+            let child_transform = child.transform;
+            let child_urn = self.insert_element(child);
+            self.attach("", &urn, &child_urn, child_transform);
+        }
+        urn
+    }
+
+    fn ensure_element(&mut self, urn: &str) {
+        self.elements
+            .entry(urn.to_string())
+            .or_insert_with(|| json!({ "urn": urn, "properties": {}, "children": [] }));
+    }
+
+    fn create_urn(&mut self, authcontext: &str) -> String {
+        self.next_urn += 1;
+        format!(
+            "urn:adsk-forma-elements:integrate:{authcontext}:element{}",
+            self.next_urn
+        )
+    }
+
+    /// Store an Integrate element, rejecting references to blobs that were
+    /// not uploaded or to children that do not exist yet.
+    fn ingest(&mut self, urn: &str, element: ElementV2) -> Result<()> {
+        self.check_ingest(&element, &BTreeSet::new())?;
+        self.integrate_elements.insert(urn.to_string(), element);
+        Ok(())
+    }
+
+    /// Check the references of an element to ingest, counting the URNs in
+    /// `batch` as existing.
+    fn check_ingest(&self, element: &ElementV2, batch: &BTreeSet<&str>) -> Result<()> {
+        for child in &element.children {
+            if !self.integrate_elements.contains_key(&child.urn)
+                && !self.elements.contains_key(&child.urn)
+                && !batch.contains(child.urn.as_str())
+            {
+                return Err(not_found("element", &child.urn));
+            }
+        }
+        let representations = &element.representations;
+        let linked = [
+            &representations.volume_mesh,
+            &representations.footprint,
+            &representations.gross_floor_area,
+            &representations.graph_building,
+        ];
+        for representation in linked.into_iter().flatten() {
+            if let (true, Some(blob_id)) = (representation.kind == "linked", &representation.id) {
+                if !self.uploads.contains_key(blob_id) {
+                    return Err(not_found("blob", blob_id));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Store a floor stack building as a new element and return its URN. It
+    /// is not part of the proposal until added to it.
+    fn create_floor_stack(&mut self, request: CreateFromFloorsRequest) -> String {
+        let urn = format!(
+            "urn:adsk-forma-elements:floorstack:{}:building{}:1",
+            self.project_id,
+            self.floor_stacks.len() + 1
+        );
+        self.ensure_element(&urn);
+        self.elements.get_mut(&urn).expect("element")["properties"] =
+            json!({ "category": "building" });
+        self.floor_stacks.insert(urn.clone(), request);
+        urn
+    }
+
+    /// Append a child reference to `parent_urn` and return its path.
+    fn attach(
+        &mut self,
+        parent_path: &str,
+        parent_urn: &str,
+        urn: &str,
+        transform: Option<Transform>,
+    ) -> String {
+        self.next_key += 1;
+        let key = format!("e{}", self.next_key);
+        let mut child = json!({ "key": key, "urn": urn });
+        if let Some(transform) = transform {
+            child["transform"] = json!(transform);
+        }
+        self.elements.get_mut(parent_urn).expect("parent element")["children"]
+            .as_array_mut()
+            .expect("children array")
+            .push(child);
+        format!("{parent_path}/{key}")
+    }
+
+    /// Resolve a path to its URN and world transform.
+    fn resolve(&self, path: &str) -> Result<(String, Transform)> {
+        let mut segments = path.split('/');
+        if segments.next() != Some("root") {
+            return Err(not_found("path", path));
+        }
+        let mut urn = self.root_urn.clone();
+        let mut transform = IDENTITY;
+        for key in segments {
+            let child = self.elements[&urn]["children"]
+                .as_array()
+                .and_then(|children| children.iter().find(|child| child["key"] == key))
+                .ok_or_else(|| not_found("path", path))?;
+            if let Ok(local) = serde_json::from_value::<Transform>(child["transform"].clone()) {
+                transform = multiply(&transform, &local);
+            }
+            urn = child["urn"].as_str().unwrap_or_default().to_string();
+        }
+        Ok((urn, transform))
+    }
+
+    /// The child reference stored in the parent for a non-root path.
+    fn child_mut(&mut self, path: &str) -> Result<&mut Value> {
+        let (parent_path, key) = split_path(path)?;
+        let (parent_urn, _) = self.resolve(parent_path)?;
+        self.elements
+            .get_mut(&parent_urn)
+            .expect("resolved element")["children"]
+            .as_array_mut()
+            .and_then(|children| children.iter_mut().find(|child| child["key"] == key))
+            .ok_or_else(|| not_found("path", path))
+    }
+
+    /// Visit `urn` at `path` and all its descendants, depth first.
+    fn walk(&self, path: &str, urn: &str, visit: &mut dyn FnMut(&str, &str)) {
+        visit(path, urn);
+        let Some(children) = self
+            .elements
+            .get(urn)
+            .and_then(|e| e["children"].as_array())
+        else {
+            return;
+        };
+        for child in children {
+            let key = child["key"].as_str().unwrap_or_default();
+            let child_urn = child["urn"].as_str().unwrap_or_default();
+            self.walk(&format!("{path}/{key}"), child_urn, visit);
+        }
+    }
+
+    /// `{ element, elements }` as returned by `elements.get` / `elements.getByPath`.
+    fn element_response(&self, urn: &str) -> Result<Value> {
+        let element = self
+            .elements
+            .get(urn)
+            .ok_or_else(|| not_found("element", urn))?;
+        let mut elements = serde_json::Map::new();
+        self.walk("", urn, &mut |_, urn| {
+            elements.insert(urn.to_string(), self.elements[urn].clone());
+        });
+        Ok(json!({ "element": element, "elements": elements }))
+    }
+}
+
+fn split_path(path: &str) -> Result<(&str, &str)> {
+    path.rsplit_once('/').ok_or_else(|| {
+        SdkError::Host(ErrorDetails::new(format!(
+            "cannot modify the root element ({path})"
+        )))
+    })
+}
+
+/// Decode the canvas member of a ground texture request.
+fn image(value: HostValue) -> Result<RgbaImage> {
+    match value {
+        HostValue::Image {
+            width,
+            height,
+            rgba,
+        } => RgbaImage::new(width, height, rgba),
+        other => Err(SdkError::Host(ErrorDetails::new(format!(
+            "expected a canvas, got {other:?}"
+        )))),
+    }
+}
+
+fn not_found(what: &str, id: &str) -> SdkError {
+    SdkError::NotFound(ErrorDetails::new(format!("{what} not found: {id}")))
+}
+
+/// Multiply two column-major 4x4 matrices.
+fn multiply(a: &Transform, b: &Transform) -> Transform {
+    let mut out = [0.0; 16];
+    for col in 0..4 {
+        for row in 0..4 {
+            out[col * 4 + row] = (0..4).map(|k| a[k * 4 + row] * b[col * 4 + k]).sum();
+        }
+    }
+    out
+}
+
+/// Apply an RFC 7386 JSON Merge Patch.
+fn merge_patch(target: &mut Value, patch: Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch;
+        return;
+    };
+    if !target.is_object() {
+        *target = json!({});
+    }
+    let target = target.as_object_mut().expect("object");
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(&key);
+        } else {
+            merge_patch(target.entry(key).or_insert(Value::Null), value);
+        }
+    }
+}
+
+struct Sleeper {
+    deadline_ms: f64,
+    state: Rc<RefCell<(bool, Option<Waker>)>>,
+}
+
+/// Future returned by [`ManualClock::sleep`]. Dropping it before it elapses
+/// unregisters it from the clock.
+struct ManualSleep {
+    time: Weak<RefCell<ManualTime>>,
+    state: Rc<RefCell<(bool, Option<Waker>)>>,
+}
+
+impl Future for ManualSleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.state.borrow_mut();
+        if state.0 {
+            return Poll::Ready(());
+        }
+        state.1 = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for ManualSleep {
+    fn drop(&mut self) {
+        if let Some(time) = self.time.upgrade() {
+            time.borrow_mut()
+                .sleepers
+                .retain(|sleeper| !Rc::ptr_eq(&sleeper.state, &self.state));
+        }
+    }
+}
+
+#[derive(Default)]
+struct ManualTime {
+    now_ms: f64,
+    sleepers: Vec<Sleeper>,
+}
+
+/// [`Clock`] that only moves when [`advance`](ManualClock::advance) is called,
+/// for testing timeouts and backoff deterministically. Clones share the same time.
+#[derive(Clone, Default)]
+pub struct ManualClock {
+    time: Rc<RefCell<ManualTime>>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use this clock for SDK calls on the current thread until the guard is dropped.
+    pub fn install(&self) -> InstalledClock {
+        clock::install(self.clone())
+    }
+
+    /// Move time forward and wake every sleep that has elapsed.
+    pub fn advance(&self, duration: Duration) {
+        let elapsed = {
+            let mut time = self.time.borrow_mut();
+            time.now_ms += duration.as_secs_f64() * 1000.0;
+            let now_ms = time.now_ms;
+            let (elapsed, pending) = std::mem::take(&mut time.sleepers)
+                .into_iter()
+                .partition::<Vec<_>, _>(|sleeper| sleeper.deadline_ms <= now_ms);
+            time.sleepers = pending;
+            elapsed
+        };
+        for sleeper in elapsed {
+            let waker = {
+                let mut state = sleeper.state.borrow_mut();
+                state.0 = true;
+                state.1.take()
+            };
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+
+    /// Number of sleeps that have not elapsed yet.
+    pub fn pending_sleeps(&self) -> usize {
+        self.time.borrow().sleepers.len()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now_ms(&self) -> f64 {
+        self.time.borrow().now_ms
+    }
+
+    fn sleep(&self, duration: Duration) -> Sleep {
+        let state = Rc::new(RefCell::new((duration.is_zero(), None::<Waker>)));
+        {
+            let mut time = self.time.borrow_mut();
+            let deadline_ms = time.now_ms + duration.as_secs_f64() * 1000.0;
+            if !duration.is_zero() {
+                time.sleepers.push(Sleeper {
+                    deadline_ms,
+                    state: state.clone(),
+                });
+            }
+        }
+        Box::pin(ManualSleep {
+            time: Rc::downgrade(&self.time),
+            state,
+        })
+    }
+}
+
+/// Poll `future` to completion. Fake calls resolve without waiting on
+/// anything external, so busy polling is enough.
+#[cfg(test)]
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_elements_are_reachable_by_path_and_category() {
+        let forma = FakeForma::new();
+        let building = forma.add_element(
+            FakeElement::new("urn:building")
+                .category("building")
+                .child(FakeElement::new("urn:floor").property("level", 1)),
+        );
+        let sdk = forma.sdk();
+
+        let floor = format!("{building}/e1");
+        assert_eq!(forma.paths(), [building.clone(), floor.clone()]);
+        assert_eq!(forma.element_at(&floor).unwrap()["properties"]["level"], 1);
+        let request = GetPathsByCategoryRequest {
+            category: "building".into(),
+        };
+        let paths = block_on(sdk.geometry().get_paths_by_category(&request)).unwrap();
+        assert_eq!(paths, [building]);
+        assert!(forma.element_at("root/missing").is_none());
+    }
+
+    #[test]
+    fn selection_changes_reach_subscribers() {
+        let forma = FakeForma::new();
+        let selection = forma.sdk().selection();
+        let received = Rc::new(RefCell::new(Vec::new()));
+        let sink = received.clone();
+        let _subscription =
+            block_on(selection.subscribe(move |paths| sink.borrow_mut().push(paths))).unwrap();
+
+        forma.set_selection(vec!["root/e1".into()]);
+
+        assert_eq!(*received.borrow(), [vec!["root/e1".to_string()]]);
+        assert_eq!(block_on(selection.get_selection()).unwrap(), ["root/e1"]);
+        assert_eq!(forma.calls(), ["selection.getSelection"]);
+    }
+
+    #[test]
+    fn manual_clock_wakes_sleeps_at_their_deadline() {
+        let clock = ManualClock::new();
+        let mut short = clock.sleep(Duration::from_millis(10));
+        let mut long = clock.sleep(Duration::from_millis(20));
+        let mut cx = Context::from_waker(Waker::noop());
+
+        assert_eq!(clock.pending_sleeps(), 2);
+        clock.advance(Duration::from_millis(10));
+        assert!(short.as_mut().poll(&mut cx).is_ready());
+        assert!(long.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(clock.pending_sleeps(), 1);
+        drop(long);
+        assert_eq!(clock.pending_sleeps(), 0);
+    }
+}
@@ -0,0 +1,294 @@
+//! `tracing` instrumentation for SDK calls (requires the `tracing` feature).
+//!
+//! With the feature enabled, every call made through an API handle runs in a
+//! `forma.call` span recording the `api`, `method`, `request_bytes`,
+//! `outcome` and `latency_ms`. Retries are reported at `INFO`, failures at
+//! `WARN`, subscription deliveries at `TRACE`, and subscription events that
+//! fail to decode (and are therefore dropped) at `WARN`.
+//!
+//! [`ConsoleLayer`] forwards these to the browser console, using
+//! `console.error`, `console.warn`, `console.info` and `console.debug`
+//! according to the level:
+//!
+//! ```no_run
+//! # #[cfg(feature = "tracing")]
+//! {
+//!     use forma_embedded_view_sdk::trace;
+//!     trace::init_console(trace::Level::DEBUG).ok();
+//! }
+//! ```
+//!
+//! Without the feature, the instrumentation compiles to nothing.
+
+use crate::host::HostValue;
+use crate::{Result, SdkError};
+use std::future::Future;
+use std::time::Duration;
+
+#[cfg(feature = "tracing")]
+pub use console::{init_console, ConsoleLayer};
+#[cfg(feature = "tracing")]
+pub use tracing::Level;
+
+/// Span around a single SDK call, from the first attempt to the final result.
+#[cfg(feature = "tracing")]
+pub(crate) struct CallSpan {
+    span: tracing::Span,
+    started_ms: f64,
+}
+
+#[cfg(feature = "tracing")]
+impl CallSpan {
+    pub(crate) fn start(
+        kind: &'static str,
+        api: &'static str,
+        method: &'static str,
+        args: &[HostValue],
+    ) -> Self {
+        let span = tracing::debug_span!(
+            "forma.call",
+            kind,
+            api,
+            method,
+            request_bytes = args.iter().map(size).sum::<usize>(),
+            outcome = tracing::field::Empty,
+            latency_ms = tracing::field::Empty,
+        );
+        Self {
+            span,
+            started_ms: crate::clock::current().now_ms(),
+        }
+    }
+
+    pub(crate) fn instrument<F: Future>(&self, future: F) -> impl Future<Output = F::Output> {
+        tracing::Instrument::instrument(future, self.span.clone())
+    }
+
+    pub(crate) fn finish<T>(self, result: Result<T>) -> Result<T> {
+        let latency_ms = crate::clock::current().now_ms() - self.started_ms;
+        self.span.record("latency_ms", latency_ms);
+        self.span.record("outcome", outcome(&result));
+        let _entered = self.span.enter();
+        match &result {
+            Ok(_) => tracing::debug!("ok"),
+            Err(err) => tracing::warn!(error = %err, "failed"),
+        }
+        result
+    }
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn retrying(attempt: u32, delay: Duration, err: &SdkError) {
+    tracing::info!(attempt, delay_ms = delay.as_millis() as u64, error = %err, "retrying");
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn delivered(api: &'static str, event: &'static str, value: &HostValue) {
+    tracing::trace!(api, event, bytes = size(value), "event delivered");
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn dropped(api: &'static str, event: &'static str, err: &SdkError) {
+    tracing::warn!(api, event, error = %err, "event dropped");
+}
+
+/// Approximate size of a host value on the wire, in bytes.
+#[cfg(feature = "tracing")]
+fn size(value: &HostValue) -> usize {
+    match value {
+        HostValue::Undefined | HostValue::Handle(_) => 0,
+        HostValue::Json(json) => serde_json::to_vec(json).map_or(0, |bytes| bytes.len()),
+        HostValue::Bytes(bytes) | HostValue::Buffer(bytes) => bytes.len(),
+        HostValue::Floats(floats) => floats.len() * 4,
+        HostValue::Date(date) => date.len(),
+        HostValue::Image { rgba, .. } => rgba.len(),
+        HostValue::Object(members) => members
+            .iter()
+            .map(|(key, value)| key.len() + size(value))
+            .sum(),
+    }
+}
+
+#[cfg(feature = "tracing")]
+fn outcome<T>(result: &Result<T>) -> &'static str {
+    match result {
+        Ok(_) => "ok",
+        Err(SdkError::PermissionDenied(_)) => "permissionDenied",
+        Err(SdkError::NotFound(_)) => "notFound",
+        Err(SdkError::Cancelled(_)) => "cancelled",
+        Err(SdkError::Timeout(_)) => "timeout",
+        Err(SdkError::HostUnavailable(_)) => "hostUnavailable",
+        Err(SdkError::Unsupported(_)) => "unsupported",
+        Err(SdkError::Host(_)) => "host",
+        Err(SdkError::InvalidRequest(_)) => "invalidRequest",
+        Err(SdkError::Decode(_)) => "decode",
+        Err(SdkError::Serialization(_)) => "serialization",
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct CallSpan;
+
+#[cfg(not(feature = "tracing"))]
+impl CallSpan {
+    pub(crate) fn start(
+        _kind: &'static str,
+        _api: &'static str,
+        _method: &'static str,
+        _args: &[HostValue],
+    ) -> Self {
+        CallSpan
+    }
+
+    pub(crate) fn instrument<F: Future>(&self, future: F) -> F {
+        future
+    }
+
+    pub(crate) fn finish<T>(self, result: Result<T>) -> Result<T> {
+        result
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn retrying(_attempt: u32, _delay: Duration, _err: &SdkError) {}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn delivered(_api: &'static str, _event: &'static str, _value: &HostValue) {}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn dropped(_api: &'static str, _event: &'static str, _err: &SdkError) {}
+
+#[cfg(feature = "tracing")]
+mod console {
+    use std::fmt::{self, Write};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::subscriber::SetGlobalDefaultError;
+    use tracing::{Event, Level, Metadata, Subscriber};
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+    use tracing_subscriber::registry::LookupSpan;
+    use tracing_subscriber::Layer;
+
+    /// Install a [`ConsoleLayer`] showing events at `level` and above as the
+    /// global `tracing` subscriber.
+    pub fn init_console(level: Level) -> Result<(), SetGlobalDefaultError> {
+        let subscriber = tracing_subscriber::registry().with(ConsoleLayer::new(level));
+        tracing::subscriber::set_global_default(subscriber)
+    }
+
+    /// `tracing` layer writing events to the browser console (stderr natively).
+    ///
+    /// Each line is prefixed with the enclosing spans and their fields, e.g.
+    /// `forma.call{kind="call" api="proposal" method="addElement" ... latency_ms=12.0 outcome="ok"}: ok`.
+    #[derive(Debug, Clone, Copy)]
+    pub struct ConsoleLayer {
+        level: Level,
+    }
+
+    impl ConsoleLayer {
+        /// Show events at `level` and above.
+        pub fn new(level: Level) -> Self {
+            Self { level }
+        }
+    }
+
+    impl Default for ConsoleLayer {
+        fn default() -> Self {
+            Self::new(Level::INFO)
+        }
+    }
+
+    /// Formatted fields of a span, kept in the span's extensions.
+    struct SpanFields(String);
+
+    impl<S> Layer<S> for ConsoleLayer
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+            *metadata.level() <= self.level
+        }
+
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+            let mut fields = Fields::default();
+            attrs.record(&mut fields);
+            if let Some(span) = ctx.span(id) {
+                span.extensions_mut().insert(SpanFields(fields.fields));
+            }
+        }
+
+        fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+            let Some(span) = ctx.span(id) else { return };
+            let mut extensions = span.extensions_mut();
+            if let Some(SpanFields(existing)) = extensions.get_mut::<SpanFields>() {
+                let mut fields = Fields {
+                    fields: std::mem::take(existing),
+                    ..Fields::default()
+                };
+                values.record(&mut fields);
+                *existing = fields.fields;
+            }
+        }
+
+        fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+            let mut line = String::new();
+            if let Some(scope) = ctx.event_scope(event) {
+                for span in scope.from_root() {
+                    line.push_str(span.name());
+                    if let Some(SpanFields(fields)) = span.extensions().get::<SpanFields>() {
+                        if !fields.is_empty() {
+                            let _ = write!(line, "{{{fields}}}");
+                        }
+                    }
+                    line.push_str(": ");
+                }
+            }
+            let mut fields = Fields::default();
+            event.record(&mut fields);
+            line.push_str(&fields.message);
+            if !fields.fields.is_empty() {
+                if !fields.message.is_empty() {
+                    line.push(' ');
+                }
+                line.push_str(&fields.fields);
+            }
+            write_line(event.metadata().level(), &line);
+        }
+    }
+
+    /// Collects the `message` field and `key=value` pairs of the other fields.
+    #[derive(Default)]
+    struct Fields {
+        message: String,
+        fields: String,
+    }
+
+    impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            if field.name() == "message" {
+                let _ = write!(self.message, "{value:?}");
+                return;
+            }
+            if !self.fields.is_empty() {
+                self.fields.push(' ');
+            }
+            let _ = write!(self.fields, "{}={value:?}", field.name());
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn write_line(level: &Level, line: &str) {
+        let line = wasm_bindgen::JsValue::from_str(line);
+        match *level {
+            Level::ERROR => web_sys::console::error_1(&line),
+            Level::WARN => web_sys::console::warn_1(&line),
+            Level::INFO => web_sys::console::info_1(&line),
+            _ => web_sys::console::debug_1(&line),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn write_line(level: &Level, line: &str) {
+        eprintln!("{level:>5} {line}");
+    }
+}
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 
+pub type Vec2 = [f64; 2];
 pub type Vec3 = [f64; 3];
 
 /// GeoJSON FeatureCollection. Uses `serde_json::Value` for maximum flexibility
@@ -81,7 +82,7 @@ pub struct Project {
 }
 
 /// Camera state returned by `camera.get_current()`.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CameraState {
     pub position: Vec3,
     pub target: Vec3,
@@ -233,18 +234,129 @@ pub struct Line {
     pub points: Vec<Vec3>,
 }
 
-/// Embedded view state.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// How the embedded view is currently shown.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmbeddedViewMode {
+    Open,
+    Collapsed,
+    Floating,
+    Closed,
+    /// Any other state reported by the host.
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl EmbeddedViewMode {
+    /// Whether the view's content is on screen.
+    pub fn is_visible(&self) -> bool {
+        matches!(self, EmbeddedViewMode::Open | EmbeddedViewMode::Floating)
+    }
+}
+
+/// Size of the embedded view, in CSS pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ViewSize {
+    pub width: f64,
+    pub height: f64,
+}
+
+/// Embedded view state. Fields the host leaves out are `None`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct EmbeddedViewState {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<EmbeddedViewMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<ViewSize>,
+    /// Fields not modelled above.
     #[serde(flatten)]
-    pub data: serde_json::Value,
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
-/// Locale update event.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Locale update event. Fields the host leaves out are `None`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct LocaleUpdate {
+    /// BCP 47 language tag, e.g. `"en-US"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+    #[serde(
+        rename = "numberFormat",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub number_format: Option<NumberFormat>,
+    #[serde(
+        rename = "dateFormat",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub date_format: Option<DateFormat>,
+    /// Fields not modelled above.
     #[serde(flatten)]
-    pub data: serde_json::Value,
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl LocaleUpdate {
+    /// Primary language subtag (`"en"` for `"en-US"`), if a locale is set.
+    pub fn language(&self) -> Option<&str> {
+        self.locale
+            .as_deref()?
+            .split(['-', '_'])
+            .next()
+            .filter(|tag| !tag.is_empty())
+    }
+
+    /// Region subtag (`"US"` for `"en-US"`), if any.
+    pub fn region(&self) -> Option<&str> {
+        self.locale
+            .as_deref()?
+            .split(['-', '_'])
+            .skip(1)
+            .find(|tag| {
+                tag.len() == 2 || (tag.len() == 3 && tag.chars().all(|c| c.is_ascii_digit()))
+            })
+    }
+}
+
+/// How the user's locale writes numbers.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NumberFormat {
+    #[serde(
+        rename = "decimalSeparator",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub decimal_separator: Option<String>,
+    #[serde(
+        rename = "groupSeparator",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub group_separator: Option<String>,
+    /// Fields not modelled above.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// How the user's locale writes dates and times.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DateFormat {
+    /// Date pattern, e.g. `"MM/dd/yyyy"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    /// Whether times use a 24-hour clock.
+    #[serde(rename = "hour24", default, skip_serializing_if = "Option::is_none")]
+    pub hour24: Option<bool>,
+    /// First day of the week, 0 for Sunday to 6 for Saturday.
+    #[serde(
+        rename = "firstDayOfWeek",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub first_day_of_week: Option<u8>,
+    /// Fields not modelled above.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 /// Floating panel options.
@@ -256,10 +368,18 @@ pub struct FloatingPanelOptions {
 }
 
 /// Close embedded view options.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct CloseEmbeddedViewOptions {
+    /// View to close; defaults to the calling view.
+    #[serde(
+        rename = "embeddedViewId",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub embedded_view_id: Option<String>,
+    /// Fields not modelled above.
     #[serde(flatten)]
-    pub data: serde_json::Value,
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 /// Create message port options.
@@ -269,11 +389,28 @@ pub struct CreateMessagePortOptions {
     pub receiver_id: String,
 }
 
-/// SDK configuration.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// SDK configuration. Unset fields are read from the embedded view's URL
+/// query parameters, as set by Forma; override them for local development.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct SdkConfig {
+    /// Origin of the Forma host window (`"https://app.autodeskforma.eu"`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
+    #[serde(
+        rename = "extensionId",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub extension_id: Option<String>,
+    #[serde(
+        rename = "embeddedViewId",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub embedded_view_id: Option<String>,
+    /// Fields not modelled above.
     #[serde(flatten)]
-    pub data: serde_json::Value,
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 /// GeoJSON render request.
@@ -370,6 +507,116 @@ pub struct GetElementByPathRequest {
     pub path: String,
 }
 
+/// Result of `elements.get` / `elements.getByPath`: the requested element and
+/// every element in its hierarchy, keyed by URN.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ElementResponse {
+    pub element: Element,
+    #[serde(default)]
+    pub elements: std::collections::BTreeMap<String, Element>,
+}
+
+impl ElementResponse {
+    /// Look up an element of the hierarchy by URN.
+    pub fn get(&self, urn: &str) -> Option<&Element> {
+        if self.element.urn == urn {
+            return Some(&self.element);
+        }
+        self.elements.get(urn)
+    }
+
+    /// The children of `element` that are part of this response, with the
+    /// child references pointing to them.
+    pub fn children<'a>(
+        &'a self,
+        element: &'a Element,
+    ) -> impl Iterator<Item = (&'a ElementChild, &'a Element)> + 'a {
+        element
+            .children
+            .iter()
+            .filter_map(move |child| Some((child, self.get(&child.urn)?)))
+    }
+}
+
+/// An element in a Forma element system.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Element {
+    pub urn: String,
+    #[serde(default)]
+    pub properties: ElementProperties,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<ElementChild>,
+    #[serde(default, skip_serializing_if = "ElementRepresentations::is_empty")]
+    pub representations: ElementRepresentations,
+    /// Fields not modelled above (e.g. `metadata`).
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Properties of an element.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ElementProperties {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// All other properties, including custom ones set with `editProperties`.
+    #[serde(flatten)]
+    pub custom: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Reference from an element to one of its children.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ElementChild {
+    /// Key of the child within its parent, used to build paths.
+    pub key: String,
+    pub urn: String,
+    /// Transform of the child relative to its parent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transform: Option<Transform>,
+}
+
+/// Representations attached to an element.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ElementRepresentations {
+    #[serde(rename = "volumeMesh", skip_serializing_if = "Option::is_none")]
+    pub volume_mesh: Option<RepresentationRef>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub footprint: Option<RepresentationRef>,
+    #[serde(rename = "grossFloorArea", skip_serializing_if = "Option::is_none")]
+    pub gross_floor_area: Option<RepresentationRef>,
+    #[serde(rename = "graphBuilding", skip_serializing_if = "Option::is_none")]
+    pub graph_building: Option<RepresentationRef>,
+    /// Representations not modelled above.
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ElementRepresentations {
+    pub fn is_empty(&self) -> bool {
+        self.volume_mesh.is_none()
+            && self.footprint.is_none()
+            && self.gross_floor_area.is_none()
+            && self.graph_building.is_none()
+            && self.other.is_empty()
+    }
+}
+
+/// Reference to a representation, either linked by id or embedded as data.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RepresentationRef {
+    /// Storage kind, e.g. `"linked"` or `"embedded-json"`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+    /// Fields not modelled above.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
 /// Get world transform request.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetWorldTransformRequest {
@@ -448,11 +695,111 @@ pub struct StorageDeleteObjectRequest {
     pub key: String,
 }
 
+/// A building to create from a stack of floors, bottom floor first. Sent as
+/// a [`CreateFromFloorsRequest`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FloorStack {
+    pub floors: Vec<Floor>,
+}
+
+/// One storey of a [`FloorStack`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Floor {
+    /// Closed, counter-clockwise outline in project coordinates.
+    pub polygon: Vec<Vec2>,
+    /// Storey height, in metres.
+    pub height: f64,
+    /// Building function of the whole floor (`"residential"`, ...).
+    pub function_id: Option<String>,
+    /// Units dividing the floor. When present the floor is sent as a floor
+    /// plan built from the units, which must lie within `polygon`.
+    pub units: Vec<FloorUnit>,
+}
+
+/// A unit of a [`Floor`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FloorUnit {
+    /// Closed, counter-clockwise outline in project coordinates.
+    pub polygon: Vec<Vec2>,
+    pub program: Option<UnitProgram>,
+    pub function_id: Option<String>,
+}
+
+/// Use of a floor plan unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum UnitProgram {
+    Core,
+    Corridor,
+    LivingUnit,
+    Parking,
+}
+
 /// Create floor stack request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CreateFromFloorsRequest {
+    pub floors: Vec<FloorStackFloor>,
+    /// Plans referenced by [`FloorStackFloor::Plan`] floors.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub plans: Vec<FloorPlan>,
+    /// Fields not modelled above.
     #[serde(flatten)]
-    pub data: serde_json::Value,
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// A floor of a [`CreateFromFloorsRequest`]: either an extruded outline or a
+/// reference to a [`FloorPlan`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FloorStackFloor {
+    Polygon {
+        polygon: Vec<Vec2>,
+        height: f64,
+        #[serde(
+            rename = "functionId",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        function_id: Option<String>,
+    },
+    Plan {
+        #[serde(rename = "planId")]
+        plan_id: String,
+        height: f64,
+    },
+}
+
+/// Floor layout shared by the floors referencing its `id`. Units refer to
+/// vertices by id, so adjacent units share their common corners.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FloorPlan {
+    pub id: String,
+    pub vertices: Vec<FloorPlanVertex>,
+    pub units: Vec<FloorPlanUnit>,
+}
+
+/// Floor plan vertex.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FloorPlanVertex {
+    pub id: String,
+    pub x: f64,
+    pub y: f64,
+}
+
+/// Floor plan unit, as rings of vertex ids (not repeating the first id).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FloorPlanUnit {
+    pub polygon: Vec<String>,
+    #[serde(default)]
+    pub holes: Vec<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub program: Option<UnitProgram>,
+    #[serde(
+        rename = "functionId",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub function_id: Option<String>,
 }
 
 /// Proposal add element request.
@@ -496,10 +843,51 @@ pub struct ProposalUpdateElementsRequest {
 }
 
 /// Proposal subscribe options.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ProposalSubscribeOptions {
+    /// Only notify once changes are persisted, instead of on every edit.
+    #[serde(
+        rename = "debouncePersistedOnly",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub debounce_persisted_only: Option<bool>,
+}
+
+/// A proposal of the current project.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProposalInfo {
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    /// URN of the root element, including its revision.
+    #[serde(rename = "rootUrn")]
+    pub root_urn: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+    /// Creation time in milliseconds since the Unix epoch.
+    #[serde(rename = "createdAt", default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<f64>,
+    /// Last modification time in milliseconds since the Unix epoch.
+    #[serde(rename = "updatedAt", default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<f64>,
+    /// User who created the proposal.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// Fields not modelled above.
     #[serde(flatten)]
-    pub data: serde_json::Value,
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Element paths that differ between two revisions of a proposal.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProposalDiff {
+    #[serde(default)]
+    pub added: Vec<String>,
+    /// Paths whose element URN changed.
+    #[serde(default)]
+    pub replaced: Vec<String>,
+    #[serde(default)]
+    pub removed: Vec<String>,
 }
 
 /// Proposal get request.
@@ -543,17 +931,25 @@ pub struct ProposalSwitchRequest {
 }
 
 /// Analysis list request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct AnalysisListRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub r#type: Option<String>,
 }
 
-/// Trigger noise analysis request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Trigger noise analysis request. Traffic data is taken from the roads and
+/// railways in the proposal.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TriggerNoiseRequest {
+    /// Height above terrain at which noise is calculated, in metres.
+    #[serde(rename = "calculationHeight", skip_serializing_if = "Option::is_none")]
+    pub calculation_height: Option<f64>,
+    /// Cell size of the result grid, in metres.
+    #[serde(rename = "gridResolution", skip_serializing_if = "Option::is_none")]
+    pub grid_resolution: Option<f64>,
+    /// Additional settings passed to the host as is.
     #[serde(flatten)]
-    pub data: serde_json::Value,
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 /// Trigger sun analysis request.
@@ -562,6 +958,74 @@ pub struct TriggerSunRequest {
     pub date: String,
 }
 
+/// Kind of analysis.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnalysisType {
+    Sun,
+    Noise,
+    /// Any other analysis type (wind, daylight, ...).
+    #[serde(untagged)]
+    Other(String),
+}
+
+/// Lifecycle state of an analysis job.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AnalysisStatus {
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+    /// Any other status (cancelled, aborted, ...).
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl AnalysisStatus {
+    /// Whether the job has finished. Statuses other than pending and running
+    /// count as finished.
+    pub fn is_done(&self) -> bool {
+        !matches!(self, AnalysisStatus::Pending | AnalysisStatus::Running)
+    }
+}
+
+/// An analysis connected to a proposal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnalysisRecord {
+    #[serde(rename = "analysisId")]
+    pub id: String,
+    #[serde(rename = "analysisType")]
+    pub analysis_type: AnalysisType,
+    /// Job state, when reported by the host.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<AnalysisStatus>,
+    #[serde(
+        rename = "proposalId",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub proposal_id: Option<String>,
+    #[serde(
+        rename = "proposalRevision",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub proposal_revision: Option<String>,
+    /// Creation time in milliseconds since the Unix epoch.
+    #[serde(rename = "createdAt", default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<f64>,
+    /// Completion between 0 and 1, when reported by the host.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progress: Option<f64>,
+    /// Settings the analysis was triggered with (date, noise settings, ...).
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    pub parameters: serde_json::Value,
+    /// Fields not modelled above.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
 /// Get analysis request (by id).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetAnalysisRequest {
@@ -581,34 +1045,304 @@ pub struct AreaMetricsCalculateRequest {
     pub paths: Option<Vec<String>>,
 }
 
+/// Result of `areaMetrics.calculate`. Areas are reported in square metres.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AreaMetrics {
+    #[serde(rename = "builtInMetrics", default)]
+    pub built_in_metrics: BuiltInMetrics,
+    #[serde(
+        rename = "customMetrics",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub custom_metrics: Vec<serde_json::Value>,
+    /// Values per element path, when reported by the host.
+    #[serde(rename = "pathMetrics", default, skip_serializing_if = "Vec::is_empty")]
+    pub path_metrics: Vec<PathAreaMetrics>,
+    /// Unit of the areas in `path_metrics` after
+    /// [`in_unit_system`](Self::in_unit_system); `None` while they are as
+    /// reported by the host.
+    #[serde(skip)]
+    pub path_unit: Option<String>,
+    /// Fields not modelled above.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Metrics calculated by Forma for every proposal.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BuiltInMetrics {
+    #[serde(rename = "grossFloorArea", skip_serializing_if = "Option::is_none")]
+    pub gross_floor_area: Option<Metric>,
+    #[serde(rename = "netFloorArea", skip_serializing_if = "Option::is_none")]
+    pub net_floor_area: Option<Metric>,
+    #[serde(rename = "siteArea", skip_serializing_if = "Option::is_none")]
+    pub site_area: Option<Metric>,
+    /// Built-up share of the site area.
+    #[serde(rename = "siteCoverage", skip_serializing_if = "Option::is_none")]
+    pub site_coverage: Option<Metric>,
+    #[serde(rename = "floorAreaRatio", skip_serializing_if = "Option::is_none")]
+    pub floor_area_ratio: Option<Metric>,
+    /// Metrics not modelled above.
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+/// A metric value with its unit and breakdown by function.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Metric {
+    pub value: MetricValue,
+    /// Unit of `value`, e.g. `"m²"`; absent for ratios.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    #[serde(
+        rename = "functionBreakdown",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub function_breakdown: Vec<FunctionMetric>,
+}
+
+/// Share of a metric attributed to one building function.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FunctionMetric {
+    #[serde(rename = "functionId")]
+    pub function_id: String,
+    #[serde(
+        rename = "functionName",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub function_name: Option<String>,
+    pub value: MetricValue,
+}
+
+/// A metric value, or the marker the host sends when it cannot be calculated.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MetricValue {
+    Value(f64),
+    /// Typically `"UNABLE_TO_CALCULATE"`.
+    Unavailable(String),
+}
+
+/// Area metrics of a single element.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PathAreaMetrics {
+    pub path: String,
+    #[serde(
+        rename = "functionId",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub function_id: Option<String>,
+    /// Floor index, 0 for the ground floor.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub floor: Option<i32>,
+    #[serde(rename = "grossFloorArea")]
+    pub gross_floor_area: MetricValue,
+    #[serde(
+        rename = "netFloorArea",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub net_floor_area: Option<MetricValue>,
+}
+
+/// Wind statistics at a site: for each direction, the frequency of each
+/// speed bin.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WindRose {
+    pub data: Vec<WindRoseSector>,
+    /// Height above ground the speeds were measured at, in metres.
+    pub height: f64,
+}
+
+/// One direction of a [`WindRose`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WindRoseSector {
+    /// Direction the wind blows from, in degrees clockwise from north.
+    pub direction: f64,
+    /// Upper bound of each speed bin, in m/s.
+    pub speed: Vec<f64>,
+    /// Fraction of the time the wind blows from this direction at each
+    /// speed bin, parallel to `speed`.
+    pub frequency: Vec<f64>,
+}
+
+/// Wind parameters Forma uses for the project location.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WindParameters {
+    #[serde(rename = "windRose")]
+    pub wind_rose: WindRose,
+    /// Aerodynamic roughness length of the surrounding terrain, in metres.
+    pub roughness: f64,
+    /// Fields not modelled above.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Kind of wind prediction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WindPredictionType {
+    /// Wind speed exceeded 5% of the time, for pedestrian comfort.
+    Comfort,
+    /// Mean wind speed.
+    Statistical,
+}
+
 /// Predict wind request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PredictWindRequest {
-    #[serde(flatten)]
-    pub data: serde_json::Value,
+    pub r#type: WindPredictionType,
+    /// Aerodynamic roughness length of the surrounding terrain, in metres.
+    pub roughness: f64,
+    #[serde(rename = "windRose")]
+    pub wind_rose: WindRose,
+    /// Polygon limiting the prediction, in project coordinates. The whole
+    /// proposal is analysed if unset.
+    #[serde(rename = "areaOfInterest", skip_serializing_if = "Option::is_none")]
+    pub area_of_interest: Option<Vec<Vec2>>,
+}
+
+impl PredictWindRequest {
+    /// Request a prediction of `r#type` with the project's wind parameters.
+    pub fn new(r#type: WindPredictionType, parameters: &WindParameters) -> Self {
+        Self {
+            r#type,
+            roughness: parameters.roughness,
+            wind_rose: parameters.wind_rose.clone(),
+            area_of_interest: None,
+        }
+    }
+}
+
+/// Where a ground texture is draped on the terrain, in project coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GroundTexturePlacement {
+    /// Centre of the texture.
+    pub center: Vec2,
+    /// Width and height covered by the texture, in metres.
+    pub size: Vec2,
+    /// Counter-clockwise rotation around the centre, in degrees.
+    #[serde(default)]
+    pub rotation: f64,
+}
+
+/// An RGBA image, row by row from the top, 4 bytes per pixel.
+#[derive(Clone, PartialEq, Eq)]
+pub struct RgbaImage {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    /// Wrap `pixels`, which must hold exactly `width * height * 4` bytes.
+    /// Fails if either dimension is zero.
+    pub fn new(width: u32, height: u32, pixels: Vec<u8>) -> crate::Result<Self> {
+        if width == 0 || height == 0 {
+            return Err(crate::SdkError::InvalidRequest(crate::ErrorDetails::new(
+                format!("{width}x{height} RGBA image is empty"),
+            )));
+        }
+        let expected = width as usize * height as usize * 4;
+        if pixels.len() != expected {
+            return Err(crate::SdkError::InvalidRequest(crate::ErrorDetails::new(
+                format!(
+                    "{width}x{height} RGBA image needs {expected} bytes, got {}",
+                    pixels.len()
+                ),
+            )));
+        }
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// An image of `width` x `height` pixels of one color. Fails if either
+    /// dimension is zero.
+    pub fn filled(width: u32, height: u32, rgba: [u8; 4]) -> crate::Result<Self> {
+        Self::new(width, height, rgba.repeat(width as usize * height as usize))
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn into_pixels(self) -> Vec<u8> {
+        self.pixels
+    }
+
+    /// Color of pixel `(x, y)`, counted from the top left.
+    pub fn pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        let index = self.index(x, y)?;
+        self.pixels[index..index + 4].try_into().ok()
+    }
+
+    /// Set pixel `(x, y)`, counted from the top left. Out-of-range pixels
+    /// are ignored.
+    pub fn set_pixel(&mut self, x: u32, y: u32, rgba: [u8; 4]) {
+        if let Some(index) = self.index(x, y) {
+            self.pixels[index..index + 4].copy_from_slice(&rgba);
+        }
+    }
+
+    fn index(&self, x: u32, y: u32) -> Option<usize> {
+        (x < self.width && y < self.height)
+            .then(|| (y as usize * self.width as usize + x as usize) * 4)
+    }
+
+    pub(crate) fn to_host(&self) -> crate::host::HostValue {
+        crate::host::HostValue::Image {
+            width: self.width,
+            height: self.height,
+            rgba: self.pixels.clone(),
+        }
+    }
+}
+
+impl std::fmt::Debug for RgbaImage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RgbaImage")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .finish()
+    }
 }
 
 /// Ground texture add request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct GroundTextureAddRequest {
-    #[serde(flatten)]
-    pub data: serde_json::Value,
+    pub id: String,
+    pub image: RgbaImage,
+    pub placement: GroundTexturePlacement,
 }
 
 /// Ground texture update data request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct GroundTextureUpdateDataRequest {
     pub id: String,
-    #[serde(flatten)]
-    pub data: serde_json::Value,
+    pub image: RgbaImage,
 }
 
 /// Ground texture update position request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct GroundTextureUpdatePositionRequest {
     pub id: String,
     #[serde(flatten)]
-    pub data: serde_json::Value,
+    pub placement: GroundTexturePlacement,
 }
 
 /// Ground texture remove request.
@@ -617,25 +1351,128 @@ pub struct GroundTextureRemoveRequest {
     pub id: String,
 }
 
-/// Terrain pad.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A flattened area of the terrain at a fixed elevation, with embankments
+/// sloping down or up to the surrounding terrain.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TerrainPad {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Closed, counter-clockwise ring in project coordinates (first point
+    /// repeated at the end).
+    pub polygon: Vec<Vec2>,
+    /// Elevation of the pad surface, in metres above sea level.
+    pub elevation: f64,
+    /// Angle of the embankments from horizontal, in degrees.
+    #[serde(
+        rename = "slopeAngle",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub slope_angle: Option<f64>,
+    /// Maximum horizontal width of the embankments, in metres.
+    #[serde(
+        rename = "embankmentWidth",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub embankment_width: Option<f64>,
+    /// Fields not modelled above.
     #[serde(flatten)]
-    pub data: serde_json::Value,
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Approximate earthwork needed to grade the terrain to a [`TerrainPad`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CutFill {
+    /// Volume of terrain above the pad to excavate, in cubic metres.
+    pub cut: f64,
+    /// Volume below the pad to fill, in cubic metres.
+    pub fill: f64,
+    /// Area covered by the samples, in square metres.
+    pub area: f64,
+    /// Number of terrain elevations sampled.
+    pub samples: usize,
+}
+
+/// A generator registered in Forma: an extension endpoint that creates
+/// elements from user input.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GeneratorDefinition {
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    /// Authcontext (project or hub id) the generator is registered in.
+    #[serde(default)]
+    pub authcontext: String,
+    /// JSON Schema of the generator's input.
+    #[serde(
+        rename = "inputSchema",
+        default,
+        skip_serializing_if = "serde_json::Value::is_null"
+    )]
+    pub input_schema: serde_json::Value,
+    /// Category of the elements the generator creates (e.g. `"building"`).
+    #[serde(
+        rename = "outputElementType",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub output_element_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<GeneratorEndpoint>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Fields not modelled above.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Extension endpoint that runs a generator.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GeneratorEndpoint {
+    #[serde(rename = "extensionId")]
+    pub extension_id: String,
+    #[serde(rename = "endpointId")]
+    pub endpoint_id: String,
 }
 
 /// Generator put request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct GeneratorPutRequest {
     #[serde(flatten)]
-    pub data: serde_json::Value,
+    pub generator: GeneratorDefinition,
+}
+
+impl From<GeneratorDefinition> for GeneratorPutRequest {
+    fn from(generator: GeneratorDefinition) -> Self {
+        Self { generator }
+    }
 }
 
 /// Generator list request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct GeneratorListRequest {
-    #[serde(flatten)]
-    pub data: serde_json::Value,
+    /// Authcontext to list; the current project if unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authcontext: Option<String>,
+    /// Maximum number of generators per page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    /// `next_token` of the previous page.
+    #[serde(rename = "nextToken", skip_serializing_if = "Option::is_none")]
+    pub next_token: Option<String>,
+}
+
+/// A page of generators.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GeneratorList {
+    #[serde(default)]
+    pub generators: Vec<GeneratorDefinition>,
+    /// Token for the next page, `None` on the last page.
+    #[serde(rename = "nextToken", default, skip_serializing_if = "Option::is_none")]
+    pub next_token: Option<String>,
 }
 
 /// Library create item request.
@@ -659,32 +1496,108 @@ pub struct LibraryDeleteItemRequest {
     pub id: String,
 }
 
+/// Content of an Integrate element (v2). Linked representations refer to
+/// blobs uploaded with `IntegrateApi::upload_file` by id.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ElementV2 {
+    #[serde(default)]
+    pub properties: ElementProperties,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<ElementChild>,
+    #[serde(default, skip_serializing_if = "ElementRepresentations::is_empty")]
+    pub representations: ElementRepresentations,
+}
+
 /// Integrate create element hierarchy request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CreateElementHierarchyRequest {
-    #[serde(flatten)]
-    pub data: serde_json::Value,
+    pub authcontext: String,
+    pub data: ElementHierarchy,
+}
+
+/// Elements of a hierarchy created in one call, by local id.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ElementHierarchy {
+    /// Local id of the root element.
+    #[serde(rename = "rootElement")]
+    pub root_element: String,
+    pub elements: std::collections::BTreeMap<String, HierarchyElement>,
+}
+
+/// Element of an [`ElementHierarchy`], referring to its children by local id.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HierarchyElement {
+    pub id: String,
+    #[serde(default)]
+    pub properties: ElementProperties,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<HierarchyChild>,
+    #[serde(default, skip_serializing_if = "ElementRepresentations::is_empty")]
+    pub representations: ElementRepresentations,
+}
+
+/// Child reference of a [`HierarchyElement`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HierarchyChild {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transform: Option<Transform>,
 }
 
 /// Integrate create element v2 request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CreateElementV2Request {
+    pub authcontext: String,
+    /// URN from `IntegrateApi::create_urn`; the host assigns one if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub urn: Option<String>,
     #[serde(flatten)]
-    pub data: serde_json::Value,
+    pub element: ElementV2,
 }
 
 /// Integrate update element v2 request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct UpdateElementV2Request {
+    pub authcontext: String,
+    pub urn: String,
     #[serde(flatten)]
-    pub data: serde_json::Value,
+    pub element: ElementV2,
 }
 
 /// Integrate batch ingest elements v2 request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BatchIngestElementsV2Request {
+    pub authcontext: String,
+    /// Elements to create or update, children before their parents.
+    pub elements: Vec<IngestElement>,
+}
+
+/// Element of a [`BatchIngestElementsV2Request`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IngestElement {
+    pub urn: String,
     #[serde(flatten)]
-    pub data: serde_json::Value,
+    pub element: ElementV2,
+}
+
+/// An element and its descendants to ingest, before URNs are assigned. Turn
+/// it into a [`BatchIngestElementsV2Request`] with `IntegrateApi::prepare`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ElementNode {
+    /// URN to reuse (to update an existing element); a new one is created
+    /// if unset.
+    pub urn: Option<String>,
+    pub properties: ElementProperties,
+    pub representations: ElementRepresentations,
+    pub children: Vec<ElementNodeChild>,
+}
+
+/// Child of an [`ElementNode`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElementNodeChild {
+    pub key: String,
+    pub transform: Option<Transform>,
+    pub node: ElementNode,
 }
 
 /// Integrate upload file request.
@@ -727,10 +1640,20 @@ pub struct GeoDataUploadRequest {
 }
 
 /// Proposal change event.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ProposalChangeEvent {
+    /// URN of the new root element, including its revision.
+    #[serde(rename = "rootUrn")]
+    pub root_urn: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+    /// Changed paths, when reported by the host. Otherwise empty; compare
+    /// revisions with [`ProposalApi::diff`](crate::proposal::ProposalApi::diff).
     #[serde(flatten)]
-    pub data: serde_json::Value,
+    pub diff: ProposalDiff,
+    /// Fields not modelled above.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 /// Selection change event.
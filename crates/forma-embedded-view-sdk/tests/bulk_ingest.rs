@@ -0,0 +1,245 @@
+use forma_embedded_view_sdk::host::{FormaHost, HostCall, HostFuture, HostSubscribe, HostValue};
+use forma_embedded_view_sdk::integrate::{
+    BulkIngestOptions, IngestOutcome, IngestProgress, IngestReport,
+};
+use forma_embedded_view_sdk::testing::FakeForma;
+use forma_embedded_view_sdk::types::{
+    BatchIngestElementsV2Request, ElementChild, ElementV2, IngestElement,
+};
+use forma_embedded_view_sdk::{EmbeddedViewSdk, Result, SdkError, Subscription};
+use std::cell::Cell;
+use std::future::{poll_fn, Future};
+use std::pin::pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+/// Poll `future` to completion. Fake calls resolve without waiting on
+/// anything external, so busy polling is enough.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+fn urn(name: &str) -> String {
+    format!("urn:adsk-forma-elements:test:pro_1:{name}:1")
+}
+
+fn element(name: &str, children: &[&str]) -> IngestElement {
+    let mut element = ElementV2::default();
+    element.properties.name = Some(name.into());
+    element.children = children
+        .iter()
+        .map(|child| ElementChild {
+            key: child.to_string(),
+            urn: urn(child),
+            transform: None,
+        })
+        .collect();
+    IngestElement {
+        urn: urn(name),
+        element,
+    }
+}
+
+fn batch(elements: Vec<IngestElement>) -> BatchIngestElementsV2Request {
+    BatchIngestElementsV2Request {
+        authcontext: "pro_1".into(),
+        elements,
+    }
+}
+
+/// `root` over leaves `a` and `b`, where `b` refers to `missing`, which is
+/// neither in the batch nor in the scene.
+fn failing_batch() -> BatchIngestElementsV2Request {
+    batch(vec![
+        element("a", &[]),
+        element("b", &["missing"]),
+        element("root", &["a", "b"]),
+    ])
+}
+
+fn one_per_chunk() -> BulkIngestOptions {
+    BulkIngestOptions {
+        max_chunk_elements: 1,
+        ..BulkIngestOptions::default()
+    }
+}
+
+fn ingest(
+    sdk: &EmbeddedViewSdk,
+    request: &BatchIngestElementsV2Request,
+    options: &BulkIngestOptions,
+) -> (Result<IngestReport>, Vec<IngestProgress>) {
+    let mut progress = Vec::new();
+    let report = block_on(
+        sdk.integrate_elements()
+            .bulk_ingest(request, options, |update| progress.push(*update)),
+    );
+    (report, progress)
+}
+
+#[test]
+fn failed_child_chunk_skips_its_parent() {
+    let fake = FakeForma::new();
+    let (report, progress) = ingest(&fake.sdk(), &failing_batch(), &one_per_chunk());
+    let report = report.unwrap();
+
+    assert_eq!(report.outcomes[&urn("a")], IngestOutcome::Ingested);
+    assert!(matches!(
+        report.outcomes[&urn("b")],
+        IngestOutcome::Failed(SdkError::NotFound(_))
+    ));
+    assert_eq!(report.outcomes[&urn("root")], IngestOutcome::Skipped);
+    assert!(!report.is_complete());
+
+    let last = progress.last().unwrap();
+    assert_eq!((last.ingested, last.failed, last.skipped), (1, 1, 1));
+    assert_eq!((last.chunks, last.chunks_done), (3, 3));
+    assert_eq!(progress.len(), 3);
+    // The skipped parent was never sent.
+    assert_eq!(
+        fake.integrate_elements()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>(),
+        vec![urn("a")]
+    );
+}
+
+#[test]
+fn remaining_batch_finishes_the_ingest() {
+    let fake = FakeForma::new();
+    let sdk = fake.sdk();
+    let request = failing_batch();
+    let (report, _) = ingest(&sdk, &request, &one_per_chunk());
+    let report = report.unwrap();
+
+    let remaining = report.remaining(&request);
+    let urns: Vec<_> = remaining.elements.iter().map(|e| e.urn.clone()).collect();
+    assert_eq!(urns, vec![urn("b"), urn("root")]);
+
+    // Provide the missing child, then send what is left.
+    let (fixed, _) = ingest(
+        &sdk,
+        &batch(vec![element("missing", &[])]),
+        &one_per_chunk(),
+    );
+    assert!(fixed.unwrap().is_complete());
+    let (report, progress) = ingest(&sdk, &remaining, &one_per_chunk());
+    let report = report.unwrap();
+
+    assert!(report.is_complete());
+    assert_eq!(report.outcomes.len(), 2);
+    assert_eq!(progress.last().unwrap().ingested, 2);
+    assert_eq!(fake.integrate_elements().len(), 4);
+}
+
+#[test]
+fn element_larger_than_a_chunk_is_sent_alone() {
+    let fake = FakeForma::new();
+    let mut large = element("large", &[]);
+    large
+        .element
+        .properties
+        .custom
+        .insert("notes".into(), "x".repeat(1000).into());
+    let request = batch(vec![element("a", &[]), element("b", &[]), large]);
+    let options = BulkIngestOptions {
+        max_chunk_bytes: 500,
+        ..BulkIngestOptions::default()
+    };
+    let (report, progress) = ingest(&fake.sdk(), &request, &options);
+
+    assert!(report.unwrap().is_complete());
+    // `a` and `b` share a chunk; `large` exceeds the limit on its own.
+    assert_eq!(progress.last().unwrap().chunks, 2);
+    let sent = fake
+        .calls()
+        .iter()
+        .filter(|call| *call == "integrateElements.batchIngestElementsV2")
+        .count();
+    assert_eq!(sent, 2);
+    assert_eq!(fake.integrate_elements().len(), 3);
+}
+
+/// Host counting the calls in flight, each of which pends once before
+/// reaching the fake.
+struct InFlight {
+    fake: FakeForma,
+    current: Rc<Cell<usize>>,
+    max: Rc<Cell<usize>>,
+}
+
+impl FormaHost for InFlight {
+    fn call(&self, call: HostCall) -> HostFuture<HostValue> {
+        let fake = self.fake.clone();
+        let current = self.current.clone();
+        let max = self.max.clone();
+        Box::pin(async move {
+            current.set(current.get() + 1);
+            max.set(max.get().max(current.get()));
+            let mut yielded = false;
+            poll_fn(|_| {
+                if yielded {
+                    Poll::Ready(())
+                } else {
+                    yielded = true;
+                    Poll::Pending
+                }
+            })
+            .await;
+            let result = fake.call(call).await;
+            current.set(current.get() - 1);
+            result
+        })
+    }
+
+    fn call_sync(&self, call: HostCall) -> Result<HostValue> {
+        self.fake.call_sync(call)
+    }
+
+    fn subscribe(&self, subscribe: HostSubscribe) -> HostFuture<Subscription> {
+        self.fake.subscribe(subscribe)
+    }
+
+    fn subscribe_sync(&self, subscribe: HostSubscribe) -> Result<Subscription> {
+        self.fake.subscribe_sync(subscribe)
+    }
+}
+
+fn max_in_flight(concurrency: usize) -> usize {
+    let host = InFlight {
+        fake: FakeForma::new(),
+        current: Rc::new(Cell::new(0)),
+        max: Rc::new(Cell::new(0)),
+    };
+    let max = host.max.clone();
+    let fake = host.fake.clone();
+    let leaves = ["a", "b", "c", "d", "e", "f"];
+    let mut elements: Vec<_> = leaves.iter().map(|leaf| element(leaf, &[])).collect();
+    elements.push(element("root", &leaves));
+    let options = BulkIngestOptions {
+        concurrency,
+        ..one_per_chunk()
+    };
+    let (report, _) = ingest(
+        &EmbeddedViewSdk::with_host(host),
+        &batch(elements),
+        &options,
+    );
+
+    assert!(report.unwrap().is_complete());
+    assert_eq!(fake.integrate_elements().len(), 7);
+    max.get()
+}
+
+#[test]
+fn concurrency_one_sends_chunks_one_at_a_time() {
+    assert_eq!(max_in_flight(1), 1);
+    assert_eq!(max_in_flight(3), 3);
+}